@@ -0,0 +1,225 @@
+use super::geometry::{Point, PointID, Polygon, PolygonID, Shape, ShapeID};
+use super::gwrapper::{GWrapper, ThingID, Visibility, DEFAULT_FILL};
+
+/// Adds objects to a GWrapper, hiding everything except what's explicitly shown
+struct Builder<'a> {
+    ctx: &'a mut GWrapper,
+}
+
+impl<'a> Builder<'a> {
+    fn circle(&mut self, center: PointID, through: PointID) -> ShapeID {
+        let id = self.ctx.geometry.add_shape(Shape::Circle(center, through));
+        self.ctx
+            .visibility
+            .insert(ThingID::ShapeID(id), Visibility::Hidden);
+        id
+    }
+
+    fn line(&mut self, a: PointID, b: PointID) -> ShapeID {
+        let id = self.ctx.geometry.add_shape(Shape::Line(a, b));
+        self.ctx
+            .visibility
+            .insert(ThingID::ShapeID(id), Visibility::Hidden);
+        id
+    }
+
+    /// Adds the intersection of `a` and `b` with the lowest score. Since the order of two
+    /// intersections is stable as the inputs move, the choice stays the same as the
+    /// construction is dragged around
+    fn intersection<F: Fn((f64, f64)) -> f64>(
+        &mut self,
+        a: ShapeID,
+        b: ShapeID,
+        score: F,
+    ) -> Option<PointID> {
+        let mut best: Option<(f64, Point)> = None;
+        for point in &[Point::PrimIntersection(a, b), Point::SecIntersection(a, b)] {
+            if let Some(pos) = self.ctx.geometry.resolve_point(point) {
+                let point_score = score(pos);
+                if best
+                    .map(|(best_score, _)| point_score < best_score)
+                    .unwrap_or(true)
+                {
+                    best = Some((point_score, *point));
+                }
+            }
+        }
+
+        let id = self.ctx.geometry.add_point(best?.1);
+        self.ctx
+            .visibility
+            .insert(ThingID::PointID(id), Visibility::Hidden);
+        Some(id)
+    }
+
+    fn pos(&self, id: PointID) -> Option<(f64, f64)> {
+        self.ctx
+            .geometry
+            .resolve_point(self.ctx.geometry.points.get(&id)?)
+    }
+
+    fn show(&mut self, id: PointID) {
+        self.ctx.visibility.remove(&ThingID::PointID(id));
+    }
+}
+
+/// Whether `regular_polygon` can construct an n-gon, which is the case for 3, 4, 5 and 6
+/// times a power of two
+pub fn is_supported(n: usize) -> bool {
+    let mut n = n;
+    while n > 6 && n.is_multiple_of(2) {
+        n /= 2;
+    }
+    (3..=6).contains(&n)
+}
+
+/// Constructs a regular n-gon with the side `a`-`b`, to the left of the line from `a` to `b`,
+/// using only circles and lines. All auxiliary objects are hidden.
+pub fn regular_polygon(ctx: &mut GWrapper, a: PointID, b: PointID, n: usize) -> Option<PolygonID> {
+    if !is_supported(n) {
+        return None;
+    }
+
+    let mut builder = Builder { ctx };
+
+    let (a_pos, b_pos) = (builder.pos(a)?, builder.pos(b)?);
+    if a_pos == b_pos {
+        return None;
+    }
+
+    // How far to the left of a -> b a point is, relative to |ab|^2
+    let side = move |p: (f64, f64)| {
+        let (dx, dy) = (b_pos.0 - a_pos.0, b_pos.1 - a_pos.1);
+        (dx * (p.1 - a_pos.1) - dy * (p.0 - a_pos.0)) / (dx * dx + dy * dy)
+    };
+    let dist = |from: (f64, f64)| {
+        move |p: (f64, f64)| (p.0 - from.0) * (p.0 - from.0) + (p.1 - from.1) * (p.1 - from.1)
+    };
+
+    let around_a = builder.circle(a, b);
+    let around_b = builder.circle(b, a);
+    // Apexes of the equilateral triangles on ab, inside and outside of the polygon
+    let apex = builder.intersection(around_a, around_b, |p| -side(p))?;
+    let apex_out = builder.intersection(around_a, around_b, side)?;
+    let bisector = builder.line(apex_out, apex);
+
+    if n == 3 {
+        builder.show(apex);
+        return Some(finish(builder.ctx, vec![a, b, apex]));
+    }
+
+    let mut base = n;
+    while base > 6 {
+        base /= 2;
+    }
+
+    let mut third = None;
+    let mut center = if base == 6 {
+        apex
+    } else {
+        // Thales' theorem: the center of the square sees ab at a right angle
+        let ab = builder.line(a, b);
+        let mid = builder.intersection(ab, bisector, |_| 0.)?;
+        let thales = builder.circle(mid, a);
+        let square_center = builder.intersection(thales, bisector, |p| -side(p))?;
+        if base == 4 {
+            square_center
+        } else {
+            // The diagonal of the pentagon is (1 + √5) / 2 |ab|, and the far vertices of the
+            // square on ab are √5 / 2 |ab| from the midpoint of ab
+            let square = builder.circle(square_center, a);
+            let square_diagonal = builder.line(a, square_center);
+            let square_vertex =
+                builder.intersection(square, square_diagonal, |p| -dist(a_pos)(p))?;
+            let golden = builder.circle(mid, square_vertex);
+            let diagonal_end = builder.intersection(golden, ab, |p| -dist(a_pos)(p))?;
+            let diagonal = builder.circle(a, diagonal_end);
+            let c = builder.intersection(diagonal, around_b, |p| -side(p))?;
+            third = Some(c);
+
+            // The center is on the perpendicular bisector of bc
+            let around_b_c = builder.circle(b, c);
+            let around_c = builder.circle(c, b);
+            let bc_first = builder.intersection(around_b_c, around_c, side)?;
+            let bc_second = builder.intersection(around_b_c, around_c, |p| -side(p))?;
+            let bc_bisector = builder.line(bc_first, bc_second);
+            builder.intersection(bc_bisector, bisector, |_| 0.)?
+        }
+    };
+
+    // The center of the 2n-gon on ab is on the circumcircle of the n-gon, since it sees ab at
+    // half the angle
+    let mut sides = base;
+    while sides < n {
+        let circumcircle = builder.circle(center, a);
+        center = builder.intersection(circumcircle, bisector, |p| -side(p))?;
+        sides *= 2;
+        third = None;
+    }
+
+    let circumcircle = builder.circle(center, a);
+    let mut vertices = vec![a, b];
+    vertices.extend(third);
+    while vertices.len() < n {
+        let (before, last) = (vertices[vertices.len() - 2], vertices[vertices.len() - 1]);
+        let before_pos = builder.pos(before)?;
+        let step = builder.circle(last, before);
+        let next = builder.intersection(step, circumcircle, |p| -dist(before_pos)(p))?;
+        vertices.push(next);
+    }
+
+    for vertex in &vertices[2..] {
+        builder.show(*vertex);
+    }
+    Some(finish(builder.ctx, vertices))
+}
+
+fn finish(ctx: &mut GWrapper, vertices: Vec<PointID>) -> PolygonID {
+    let id = ctx.geometry.add_polygon(Polygon(vertices));
+    ctx.style_mut(ThingID::PolygonID(id)).fill = Some(DEFAULT_FILL);
+    id
+}
+
+#[test]
+fn test_regular_polygon() {
+    use super::geometry::{create_arbitrary, Geometry};
+
+    fn is_regular(ctx: &GWrapper, id: PolygonID, n: usize, side: f64) -> bool {
+        let vertices = ctx.resolve_polygon(&ctx.polygons[&id]).unwrap();
+        let angle = 2. * ::std::f64::consts::PI / n as f64;
+        vertices.len() == n
+            && (0..n).all(|i| {
+                let (a, b, c) = (vertices[i], vertices[(i + 1) % n], vertices[(i + 2) % n]);
+                let turn = (b.1 - a.1).atan2(b.0 - a.0) + angle - (c.1 - b.1).atan2(c.0 - b.0);
+                let len = ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt();
+                (len - side).abs() < 1e-6 && turn.sin().abs() < 1e-6 && turn.cos() > 0.
+            })
+    }
+
+    for &n in &[3, 4, 5, 6, 8, 10, 12, 16, 20, 24] {
+        let mut ctx = GWrapper::new(Geometry::new());
+        let a = ctx.add_point(create_arbitrary((0., 0.)));
+        let b = ctx.add_point(create_arbitrary((1., 0.)));
+        let id = regular_polygon(&mut ctx, a, b, n).unwrap();
+        assert!(is_regular(&ctx, id, n, 1.), "{}-gon", n);
+
+        // The construction follows its side around
+        ctx.points.insert(b, create_arbitrary((-1., 2.)));
+        ctx.invalidate_cache();
+        assert!(is_regular(&ctx, id, n, 5f64.sqrt()), "moved {}-gon", n);
+
+        let shown = ctx
+            .points
+            .keys()
+            .filter(|id| !ctx.visibility.contains_key(&ThingID::PointID(**id)))
+            .count();
+        assert_eq!(shown, n);
+    }
+
+    let mut ctx = GWrapper::new(Geometry::new());
+    let a = ctx.add_point(create_arbitrary((0., 0.)));
+    let b = ctx.add_point(create_arbitrary((1., 0.)));
+    assert!(!is_supported(7) && !is_supported(15) && is_supported(40));
+    assert_eq!(regular_polygon(&mut ctx, a, b, 7), None);
+    assert_eq!(regular_polygon(&mut ctx, a, a, 4), None);
+}
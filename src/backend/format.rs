@@ -0,0 +1,742 @@
+// A plain text format for constructions. Every line defines one named object:
+//
+//     A = point 0 0
+//     B = point 4 1
+//     c = circle A B
+//     l = line A B
+//     P = intersection c l
+//     Q = intersection2 c l
+//     hidden t = polygon A B P
+//     L = locus P A c
+//     a = slider 0 360 1 45
+//     R = oncircle c a
+//     T = online l a
+//     S = reflect P l
+//     d = dilate c A 0.5
+//     style c color 200 40 40 width 2 dash 8 4
+//
+// `intersection` and `intersection2` are the primary and secondary intersection of two shapes.
+// `locus P A c` is the path of P as A moves along c.
+// `slider min max step value` is a number parameter, and `oncircle c a` is the point on c at
+// the angle a in degrees. `online l a` is the point on l at a, which is 0 at the first point l
+// is through and 1 at the second.
+// The image of a point or shape X is `reflect X l`, `rotate X O A V B` (around O by the angle
+// AVB), `translate X A B`, `dilate X O k` (k being a number or a slider) or `invert X c`.
+// Objects prefixed with `hidden` are hidden. Everything after a # is a comment.
+//
+// Copied objects are written the same way. Points they are constructed from that weren't copied
+// are written where they were, as `input` points, e.g. `input A = point 0 0`. Other objects they
+// need are written hidden.
+//
+// A `style` line changes how an object is drawn. Its parts are `color r g b|default`, `width w`,
+// `dash lengths...`, `point circle|square|cross`, `size r` and `fill r g b opacity|none`,
+// everything left out staying as it was.
+
+use std::collections::{HashMap, HashSet};
+
+use super::geometry::{
+    self, Locus, Point, PointID, Polygon, Shape, ShapeID, Slider, SliderID, Transformation,
+};
+use super::gwrapper::{Fill, GWrapper, PointShape, Style, ThingID, Visibility};
+use super::protocol;
+
+const POINT_SHAPES: &[(&str, PointShape)] = &[
+    ("circle", PointShape::Circle),
+    ("square", PointShape::Square),
+    ("cross", PointShape::Cross),
+];
+
+/// Reads construction lines into a GWrapper, keeping track of the names of the objects
+pub struct Reader {
+    pub names: HashMap<String, ThingID>,
+    /// Sliders aren't objects on the plane, so they have their own names
+    pub sliders: HashMap<String, SliderID>,
+    /// The points read as inputs, in order
+    pub inputs: Vec<PointID>,
+}
+
+impl Reader {
+    pub fn new() -> Reader {
+        Reader {
+            names: HashMap::new(),
+            sliders: HashMap::new(),
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn point(&self, name: &str) -> Result<PointID, String> {
+        match self.names.get(name) {
+            Some(ThingID::PointID(id)) => Ok(*id),
+            Some(_) => Err(format!("`{}` is not a point", name)),
+            None => Err(format!("Unknown object `{}`", name)),
+        }
+    }
+
+    pub fn shape(&self, name: &str) -> Result<ShapeID, String> {
+        match self.names.get(name) {
+            Some(ThingID::ShapeID(id)) => Ok(*id),
+            Some(_) => Err(format!("`{}` is not a shape", name)),
+            None => Err(format!("Unknown object `{}`", name)),
+        }
+    }
+
+    pub fn thing(&self, name: &str) -> Result<ThingID, String> {
+        match self.names.get(name) {
+            Some(id) => Ok(*id),
+            None => Err(format!("Unknown object `{}`", name)),
+        }
+    }
+
+    pub fn slider(&self, name: &str) -> Result<SliderID, String> {
+        match self.sliders.get(name) {
+            Some(id) => Ok(*id),
+            None if self.names.contains_key(name) => Err(format!("`{}` is not a slider", name)),
+            None => Err(format!("Unknown object `{}`", name)),
+        }
+    }
+
+    /// Reads one line, which may be empty or a comment
+    pub fn read_line(&mut self, ctx: &mut GWrapper, line: &str) -> Result<(), String> {
+        let words = split_line(line);
+        if words.is_empty() {
+            return Ok(());
+        }
+
+        if words[0] == "style" {
+            if words.len() < 2 {
+                return Err("Expected `style name ...`".into());
+            }
+            let id = self.thing(words[1])?;
+            return read_style(ctx.style_mut(id), &words[2..]);
+        }
+
+        let (mut hidden, mut input) = (false, false);
+        let mut words = &words[..];
+        while let Some(&prefix) = words.first() {
+            match prefix {
+                "hidden" => hidden = true,
+                "input" => input = true,
+                _ => break,
+            }
+            words = &words[1..];
+        }
+
+        if words.len() < 3 || words[1] != "=" {
+            return Err(format!("Expected `name = kind ...`, got `{}`", line.trim()));
+        }
+        let (name, kind, args) = (words[0], words[2], &words[3..]);
+        if self.names.contains_key(name) || self.sliders.contains_key(name) {
+            return Err(format!("`{}` is defined twice", name));
+        }
+
+        let expect_args = |n: usize| {
+            if args.len() == n {
+                Ok(())
+            } else {
+                Err(format!(
+                    "`{}` takes {} arguments, got {}",
+                    kind,
+                    n,
+                    args.len()
+                ))
+            }
+        };
+
+        let numbers = || {
+            args.iter()
+                .map(|arg| match arg.parse::<f64>() {
+                    Ok(x) if x.is_finite() => Ok(x),
+                    _ => Err(format!("Invalid number `{}`", arg)),
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        let id = match kind {
+            "slider" => {
+                expect_args(4)?;
+                let values = numbers()?;
+                if hidden {
+                    return Err("Sliders can't be hidden".into());
+                }
+                let slider = Slider::new(name, values[0], values[1], values[2], values[3]);
+                let id = ctx.geometry.add_slider(slider);
+                self.sliders.insert(name.to_string(), id);
+                return Ok(());
+            }
+            "point" => {
+                expect_args(2)?;
+                let pos = numbers()?;
+                ThingID::PointID(
+                    ctx.geometry
+                        .add_point(geometry::create_arbitrary((pos[0], pos[1]))),
+                )
+            }
+            "circle" | "line" => {
+                expect_args(2)?;
+                let (a, b) = (self.point(args[0])?, self.point(args[1])?);
+                let shape = if kind == "circle" {
+                    Shape::Circle(a, b)
+                } else {
+                    Shape::Line(a, b)
+                };
+                ThingID::ShapeID(ctx.geometry.add_shape(shape))
+            }
+            "intersection" | "intersection2" => {
+                expect_args(2)?;
+                let (a, b) = (self.shape(args[0])?, self.shape(args[1])?);
+                let point = if kind == "intersection" {
+                    Point::PrimIntersection(a, b)
+                } else {
+                    Point::SecIntersection(a, b)
+                };
+                ThingID::PointID(ctx.geometry.add_point(point))
+            }
+            "oncircle" => {
+                expect_args(2)?;
+                let point = Point::OnCircle(self.shape(args[0])?, self.slider(args[1])?);
+                ThingID::PointID(ctx.geometry.add_point(point))
+            }
+            "online" => {
+                expect_args(2)?;
+                let point = Point::OnLine(self.shape(args[0])?, self.slider(args[1])?);
+                ThingID::PointID(ctx.geometry.add_point(point))
+            }
+            "reflect" | "rotate" | "translate" | "dilate" | "invert" => {
+                let transformation = match kind {
+                    "reflect" => {
+                        expect_args(2)?;
+                        Transformation::Reflect(self.shape(args[1])?)
+                    }
+                    "rotate" => {
+                        expect_args(5)?;
+                        Transformation::Rotate(
+                            self.point(args[1])?,
+                            (
+                                self.point(args[2])?,
+                                self.point(args[3])?,
+                                self.point(args[4])?,
+                            ),
+                        )
+                    }
+                    "translate" => {
+                        expect_args(3)?;
+                        Transformation::Translate(self.point(args[1])?, self.point(args[2])?)
+                    }
+                    "dilate" => {
+                        expect_args(3)?;
+                        let center = self.point(args[1])?;
+                        match args[2].parse::<f64>() {
+                            Ok(factor) if factor.is_finite() => {
+                                geometry::create_dilation(center, factor)
+                            }
+                            _ => Transformation::DilateBySlider(center, self.slider(args[2])?),
+                        }
+                    }
+                    _ => {
+                        expect_args(2)?;
+                        Transformation::Invert(self.shape(args[1])?)
+                    }
+                };
+                match self.thing(args[0])? {
+                    original @ ThingID::PointID(_) | original @ ThingID::ShapeID(_) => {
+                        ctx.add_transformed(original, transformation)
+                    }
+                    _ => return Err(format!("`{}` is not a point or a shape", args[0])),
+                }
+            }
+            "polygon" => {
+                if args.len() < 3 {
+                    return Err("A polygon needs at least 3 vertices".into());
+                }
+                let vertices = args
+                    .iter()
+                    .map(|arg| self.point(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                ThingID::PolygonID(ctx.geometry.add_polygon(Polygon(vertices)))
+            }
+            "locus" => {
+                expect_args(3)?;
+                ThingID::LocusID(ctx.geometry.add_locus(Locus {
+                    traced: self.point(args[0])?,
+                    driver: self.point(args[1])?,
+                    path: self.shape(args[2])?,
+                }))
+            }
+            _ => return Err(format!("Unknown kind of object `{}`", kind)),
+        };
+
+        if hidden {
+            ctx.visibility.insert(id, Visibility::Hidden);
+        }
+        if input {
+            match id {
+                ThingID::PointID(point) => self.inputs.push(point),
+                _ => return Err(format!("`{}` is an input but not a point", name)),
+            }
+        }
+        self.names.insert(name.to_string(), id);
+        Ok(())
+    }
+}
+
+/// Reads the parts of a style line into `style`
+fn read_style(style: &mut Style, words: &[&str]) -> Result<(), String> {
+    let number = |word: &str| match word.parse::<f64>() {
+        Ok(x) if x.is_finite() && x >= 0. => Ok(x),
+        _ => Err(format!("Invalid number `{}`", word)),
+    };
+    let channel = |word: &str| {
+        word.parse::<u8>()
+            .map_err(|_| format!("Invalid color `{}`", word))
+    };
+    // The arguments of a part, which has to have at least `n`
+    let args = |i: usize, n: usize| {
+        if words.len() > i + n {
+            Ok(&words[i + 1..])
+        } else {
+            Err(format!("`{}` takes {} arguments", words[i], n))
+        }
+    };
+
+    let mut i = 0;
+    while i < words.len() {
+        let used = match words[i] {
+            "color" if words.get(i + 1) == Some(&"default") => {
+                style.color = None;
+                1
+            }
+            "color" => {
+                let args = args(i, 3)?;
+                style.color = Some((channel(args[0])?, channel(args[1])?, channel(args[2])?));
+                3
+            }
+            "width" => {
+                style.width = number(args(i, 1)?[0])?;
+                1
+            }
+            "size" => {
+                style.point_size = number(args(i, 1)?[0])?;
+                1
+            }
+            "dash" => {
+                // Every number after it
+                style.dash = words[i + 1..]
+                    .iter()
+                    .take_while(|word| number(word).is_ok())
+                    .map(|word| number(word).unwrap())
+                    .collect();
+                style.dash.len()
+            }
+            "point" => {
+                let name = args(i, 1)?[0];
+                style.point_shape = POINT_SHAPES
+                    .iter()
+                    .find(|(shape, _)| *shape == name)
+                    .map(|(_, shape)| *shape)
+                    .ok_or_else(|| format!("Unknown point shape `{}`", name))?;
+                1
+            }
+            "fill" if words.get(i + 1) == Some(&"none") => {
+                style.fill = None;
+                1
+            }
+            "fill" => {
+                let args = args(i, 4)?;
+                style.fill = Some(Fill {
+                    color: (channel(args[0])?, channel(args[1])?, channel(args[2])?),
+                    opacity: number(args[3])?.min(1.),
+                });
+                4
+            }
+            word => return Err(format!("Unknown part of a style `{}`", word)),
+        };
+        i += 1 + used;
+    }
+    Ok(())
+}
+
+/// The parts of `style` that differ from `default`, as read by `read_style`
+pub fn style_words(style: &Style, default: &Style) -> Vec<String> {
+    let mut words = Vec::new();
+    if style.color != default.color {
+        words.push(match style.color {
+            Some((r, g, b)) => format!("color {} {} {}", r, g, b),
+            None => "color default".to_string(),
+        });
+    }
+    if style.width != default.width {
+        words.push(format!("width {}", style.width));
+    }
+    if style.dash != default.dash {
+        let lengths: Vec<String> = style.dash.iter().map(|len| len.to_string()).collect();
+        words.push(format!("dash {}", lengths.join(" ")).trim_end().to_string());
+    }
+    if style.point_shape != default.point_shape {
+        if let Some((name, _)) = POINT_SHAPES
+            .iter()
+            .find(|(_, shape)| *shape == style.point_shape)
+        {
+            words.push(format!("point {}", name));
+        }
+    }
+    if style.point_size != default.point_size {
+        words.push(format!("size {}", style.point_size));
+    }
+    if style.fill != default.fill {
+        words.push(match style.fill {
+            Some(Fill {
+                color: (r, g, b),
+                opacity,
+            }) => format!("fill {} {} {} {}", r, g, b, opacity),
+            None => "fill none".to_string(),
+        });
+    }
+    words
+}
+
+/// Reads a whole construction
+pub fn read(text: &str) -> Result<GWrapper, String> {
+    let mut ctx = GWrapper::new(geometry::Geometry::new());
+    let mut reader = Reader::new();
+    for (i, line) in text.lines().enumerate() {
+        reader
+            .read_line(&mut ctx, line)
+            .map_err(|err| format!("Line {}: {}", i + 1, err))?;
+    }
+    Ok(ctx)
+}
+
+/// Reads copied lines into `ctx` as new objects, without changing anything if they can't be
+/// read. Sliders get a number appended if `ctx` already has one of the same name. Returns the
+/// pasted objects and the input points among them.
+pub fn paste(ctx: &mut GWrapper, text: &str) -> Result<(Vec<ThingID>, Vec<PointID>), String> {
+    read(text)?;
+    let first = ctx.order.len();
+    let mut reader = Reader::new();
+    for line in text.lines() {
+        reader.read_line(ctx, line)?;
+    }
+
+    for (name, id) in reader.sliders {
+        let taken = |name: &str| {
+            ctx.sliders
+                .iter()
+                .any(|(other, slider)| *other != id && slider.name == name)
+        };
+        if taken(&name) {
+            let unused = (2..)
+                .map(|i| format!("{}{}", name, i))
+                .find(|name| !taken(name))
+                .unwrap();
+            if let Some(slider) = ctx.geometry.sliders.get_mut(&id) {
+                slider.name = unused;
+            }
+        }
+    }
+    Ok((ctx.order[first..].to_vec(), reader.inputs))
+}
+
+/// Writes a construction so that `read` gives it back, with the names of the protocol
+pub fn write(ctx: &GWrapper) -> String {
+    let things = ctx.order.iter().map(|id| (*id, Copied::Whole)).collect();
+    let sliders = ctx.sliders.keys().cloned().collect();
+    write_copied(ctx, &things, &sliders)
+}
+
+/// How an object is written
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Copied {
+    Whole,
+    /// Only written to construct what was copied
+    Hidden,
+    /// An input point where the point was
+    Input((f64, f64)),
+}
+
+/// Writes `selection` so that `paste` adds a copy of it. Points it's constructed from that
+/// aren't selected become inputs, and other objects it needs are hidden.
+pub fn write_selection(ctx: &GWrapper, selection: &HashSet<ThingID>) -> String {
+    let mut things = HashMap::new();
+    let mut sliders = HashSet::new();
+    let mut todo: Vec<ThingID> = selection.iter().cloned().collect();
+    while let Some(id) = todo.pop() {
+        if things.contains_key(&id) {
+            continue;
+        }
+        let position = match id {
+            ThingID::PointID(point) => ctx.points.get(&point).and_then(|p| ctx.resolve_point(p)),
+            _ => None,
+        };
+        let copied = match position {
+            _ if selection.contains(&id) => Copied::Whole,
+            // Points that don't exist where they are can't be inputs
+            Some(position) => Copied::Input(position),
+            None => Copied::Hidden,
+        };
+        things.insert(id, copied);
+        if let Copied::Input(_) = copied {
+            continue;
+        }
+        let (parents, used) = ctx.dependencies(id);
+        todo.extend(parents);
+        sliders.extend(used);
+    }
+    write_copied(ctx, &things, &sliders)
+}
+
+/// Writes `things` the way they're copied, and `sliders`
+pub fn write_copied(
+    ctx: &GWrapper,
+    things: &HashMap<ThingID, Copied>,
+    sliders: &HashSet<SliderID>,
+) -> String {
+    let names = protocol::names(ctx);
+    let name = |id: ThingID| names.get(&id).map(|name| &name[..]).unwrap_or("?");
+    let point = |id| name(ThingID::PointID(id));
+    let shape = |id| name(ThingID::ShapeID(id));
+    let slider = |id| {
+        ctx.sliders
+            .get(&id)
+            .map(|slider| &slider.name[..])
+            .unwrap_or("?")
+    };
+    let transformed = |original: &str, t: &Transformation| match t {
+        Transformation::Reflect(line) => format!("reflect {} {}", original, shape(*line)),
+        Transformation::Rotate(center, (a, vertex, b)) => format!(
+            "rotate {} {} {} {} {}",
+            original,
+            point(*center),
+            point(*a),
+            point(*vertex),
+            point(*b)
+        ),
+        Transformation::Translate(from, to) => {
+            format!("translate {} {} {}", original, point(*from), point(*to))
+        }
+        Transformation::Dilate(center, factor) => {
+            format!("dilate {} {} {}", original, point(*center), factor)
+        }
+        Transformation::DilateBySlider(center, factor) => {
+            format!("dilate {} {} {}", original, point(*center), slider(*factor))
+        }
+        Transformation::Invert(circle) => format!("invert {} {}", original, shape(*circle)),
+    };
+
+    let mut text = String::new();
+    let mut sliders: Vec<_> = ctx
+        .sliders
+        .iter()
+        .filter(|(id, _)| sliders.contains(id))
+        .collect();
+    sliders.sort_by_key(|(id, _)| ***id);
+    for (_, s) in sliders {
+        text.push_str(&format!(
+            "{} = slider {} {} {} {}\n",
+            s.name, s.min, s.max, s.step, s.value
+        ));
+    }
+
+    for id in &ctx.order {
+        let copied = match things.get(id) {
+            Some(copied) => *copied,
+            None => continue,
+        };
+        if let Copied::Input((x, y)) = copied {
+            text.push_str(&format!("input {} = point {} {}\n", name(*id), x, y));
+            continue;
+        }
+        let definition = match id {
+            ThingID::PointID(point_id) => match ctx.points.get(point_id) {
+                Some(Point::Arbitrary((x, y))) => format!("point {} {}", x, y),
+                Some(Point::PrimIntersection(a, b)) => {
+                    format!("intersection {} {}", shape(*a), shape(*b))
+                }
+                Some(Point::SecIntersection(a, b)) => {
+                    format!("intersection2 {} {}", shape(*a), shape(*b))
+                }
+                Some(Point::Transformed(original, t)) => transformed(point(*original), t),
+                Some(Point::OnCircle(circle, angle)) => {
+                    format!("oncircle {} {}", shape(*circle), slider(*angle))
+                }
+                Some(Point::OnLine(line, along)) => {
+                    format!("online {} {}", shape(*line), slider(*along))
+                }
+                None => continue,
+            },
+            ThingID::ShapeID(shape_id) => match ctx.shapes.get(shape_id) {
+                Some(Shape::Circle(center, through)) => {
+                    format!("circle {} {}", point(*center), point(*through))
+                }
+                Some(Shape::Line(a, b)) => format!("line {} {}", point(*a), point(*b)),
+                Some(Shape::Transformed(original, t)) => transformed(shape(*original), t),
+                None => continue,
+            },
+            ThingID::PolygonID(polygon_id) => match ctx.polygons.get(polygon_id) {
+                Some(polygon) => {
+                    let vertices: Vec<&str> = polygon.0.iter().map(|v| point(*v)).collect();
+                    format!("polygon {}", vertices.join(" "))
+                }
+                None => continue,
+            },
+            ThingID::LocusID(locus_id) => match ctx.loci.get(locus_id) {
+                Some(locus) => format!(
+                    "locus {} {} {}",
+                    point(locus.traced),
+                    point(locus.driver),
+                    shape(locus.path)
+                ),
+                None => continue,
+            },
+        };
+        if copied == Copied::Hidden || ctx.visibility.get(id) == Some(&Visibility::Hidden) {
+            text.push_str("hidden ");
+        }
+        text.push_str(&format!("{} = {}\n", name(*id), definition));
+    }
+
+    for id in ctx.order.iter().filter(|id| things.contains_key(id)) {
+        let style = style_words(&ctx.style(*id), &GWrapper::default_style(*id));
+        if !style.is_empty() {
+            text.push_str(&format!("style {} {}\n", name(*id), style.join(" ")));
+        }
+    }
+    text
+}
+
+/// The words of a line, without comments
+pub fn split_line(line: &str) -> Vec<&str> {
+    line.split('#')
+        .next()
+        .unwrap_or("")
+        .split_whitespace()
+        .collect()
+}
+
+#[test]
+fn test_write() {
+    let text = "\
+a = slider 0 360 1 45
+k = slider -3 3 0.1 0.5
+A = point 0 0
+B = point 1.5 -0.25
+c1 = circle A B
+l1 = line A B
+C = intersection c1 l1
+hidden D = intersection2 c1 l1
+E = oncircle c1 a
+F = reflect E l1
+s1 = rotate c1 B A C E
+G = translate F A B
+s2 = dilate l1 E 2
+H = dilate G A k
+I = invert H c1
+J = online l1 k
+poly1 = polygon A B E
+loc1 = locus F E c1
+style l1 color 200 40 40 width 2 dash 8 4
+style E point cross size 7
+style poly1 fill 100 150 255 0.3
+";
+    let ctx = read(text).unwrap();
+    assert_eq!(write(&ctx), text);
+    assert_eq!(
+        ctx.style(ThingID::PolygonID(
+            ctx.polygons.keys().cloned().next().unwrap()
+        ))
+        .fill
+        .map(|fill| fill.color),
+        Some((100, 150, 255))
+    );
+
+    let style = |line: &str| {
+        let mut ctx = read("A = point 0 0").unwrap();
+        let mut reader = Reader::new();
+        reader.names.insert("A".into(), ctx.order[0]);
+        reader
+            .read_line(&mut ctx, line)
+            .map(|()| ctx.style(ctx.order[0]))
+    };
+    assert_eq!(
+        style("style A dash width 2").unwrap().dash,
+        Vec::<f64>::new()
+    );
+    assert_eq!(style("style A dash 1 2 3 size 4").unwrap().point_size, 4.);
+    assert!(style("style A color 300 0 0").is_err());
+    assert!(style("style A width").is_err());
+    assert!(style("style A point star").is_err());
+    assert!(style("style B width 2").is_err());
+    assert!(read("P = point 0 0\nl = line P P\nQ = reflect l l\nR = reflect P P").is_err());
+}
+
+#[test]
+fn test_paste() {
+    let ctx = read(
+        "\
+a = slider 0 360 1 0
+k = slider -3 3 0.1 0.5
+A = point 0 0
+B = point 1 0
+C = point 5 5
+c1 = circle A B
+l1 = line A C
+D = oncircle c1 a
+E = reflect D l1
+style E color 200 40 40
+",
+    )
+    .unwrap();
+    let id = |i: usize| ctx.order[i];
+    let copy = |selection: &[ThingID]| write_selection(&ctx, &selection.iter().cloned().collect());
+    assert_eq!(
+        copy(&[id(6)]),
+        "\
+input A = point 0 0
+input C = point 5 5
+hidden l1 = line A C
+input D = point 1 0
+E = reflect D l1
+style E color 200 40 40
+"
+    );
+    let copied = copy(&[id(5), id(6)]);
+    assert_eq!(
+        copied,
+        "\
+a = slider 0 360 1 0
+input A = point 0 0
+input B = point 1 0
+input C = point 5 5
+hidden c1 = circle A B
+hidden l1 = line A C
+D = oncircle c1 a
+E = reflect D l1
+style E color 200 40 40
+"
+    );
+
+    let mut other = read("a = slider 0 10 1 5\nA = point 3 3").unwrap();
+    let (pasted, inputs) = paste(&mut other, &copied).unwrap();
+    assert_eq!(pasted.len(), 7);
+    assert_eq!(inputs.len(), 3);
+    let mut names: Vec<_> = other.sliders.values().map(|s| s.name.clone()).collect();
+    names.sort();
+    assert_eq!(names, vec!["a", "a2"]);
+    assert_eq!(other.style(pasted[6]).color, Some((200, 40, 40)));
+
+    // The circle is centered on the old point once the first input is attached to it
+    let old = match other.order[0] {
+        ThingID::PointID(id) => id,
+        _ => unreachable!(),
+    };
+    other.replace(ThingID::PointID(inputs[0]), ThingID::PointID(old));
+    assert_eq!(other.order.len(), 7);
+    assert!(other
+        .shapes
+        .values()
+        .any(|shape| *shape == Shape::Circle(old, inputs[1])));
+    assert!(read(&write(&other)).is_ok());
+
+    assert!(paste(&mut other, "X = point 0 0\nl = line X Y").is_err());
+    assert!(paste(&mut other, "input l = line X X").is_err());
+    assert_eq!(other.order.len(), 7);
+}
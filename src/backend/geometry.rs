@@ -1,11 +1,24 @@
 use ordered_float::NotNan;
 
 use std::cell::Cell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::f64::consts::PI;
 use std::iter::IntoIterator;
+use std::mem;
 use std::ops::Deref;
 
 const EPSILON: f64 = 1e-8;
+/// The number of positions of the driver point a locus is traced at
+pub const LOCUS_SAMPLES: usize = 240;
+/// How many seconds a playing slider takes to sweep its whole range
+pub const SLIDER_PERIOD: f64 = 6.;
+/// How close two objects have to be to count as duplicates
+pub const DUPLICATE_TOLERANCE: f64 = 1e-6;
+/// How many times the free points are moved around to check that something holds wherever
+/// they are
+pub const PERTURBATIONS: usize = 5;
+/// How far the free points are moved, relative to how far apart they are
+pub const PERTURBATION_SIZE: f64 = 0.2;
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
 pub struct PointID(u64);
@@ -25,11 +38,89 @@ impl Deref for ShapeID {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct PolygonID(u64);
+impl Deref for PolygonID {
+    type Target = u64;
+    fn deref(&self) -> &u64 {
+        &self.0
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct LocusID(u64);
+impl Deref for LocusID {
+    type Target = u64;
+    fn deref(&self) -> &u64 {
+        &self.0
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct SliderID(u64);
+impl Deref for SliderID {
+    type Target = u64;
+    fn deref(&self) -> &u64 {
+        &self.0
+    }
+}
+
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub enum ThingID {
+    PointID(PointID),
+    ShapeID(ShapeID),
+    PolygonID(PolygonID),
+    LocusID(LocusID),
+}
+
 #[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
 pub enum Point {
     PrimIntersection(ShapeID, ShapeID),
     SecIntersection(ShapeID, ShapeID),
     Arbitrary((NotNan<f64>, NotNan<f64>)),
+    /// The image of a point under a transformation
+    Transformed(PointID, Transformation),
+    /// On a circle, at the angle of a slider in degrees, counter-clockwise from the right
+    OnCircle(ShapeID, SliderID),
+    /// On a line, at a slider that is 0 at the first point the line is through and 1 at the
+    /// second
+    OnLine(ShapeID, SliderID),
+}
+
+impl Point {
+    /// The objects and sliders the point is defined from
+    pub fn dependencies(&self) -> (Vec<ThingID>, Vec<SliderID>) {
+        match self {
+            Point::PrimIntersection(a, b) | Point::SecIntersection(a, b) => {
+                (vec![ThingID::ShapeID(*a), ThingID::ShapeID(*b)], Vec::new())
+            }
+            Point::Arbitrary(_) => (Vec::new(), Vec::new()),
+            Point::Transformed(original, t) => {
+                let (mut things, sliders) = t.dependencies();
+                things.insert(0, ThingID::PointID(*original));
+                (things, sliders)
+            }
+            Point::OnCircle(shape, slider) | Point::OnLine(shape, slider) => {
+                (vec![ThingID::ShapeID(*shape)], vec![*slider])
+            }
+        }
+    }
+
+    /// Uses `new` wherever the point is defined from `old`
+    pub fn replace(&mut self, old: ThingID, new: ThingID) {
+        match self {
+            Point::PrimIntersection(a, b) | Point::SecIntersection(a, b) => {
+                replace_shape(a, old, new);
+                replace_shape(b, old, new);
+            }
+            Point::Arbitrary(_) => {}
+            Point::Transformed(original, t) => {
+                replace_point(original, old, new);
+                t.replace(old, new);
+            }
+            Point::OnCircle(shape, _) | Point::OnLine(shape, _) => replace_shape(shape, old, new),
+        }
+    }
 }
 
 pub fn create_arbitrary(pos: (f64, f64)) -> Point {
@@ -38,12 +129,367 @@ pub fn create_arbitrary(pos: (f64, f64)) -> Point {
     Point::Arbitrary((pos.0.unwrap(), pos.1.unwrap()))
 }
 
+/// Where the free points at `positions` are moved in the `trial`th of PERTURBATIONS checks:
+/// each one by PERTURBATION_SIZE of how far apart they are, in a direction no other point or
+/// trial uses
+pub fn perturbed(positions: &[(f64, f64)], trial: usize) -> Vec<(f64, f64)> {
+    let mut size: f64 = 1.;
+    for a in positions {
+        for b in positions {
+            size = size.max(((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt());
+        }
+    }
+
+    let golden_angle = PI * (3. - 5f64.sqrt());
+    positions
+        .iter()
+        .enumerate()
+        .map(|(i, pos)| {
+            let angle = (trial * positions.len() + i) as f64 * golden_angle;
+            (
+                pos.0 + size * PERTURBATION_SIZE * angle.cos(),
+                pos.1 + size * PERTURBATION_SIZE * angle.sin(),
+            )
+        })
+        .collect()
+}
+
 #[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
 pub enum Shape {
     /// (center, point on circumference),
     Circle(PointID, PointID),
     /// Two points on the line
     Line(PointID, PointID),
+    /// The image of a shape under a transformation
+    Transformed(ShapeID, Transformation),
+}
+
+impl Shape {
+    /// The objects and sliders the shape is defined from
+    pub fn dependencies(&self) -> (Vec<ThingID>, Vec<SliderID>) {
+        match self {
+            Shape::Circle(a, b) | Shape::Line(a, b) => {
+                (vec![ThingID::PointID(*a), ThingID::PointID(*b)], Vec::new())
+            }
+            Shape::Transformed(original, t) => {
+                let (mut things, sliders) = t.dependencies();
+                things.insert(0, ThingID::ShapeID(*original));
+                (things, sliders)
+            }
+        }
+    }
+
+    /// Uses `new` wherever the shape is defined from `old`
+    pub fn replace(&mut self, old: ThingID, new: ThingID) {
+        match self {
+            Shape::Circle(a, b) | Shape::Line(a, b) => {
+                replace_point(a, old, new);
+                replace_point(b, old, new);
+            }
+            Shape::Transformed(original, t) => {
+                replace_shape(original, old, new);
+                t.replace(old, new);
+            }
+        }
+    }
+}
+
+/// The vertices, in order
+#[derive(Clone, Hash, PartialEq, Eq, Debug)]
+pub struct Polygon(pub Vec<PointID>);
+
+/// The path `traced` follows as `driver` moves along `path`
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub struct Locus {
+    pub traced: PointID,
+    pub driver: PointID,
+    pub path: ShapeID,
+}
+
+/// A number parameter of the construction, set by hand or animated
+#[derive(Clone, PartialEq, Debug)]
+pub struct Slider {
+    pub name: String,
+    pub min: f64,
+    pub max: f64,
+    /// Values set by hand are rounded to a multiple of the step from min, 0 allows any value
+    pub step: f64,
+    pub value: f64,
+    pub playing: bool,
+}
+
+impl Slider {
+    pub fn new(name: &str, min: f64, max: f64, step: f64, value: f64) -> Slider {
+        let mut slider = Slider {
+            name: name.to_string(),
+            min: min.min(max),
+            max: max.max(min),
+            step: step.abs(),
+            value: min,
+            playing: false,
+        };
+        slider.set(value);
+        slider
+    }
+
+    /// Sets the value, rounded to the step and kept within the range
+    pub fn set(&mut self, value: f64) {
+        let value = if self.step > 0. {
+            self.min + ((value - self.min) / self.step).round() * self.step
+        } else {
+            value
+        };
+        self.value = value.max(self.min).min(self.max);
+    }
+
+    /// Where in the range the value is, from 0 to 1
+    pub fn fraction(&self) -> f64 {
+        if self.max > self.min {
+            (self.value - self.min) / (self.max - self.min)
+        } else {
+            0.
+        }
+    }
+
+    /// Moves the value `dt` seconds forward, starting over from min after max. The value
+    /// isn't rounded to the step, so the animation is smooth.
+    pub fn advance(&mut self, dt: f64) {
+        let range = self.max - self.min;
+        if range <= 0. {
+            return;
+        }
+        let fraction = self.fraction() + dt / SLIDER_PERIOD;
+        self.value = self.min + range * (fraction - fraction.floor());
+    }
+}
+
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub enum Transformation {
+    /// Reflection across a line
+    Reflect(ShapeID),
+    /// (center, (a, vertex, b)), rotation by the angle from a to b around vertex
+    Rotate(PointID, (PointID, PointID, PointID)),
+    /// Translation by the vector from the first point to the second
+    Translate(PointID, PointID),
+    /// (center, factor)
+    Dilate(PointID, NotNan<f64>),
+    /// (center, slider), dilation by the value of a slider
+    DilateBySlider(PointID, SliderID),
+    /// Inversion in a circle
+    Invert(ShapeID),
+}
+
+impl Transformation {
+    /// The objects and sliders the transformation is defined by
+    pub fn dependencies(&self) -> (Vec<ThingID>, Vec<SliderID>) {
+        match self {
+            Transformation::Reflect(line) | Transformation::Invert(line) => {
+                (vec![ThingID::ShapeID(*line)], Vec::new())
+            }
+            Transformation::Rotate(center, (a, vertex, b)) => (
+                vec![
+                    ThingID::PointID(*center),
+                    ThingID::PointID(*a),
+                    ThingID::PointID(*vertex),
+                    ThingID::PointID(*b),
+                ],
+                Vec::new(),
+            ),
+            Transformation::Translate(from, to) => (
+                vec![ThingID::PointID(*from), ThingID::PointID(*to)],
+                Vec::new(),
+            ),
+            Transformation::Dilate(center, _) => (vec![ThingID::PointID(*center)], Vec::new()),
+            Transformation::DilateBySlider(center, factor) => {
+                (vec![ThingID::PointID(*center)], vec![*factor])
+            }
+        }
+    }
+
+    fn replace(&mut self, old: ThingID, new: ThingID) {
+        let points: Vec<&mut PointID> = match self {
+            Transformation::Reflect(line) | Transformation::Invert(line) => {
+                replace_shape(line, old, new);
+                Vec::new()
+            }
+            Transformation::Rotate(center, (a, vertex, b)) => vec![center, a, vertex, b],
+            Transformation::Translate(from, to) => vec![from, to],
+            Transformation::Dilate(center, _) | Transformation::DilateBySlider(center, _) => {
+                vec![center]
+            }
+        };
+        for point in points {
+            replace_point(point, old, new);
+        }
+    }
+}
+
+fn replace_point(id: &mut PointID, old: ThingID, new: ThingID) {
+    if let (ThingID::PointID(old), ThingID::PointID(new)) = (old, new) {
+        if *id == old {
+            *id = new;
+        }
+    }
+}
+
+fn replace_shape(id: &mut ShapeID, old: ThingID, new: ThingID) {
+    if let (ThingID::ShapeID(old), ThingID::ShapeID(new)) = (old, new) {
+        if *id == old {
+            *id = new;
+        }
+    }
+}
+
+pub fn create_dilation(center: PointID, factor: f64) -> Transformation {
+    let factor = NotNan::new(factor);
+    assert!(factor.is_ok());
+    Transformation::Dilate(center, factor.unwrap())
+}
+
+/// A transformation with all its parameters resolved
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ResolvedTransformation {
+    /// x -> Ax + b, ((a11, a12, a21, a22), b). A is always a similarity
+    Affine((f64, f64, f64, f64), (f64, f64)),
+    /// (center, radius) of the circle of inversion
+    Inversion((f64, f64), f64),
+}
+
+impl ResolvedTransformation {
+    pub fn apply_point(&self, (x, y): (f64, f64)) -> Option<(f64, f64)> {
+        match self {
+            ResolvedTransformation::Affine((a11, a12, a21, a22), (bx, by)) => {
+                Some((a11 * x + a12 * y + bx, a21 * x + a22 * y + by))
+            }
+            ResolvedTransformation::Inversion((cx, cy), r) => {
+                let (dx, dy) = (x - cx, y - cy);
+                let dist_sq = dx * dx + dy * dy;
+                if dist_sq == 0. {
+                    return None;
+                }
+                let factor = r * r / dist_sq;
+                Some((cx + dx * factor, cy + dy * factor))
+            }
+        }
+    }
+
+    pub fn apply_shape(&self, shape: &ResolvedShape) -> Option<ResolvedShape> {
+        match (self, shape) {
+            (
+                ResolvedTransformation::Affine((a11, a12, a21, a22), _),
+                ResolvedShape::Circle(center, rad),
+            ) => {
+                let scale = (a11 * a22 - a12 * a21).abs().sqrt();
+                Some(ResolvedShape::Circle(
+                    self.apply_point(*center)?,
+                    rad * scale,
+                ))
+            }
+            (ResolvedTransformation::Affine(..), line) => {
+                let (p1, p2) = line.line_points()?;
+                Some(line_through(self.apply_point(p1)?, self.apply_point(p2)?))
+            }
+            (
+                ResolvedTransformation::Inversion(inv_center, _),
+                ResolvedShape::Circle(center, rad),
+            ) => {
+                let (dx, dy) = (center.0 - inv_center.0, center.1 - inv_center.1);
+                let dist = (dx * dx + dy * dy).sqrt();
+                if dist == 0. {
+                    // Concentric circles stay concentric
+                    let image = self.apply_point((center.0 + rad, center.1))?;
+                    return Some(ResolvedShape::Circle(*center, image.0 - center.0));
+                }
+                let (ux, uy) = (dx / dist, dy / dist);
+                // The point of the circle furthest away from the inversion center
+                let far = self.apply_point((center.0 + ux * rad, center.1 + uy * rad))?;
+                // Relative to how far away the circle is, since huge circles through the center
+                // (like the images of lines almost through it) can't be placed more exactly
+                if (dist - rad).abs() < EPSILON * dist.max(1.) {
+                    // Circles through the inversion center become lines, perpendicular to the
+                    // line between the centers
+                    return Some(line_through(far, (far.0 - uy, far.1 + ux)));
+                }
+                let near = self.apply_point((center.0 - ux * rad, center.1 - uy * rad))?;
+                let (ddx, ddy) = (far.0 - near.0, far.1 - near.1);
+                Some(ResolvedShape::Circle(
+                    ((far.0 + near.0) / 2., (far.1 + near.1) / 2.),
+                    (ddx * ddx + ddy * ddy).sqrt() / 2.,
+                ))
+            }
+            (ResolvedTransformation::Inversion(inv_center, _), line) => {
+                if line.distance_to(*inv_center) < EPSILON {
+                    // Lines through the inversion center are fixed
+                    return Some(*line);
+                }
+                let ((x1, y1), (x2, y2)) = line.line_points()?;
+                let (dx, dy) = (x2 - x1, y2 - y1);
+                let t = ((inv_center.0 - x1) * dx + (inv_center.1 - y1) * dy) / (dx * dx + dy * dy);
+                // Lines not through the inversion center become circles through it, with the
+                // image of the closest point of the line diametrically opposite of the center
+                let foot = self.apply_point((x1 + t * dx, y1 + t * dy))?;
+                let (fdx, fdy) = (foot.0 - inv_center.0, foot.1 - inv_center.1);
+                Some(ResolvedShape::Circle(
+                    ((foot.0 + inv_center.0) / 2., (foot.1 + inv_center.1) / 2.),
+                    (fdx * fdx + fdy * fdy).sqrt() / 2.,
+                ))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Hash, PartialEq, Eq, Debug)]
+pub enum Measurement {
+    /// The distance between two points
+    Distance(PointID, PointID),
+    /// (a, vertex, b), the unsigned angle in radians
+    Angle(PointID, PointID, PointID),
+    /// The radius of a circle
+    Radius(ShapeID),
+    /// The area enclosed by a (non self-intersecting) polygon
+    Area(Vec<PointID>),
+    /// The total length of the edges of a polygon
+    Perimeter(Vec<PointID>),
+}
+
+impl Measurement {
+    /// The objects the measurement is taken of
+    pub fn dependencies(&self) -> Vec<ThingID> {
+        match self {
+            Measurement::Distance(a, b) => vec![ThingID::PointID(*a), ThingID::PointID(*b)],
+            Measurement::Angle(a, vertex, b) => vec![
+                ThingID::PointID(*a),
+                ThingID::PointID(*vertex),
+                ThingID::PointID(*b),
+            ],
+            Measurement::Radius(circle) => vec![ThingID::ShapeID(*circle)],
+            Measurement::Area(vertices) | Measurement::Perimeter(vertices) => vertices
+                .iter()
+                .map(|vertex| ThingID::PointID(*vertex))
+                .collect(),
+        }
+    }
+
+    /// Measures `new` wherever `old` is measured
+    pub fn replace(&mut self, old: ThingID, new: ThingID) {
+        match self {
+            Measurement::Distance(a, b) => {
+                replace_point(a, old, new);
+                replace_point(b, old, new);
+            }
+            Measurement::Angle(a, vertex, b) => {
+                replace_point(a, old, new);
+                replace_point(vertex, old, new);
+                replace_point(b, old, new);
+            }
+            Measurement::Radius(circle) => replace_shape(circle, old, new),
+            Measurement::Area(vertices) | Measurement::Perimeter(vertices) => {
+                for vertex in vertices.iter_mut() {
+                    replace_point(vertex, old, new);
+                }
+            }
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -57,6 +503,50 @@ pub enum ResolvedShape {
 }
 
 impl ResolvedShape {
+    /// Two distinct points on a line, None for circles
+    pub fn line_points(&self) -> Option<((f64, f64), (f64, f64))> {
+        match self {
+            ResolvedShape::Circle(..) => None,
+            ResolvedShape::Line(k, m) => Some(((0., *m), (1., k + m))),
+            ResolvedShape::LineUp(x) => Some(((*x, 0.), (*x, 1.))),
+        }
+    }
+
+    pub fn distance_to(&self, at: (f64, f64)) -> f64 {
+        match self {
+            ResolvedShape::Circle(pos, rad) => {
+                let (dx, dy) = (pos.0 - at.0, pos.1 - at.1);
+                let dist = (dx * dx + dy * dy).sqrt();
+                (dist - rad).abs()
+            }
+            ResolvedShape::Line(k, m) => (k * at.0 - at.1 + m).abs() / (k * k + 1.).sqrt(),
+            ResolvedShape::LineUp(k) => (k - at.0).abs(),
+        }
+    }
+
+    /// Whether two shapes are the same, up to `tolerance`
+    pub fn approx_eq(&self, other: &ResolvedShape, tolerance: f64) -> bool {
+        match (self, other) {
+            (ResolvedShape::Circle(c1, r1), ResolvedShape::Circle(c2, r2)) => {
+                (c1.0 - c2.0).abs() < tolerance
+                    && (c1.1 - c2.1).abs() < tolerance
+                    && (r1 - r2).abs() < tolerance
+            }
+            (ResolvedShape::Circle(..), _) | (_, ResolvedShape::Circle(..)) => false,
+            _ => {
+                let direction = |shape: &ResolvedShape| {
+                    let (p1, p2) = shape.line_points().unwrap();
+                    let (dx, dy) = (p2.0 - p1.0, p2.1 - p1.1);
+                    let len = (dx * dx + dy * dy).sqrt();
+                    (dx / len, dy / len)
+                };
+                let (d1, d2) = (direction(self), direction(other));
+                (d1.0 * d2.1 - d1.1 * d2.0).abs() < tolerance
+                    && other.distance_to(self.line_points().unwrap().0) < tolerance
+            }
+        }
+    }
+
     #[allow(unused)]
     pub fn as_eq(&self) -> String {
         match self {
@@ -96,13 +586,34 @@ impl ResolvedShape {
     }
 }
 
+/// Why an object can't be redefined
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RedefineError {
+    /// The object, or something its new definition uses, doesn't exist
+    Missing,
+    /// The new definition uses this object, which is the redefined object or defined from it
+    Cycle(ThingID),
+}
+
 pub struct Geometry {
     pub shapes: HashMap<ShapeID, Shape>,
     pub points: HashMap<PointID, Point>,
+    pub polygons: HashMap<PolygonID, Polygon>,
+    pub loci: HashMap<LocusID, Locus>,
+    /// Sliders aren't drawn on the plane, so they aren't part of `order`
+    pub sliders: HashMap<SliderID, Slider>,
+    /// Every object, in the order they were added
+    pub order: Vec<ThingID>,
 
     point_cache: Cell<HashMap<Point, (f64, f64)>>,
+    /// The traced paths of the loci, which are only updated by `trace_loci`
+    locus_paths: HashMap<LocusID, Vec<Vec<(f64, f64)>>>,
+    loci_outdated: bool,
     last_shape: ShapeID,
     last_point: PointID,
+    last_polygon: PolygonID,
+    last_locus: LocusID,
+    last_slider: SliderID,
 }
 
 impl Geometry {
@@ -110,15 +621,25 @@ impl Geometry {
         Geometry {
             shapes: HashMap::new(),
             points: HashMap::new(),
+            polygons: HashMap::new(),
+            loci: HashMap::new(),
+            sliders: HashMap::new(),
+            order: Vec::new(),
             point_cache: Cell::new(HashMap::new()),
+            locus_paths: HashMap::new(),
+            loci_outdated: false,
             last_shape: ShapeID(0),
             last_point: PointID(0),
+            last_polygon: PolygonID(0),
+            last_locus: LocusID(0),
+            last_slider: SliderID(0),
         }
     }
 
     pub fn add_shape(&mut self, shape: Shape) -> ShapeID {
         let id = self.next_shape_id();
         self.shapes.insert(id, shape);
+        self.order.push(ThingID::ShapeID(id));
         self.invalidate_cache();
 
         id
@@ -127,11 +648,68 @@ impl Geometry {
     pub fn add_point(&mut self, point: Point) -> PointID {
         let id = self.next_point_id();
         self.points.insert(id, point);
+        self.order.push(ThingID::PointID(id));
         self.invalidate_cache();
 
         id
     }
 
+    pub fn add_polygon(&mut self, polygon: Polygon) -> PolygonID {
+        let id = self.next_polygon_id();
+        self.polygons.insert(id, polygon);
+        self.order.push(ThingID::PolygonID(id));
+
+        id
+    }
+
+    pub fn add_locus(&mut self, locus: Locus) -> LocusID {
+        let id = self.next_locus_id();
+        self.loci.insert(id, locus);
+        self.order.push(ThingID::LocusID(id));
+        self.loci_outdated = true;
+
+        id
+    }
+
+    pub fn add_slider(&mut self, slider: Slider) -> SliderID {
+        let id = self.next_slider_id();
+        self.sliders.insert(id, slider);
+
+        id
+    }
+
+    /// Removes the slider `id` unless something is defined from it, returns whether it did
+    pub fn remove_unused_slider(&mut self, id: SliderID) -> bool {
+        let used = self
+            .order
+            .iter()
+            .any(|thing| self.dependencies(*thing).1.contains(&id));
+        !used && self.sliders.remove(&id).is_some()
+    }
+
+    /// Sets the value of a slider, moving everything that depends on it
+    pub fn set_slider(&mut self, id: SliderID, value: f64) {
+        if let Some(slider) = self.sliders.get_mut(&id) {
+            slider.set(value);
+        }
+        self.invalidate_cache();
+    }
+
+    /// Advances the playing sliders, returns whether any of them moved
+    pub fn animate_sliders(&mut self, dt: f64) -> bool {
+        let mut moved = false;
+        for slider in self.sliders.values_mut() {
+            if slider.playing {
+                slider.advance(dt);
+                moved = true;
+            }
+        }
+        if moved {
+            self.invalidate_cache();
+        }
+        moved
+    }
+
     fn next_shape_id(&mut self) -> ShapeID {
         loop {
             self.last_shape = ShapeID(*self.last_shape + 1);
@@ -150,7 +728,47 @@ impl Geometry {
         }
     }
 
+    fn next_polygon_id(&mut self) -> PolygonID {
+        loop {
+            self.last_polygon = PolygonID(*self.last_polygon + 1);
+            if !self.polygons.contains_key(&self.last_polygon) {
+                return self.last_polygon;
+            }
+        }
+    }
+
+    fn next_locus_id(&mut self) -> LocusID {
+        loop {
+            self.last_locus = LocusID(*self.last_locus + 1);
+            if !self.loci.contains_key(&self.last_locus) {
+                return self.last_locus;
+            }
+        }
+    }
+
+    fn next_slider_id(&mut self) -> SliderID {
+        loop {
+            self.last_slider = SliderID(*self.last_slider + 1);
+            if !self.sliders.contains_key(&self.last_slider) {
+                return self.last_slider;
+            }
+        }
+    }
+
     pub fn get_potential_points(&self) -> Vec<Point> {
+        self.intersections()
+            .into_iter()
+            .filter(|p| {
+                !self
+                    .points
+                    .values()
+                    .any(|x| self.resolve_point(x) == self.resolve_point(p))
+            })
+            .collect()
+    }
+
+    /// Every intersection of two shapes that exists
+    pub fn intersections(&self) -> Vec<Point> {
         let mut points = Vec::new();
         for s1 in self.shapes.keys() {
             for s2 in self.shapes.keys() {
@@ -161,11 +779,7 @@ impl Geometry {
                     Point::PrimIntersection(*s1, *s2),
                     Point::SecIntersection(*s1, *s2),
                 ] {
-                    if self.resolve_point(p).is_some() && !self
-                        .points
-                        .values()
-                        .any(|x| self.resolve_point(x) == self.resolve_point(p))
-                    {
+                    if self.resolve_point(p).is_some() {
                         points.push(*p);
                     }
                 }
@@ -174,8 +788,373 @@ impl Geometry {
         points
     }
 
+    /// Whether `id` is `from` or defined from it, directly or not
+    pub fn is_defined_from(&self, id: ThingID, from: ThingID) -> bool {
+        let mut seen = HashSet::new();
+        let mut todo = vec![id];
+        while let Some(thing) = todo.pop() {
+            if thing == from {
+                return true;
+            }
+            if seen.insert(thing) {
+                todo.extend(self.dependencies(thing).0);
+            }
+        }
+        false
+    }
+
+    /// The objects and sliders `id` is directly defined from
+    pub fn dependencies(&self, id: ThingID) -> (Vec<ThingID>, Vec<SliderID>) {
+        let shape = |id: &ShapeID| ThingID::ShapeID(*id);
+        let point = |id: &PointID| ThingID::PointID(*id);
+        match id {
+            ThingID::PointID(id) => match self.points.get(&id) {
+                Some(definition) => definition.dependencies(),
+                None => (Vec::new(), Vec::new()),
+            },
+            ThingID::ShapeID(id) => match self.shapes.get(&id) {
+                Some(definition) => definition.dependencies(),
+                None => (Vec::new(), Vec::new()),
+            },
+            ThingID::PolygonID(id) => match self.polygons.get(&id) {
+                Some(polygon) => (polygon.0.iter().map(point).collect(), Vec::new()),
+                None => (Vec::new(), Vec::new()),
+            },
+            ThingID::LocusID(id) => match self.loci.get(&id) {
+                Some(locus) => (
+                    vec![
+                        point(&locus.traced),
+                        point(&locus.driver),
+                        shape(&locus.path),
+                    ],
+                    Vec::new(),
+                ),
+                None => (Vec::new(), Vec::new()),
+            },
+        }
+    }
+
+    /// Makes everything defined from `old` use `new` instead, and removes `old`. Both have to be
+    /// points or both shapes, and `new` has to be older than `old`, so that everything still
+    /// comes after what it's defined from.
+    pub fn replace(&mut self, old: ThingID, new: ThingID) {
+        for point in self.points.values_mut() {
+            point.replace(old, new);
+        }
+        for shape in self.shapes.values_mut() {
+            shape.replace(old, new);
+        }
+        for polygon in self.polygons.values_mut() {
+            for vertex in polygon.0.iter_mut() {
+                replace_point(vertex, old, new);
+            }
+        }
+        for locus in self.loci.values_mut() {
+            replace_point(&mut locus.traced, old, new);
+            replace_point(&mut locus.driver, old, new);
+            replace_shape(&mut locus.path, old, new);
+        }
+        match old {
+            ThingID::PointID(id) => {
+                self.points.remove(&id);
+            }
+            ThingID::ShapeID(id) => {
+                self.shapes.remove(&id);
+            }
+            _ => {}
+        }
+        self.order.retain(|id| *id != old);
+        self.invalidate_cache();
+    }
+
+    /// Pairs of an object and a newer point or shape that is the same wherever the free points
+    /// are, like two lines through the same points. They are checked where everything is now
+    /// and after moving the free points around, so objects that only happen to be in the same
+    /// place aren't paired. Every duplicate is paired with the oldest object it's the same as,
+    /// so they can be merged with `replace`.
+    pub fn duplicates(&mut self) -> Vec<(ThingID, ThingID)> {
+        let things: Vec<ThingID> = self
+            .order
+            .iter()
+            .filter(|id| matches!(id, ThingID::PointID(_) | ThingID::ShapeID(_)))
+            .cloned()
+            .collect();
+        let mut candidates = Vec::new();
+        for (i, id) in things.iter().enumerate() {
+            for original in &things[..i] {
+                if self.is_same(*original, *id) {
+                    candidates.push((*original, *id));
+                }
+            }
+        }
+
+        let free: Vec<(PointID, Point)> = self
+            .points
+            .iter()
+            .filter(|(_, point)| matches!(point, Point::Arbitrary(_)))
+            .map(|(id, point)| (*id, *point))
+            .collect();
+        let positions: Vec<(f64, f64)> = free
+            .iter()
+            .filter_map(|(_, point)| self.resolve_point(point))
+            .collect();
+        for trial in 0..PERTURBATIONS {
+            if candidates.is_empty() {
+                break;
+            }
+            for ((id, _), moved) in free.iter().zip(perturbed(&positions, trial)) {
+                self.points.insert(*id, create_arbitrary(moved));
+            }
+            self.invalidate_cache();
+            candidates.retain(|(original, id)| self.is_same(*original, *id));
+        }
+        for (id, point) in free {
+            self.points.insert(id, point);
+        }
+        self.invalidate_cache();
+
+        // Duplicates of duplicates are merged into the oldest object instead
+        let mut merged = HashSet::new();
+        let mut duplicates = Vec::new();
+        for (original, id) in candidates {
+            if !merged.contains(&original) && merged.insert(id) {
+                duplicates.push((original, id));
+            }
+        }
+        duplicates
+    }
+
+    /// Whether the points or shapes `a` and `b` are in the same place, within
+    /// DUPLICATE_TOLERANCE
+    fn is_same(&self, a: ThingID, b: ThingID) -> bool {
+        match (a, b) {
+            (ThingID::PointID(a), ThingID::PointID(b)) => {
+                let resolve = |id| self.points.get(&id).and_then(|p| self.resolve_point(p));
+                match (resolve(a), resolve(b)) {
+                    (Some(a), Some(b)) => {
+                        (a.0 - b.0).abs() < DUPLICATE_TOLERANCE
+                            && (a.1 - b.1).abs() < DUPLICATE_TOLERANCE
+                    }
+                    _ => false,
+                }
+            }
+            (ThingID::ShapeID(a), ThingID::ShapeID(b)) => {
+                let resolve = |id| self.shapes.get(&id).and_then(|s| self.resolve_shape(s));
+                match (resolve(a), resolve(b)) {
+                    (Some(a), Some(b)) => a.approx_eq(&b, DUPLICATE_TOLERANCE),
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// Defines `id` as `point` instead, keeping everything defined from it. Returns the objects
+    /// that existed before but can't be resolved anymore.
+    pub fn redefine_point(
+        &mut self,
+        id: PointID,
+        point: Point,
+    ) -> Result<Vec<ThingID>, RedefineError> {
+        if !self.points.contains_key(&id) {
+            return Err(RedefineError::Missing);
+        }
+        self.check_redefinition(ThingID::PointID(id), point.dependencies())?;
+        let resolved = self.resolved();
+        self.points.insert(id, point);
+        Ok(self.redefined(resolved))
+    }
+
+    /// Defines `id` as `shape` instead, like `redefine_point`
+    pub fn redefine_shape(
+        &mut self,
+        id: ShapeID,
+        shape: Shape,
+    ) -> Result<Vec<ThingID>, RedefineError> {
+        if !self.shapes.contains_key(&id) {
+            return Err(RedefineError::Missing);
+        }
+        self.check_redefinition(ThingID::ShapeID(id), shape.dependencies())?;
+        let resolved = self.resolved();
+        self.shapes.insert(id, shape);
+        Ok(self.redefined(resolved))
+    }
+
+    /// Checks that `id` can be defined from `uses`, which have to exist and not be defined from
+    /// `id`
+    fn check_redefinition(
+        &self,
+        id: ThingID,
+        (things, sliders): (Vec<ThingID>, Vec<SliderID>),
+    ) -> Result<(), RedefineError> {
+        let exists = |thing: &ThingID| match thing {
+            ThingID::PointID(point) => self.points.contains_key(point),
+            ThingID::ShapeID(shape) => self.shapes.contains_key(shape),
+            ThingID::PolygonID(polygon) => self.polygons.contains_key(polygon),
+            ThingID::LocusID(locus) => self.loci.contains_key(locus),
+        };
+        if !things.iter().all(exists) || !sliders.iter().all(|s| self.sliders.contains_key(s)) {
+            return Err(RedefineError::Missing);
+        }
+        match things
+            .into_iter()
+            .find(|used| self.is_defined_from(*used, id))
+        {
+            Some(used) => Err(RedefineError::Cycle(used)),
+            None => Ok(()),
+        }
+    }
+
+    /// The objects that can be resolved, loci being resolved when their points and path are
+    fn resolved(&self) -> HashSet<ThingID> {
+        let points = self
+            .points
+            .iter()
+            .filter(|(_, point)| self.resolve_point(point).is_some())
+            .map(|(id, _)| ThingID::PointID(*id));
+        let shapes = self
+            .shapes
+            .iter()
+            .filter(|(_, shape)| self.resolve_shape(shape).is_some())
+            .map(|(id, _)| ThingID::ShapeID(*id));
+        let polygons = self
+            .polygons
+            .iter()
+            .filter(|(_, polygon)| self.resolve_polygon(polygon).is_some())
+            .map(|(id, _)| ThingID::PolygonID(*id));
+        let resolves = |point: &PointID| {
+            self.points
+                .get(point)
+                .and_then(|point| self.resolve_point(point))
+                .is_some()
+        };
+        let loci = self
+            .loci
+            .iter()
+            .filter(|(_, locus)| {
+                resolves(&locus.traced)
+                    && resolves(&locus.driver)
+                    && self
+                        .shapes
+                        .get(&locus.path)
+                        .and_then(|path| self.resolve_shape(path))
+                        .is_some()
+            })
+            .map(|(id, _)| ThingID::LocusID(*id));
+        points.chain(shapes).chain(polygons).chain(loci).collect()
+    }
+
+    /// Updates everything after a redefinition. Returns the objects in `resolved` that can't be
+    /// resolved anymore, in order.
+    fn redefined(&mut self, resolved: HashSet<ThingID>) -> Vec<ThingID> {
+        self.invalidate_cache();
+        self.sort_order();
+        let now = self.resolved();
+        self.order
+            .iter()
+            .filter(|id| resolved.contains(id) && !now.contains(id))
+            .cloned()
+            .collect()
+    }
+
+    /// Moves everything after what it's defined from, keeping the order otherwise
+    fn sort_order(&mut self) {
+        let order = mem::take(&mut self.order);
+        let mut placed = HashSet::new();
+        let mut sorted = Vec::with_capacity(order.len());
+        for id in order {
+            self.place(id, &mut placed, &mut sorted);
+        }
+        self.order = sorted;
+    }
+
+    fn place(&self, id: ThingID, placed: &mut HashSet<ThingID>, sorted: &mut Vec<ThingID>) {
+        if placed.insert(id) {
+            for dependency in self.dependencies(id).0 {
+                self.place(dependency, placed, sorted);
+            }
+            sorted.push(id);
+        }
+    }
+
     pub fn invalidate_cache(&mut self) {
         self.point_cache.take();
+        self.loci_outdated = true;
+    }
+
+    /// Retraces the loci if anything has changed since they were last traced
+    pub fn trace_loci(&mut self) {
+        if !self.loci_outdated {
+            return;
+        }
+        let loci: Vec<(LocusID, Locus)> =
+            self.loci.iter().map(|(id, locus)| (*id, *locus)).collect();
+        for (id, locus) in loci {
+            let path = self.sample_locus(&locus);
+            self.locus_paths.insert(id, path);
+        }
+        self.loci_outdated = false;
+    }
+
+    /// The traced path of a locus, split where the traced point doesn't exist
+    pub fn locus_path(&self, id: LocusID) -> Option<&Vec<Vec<(f64, f64)>>> {
+        self.locus_paths.get(&id)
+    }
+
+    fn sample_locus(&mut self, locus: &Locus) -> Vec<Vec<(f64, f64)>> {
+        let (driver, traced, path) = match (
+            self.points.get(&locus.driver).cloned(),
+            self.points.get(&locus.traced).cloned(),
+            self.shapes
+                .get(&locus.path)
+                .and_then(|shape| self.resolve_shape(shape)),
+        ) {
+            (Some(driver), Some(traced), Some(path)) => (driver, traced, path),
+            _ => return Vec::new(),
+        };
+
+        let positions: Vec<(f64, f64)> = match path {
+            ResolvedShape::Circle(center, rad) => (0..=LOCUS_SAMPLES)
+                .map(|i| {
+                    let angle = 2. * PI * i as f64 / LOCUS_SAMPLES as f64;
+                    (center.0 + rad * angle.cos(), center.1 + rad * angle.sin())
+                })
+                .collect(),
+            line => {
+                let (p1, p2) = line.line_points().unwrap();
+                let (dx, dy) = (p2.0 - p1.0, p2.1 - p1.1);
+                let len = (dx * dx + dy * dy).sqrt();
+                let (dx, dy) = (dx / len, dy / len);
+                // Centered around where the driver is now, spreading out along the whole line
+                let start = self.resolve_point(&driver).unwrap_or(p1);
+                let t0 = (start.0 - p1.0) * dx + (start.1 - p1.1) * dy;
+                (1..LOCUS_SAMPLES)
+                    .map(|i| {
+                        let t = t0 + (PI * (i as f64 / LOCUS_SAMPLES as f64 - 0.5)).tan();
+                        (p1.0 + dx * t, p1.1 + dy * t)
+                    })
+                    .collect()
+            }
+        };
+
+        let mut paths = vec![Vec::new()];
+        for pos in positions {
+            self.points.insert(locus.driver, create_arbitrary(pos));
+            self.point_cache.take();
+            match self.resolve_point(&traced) {
+                Some(traced_pos) => paths.last_mut().unwrap().push(traced_pos),
+                None => {
+                    if !paths.last().unwrap().is_empty() {
+                        paths.push(Vec::new());
+                    }
+                }
+            }
+        }
+        self.points.insert(locus.driver, driver);
+        self.point_cache.take();
+
+        paths.retain(|path| !path.is_empty());
+        paths
     }
 
     pub fn resolve_point(&self, point: &Point) -> Option<(f64, f64)> {
@@ -188,6 +1167,26 @@ impl Geometry {
 
         let res = match point {
             Point::Arbitrary(pos) => Some((*pos.0, *pos.1)),
+            Point::Transformed(id, transformation) => {
+                let pos = self.resolve_point(self.points.get(id)?)?;
+                self.resolve_transformation(transformation)?
+                    .apply_point(pos)
+            }
+            Point::OnCircle(circle_id, slider_id) => {
+                let angle = self.sliders.get(slider_id)?.value.to_radians();
+                match self.resolve_shape(self.shapes.get(circle_id)?)? {
+                    // y points down, so up on the screen is negative
+                    ResolvedShape::Circle(center, rad) => {
+                        Some((center.0 + rad * angle.cos(), center.1 - rad * angle.sin()))
+                    }
+                    _ => None,
+                }
+            }
+            Point::OnLine(line_id, slider_id) => {
+                let t = self.sliders.get(slider_id)?.value;
+                let (a, b) = self.line_anchors(self.shapes.get(line_id)?)?;
+                Some((a.0 + t * (b.0 - a.0), a.1 + t * (b.1 - a.1)))
+            }
             Point::PrimIntersection(a, b) | Point::SecIntersection(a, b) => {
                 let obj_a = self.shapes.get(&a)?;
                 let obj_b = self.shapes.get(&b)?;
@@ -247,6 +1246,7 @@ impl Geometry {
                         );
                         intersect_line_line(point1, point2, point3, point4)
                     }
+                    _ => intersect_shapes(&self.resolve_shape(obj_a)?, &self.resolve_shape(obj_b)?),
                 };
 
                 match (point, intersection) {
@@ -266,6 +1266,27 @@ impl Geometry {
         res
     }
 
+    /// Two points the line `shape` is through, which move with it. None for circles.
+    pub fn line_anchors(&self, shape: &Shape) -> Option<((f64, f64), (f64, f64))> {
+        match shape {
+            Shape::Circle(..) => None,
+            Shape::Line(a, b) => Some((
+                self.resolve_point(self.points.get(a)?)?,
+                self.resolve_point(self.points.get(b)?)?,
+            )),
+            // The images of the points the original is through, unless it's no longer a line
+            Shape::Transformed(original, transformation) => {
+                self.resolve_shape(shape)?.line_points()?;
+                let (a, b) = self.line_anchors(self.shapes.get(original)?)?;
+                let transformation = self.resolve_transformation(transformation)?;
+                Some((
+                    transformation.apply_point(a)?,
+                    transformation.apply_point(b)?,
+                ))
+            }
+        }
+    }
+
     pub fn resolve_shape(&self, shape: &Shape) -> Option<ResolvedShape> {
         match shape {
             Shape::Circle(center_pos, cpoint_pos) => {
@@ -283,17 +1304,191 @@ impl Geometry {
                     self.resolve_point(self.points.get(p1_pos)?)?,
                     self.resolve_point(self.points.get(p2_pos)?)?,
                 );
-                if p1.0 == p2.0 {
-                    return Some(ResolvedShape::LineUp(p1.0));
+                Some(line_through(p1, p2))
+            }
+            Shape::Transformed(id, transformation) => {
+                let shape = self.resolve_shape(self.shapes.get(id)?)?;
+                self.resolve_transformation(transformation)?
+                    .apply_shape(&shape)
+            }
+        }
+    }
+
+    pub fn resolve_measurement(&self, measurement: &Measurement) -> Option<f64> {
+        match measurement {
+            Measurement::Distance(a_pos, b_pos) => {
+                let (a, b) = (
+                    self.resolve_point(self.points.get(a_pos)?)?,
+                    self.resolve_point(self.points.get(b_pos)?)?,
+                );
+                Some(((a.0 - b.0) * (a.0 - b.0) + (a.1 - b.1) * (a.1 - b.1)).sqrt())
+            }
+            Measurement::Angle(a_pos, vertex_pos, b_pos) => {
+                let (a, vertex, b) = (
+                    self.resolve_point(self.points.get(a_pos)?)?,
+                    self.resolve_point(self.points.get(vertex_pos)?)?,
+                    self.resolve_point(self.points.get(b_pos)?)?,
+                );
+                let (ax, ay) = (a.0 - vertex.0, a.1 - vertex.1);
+                let (bx, by) = (b.0 - vertex.0, b.1 - vertex.1);
+                Some((ax * by - ay * bx).atan2(ax * bx + ay * by).abs())
+            }
+            Measurement::Radius(circle_pos) => {
+                match self.resolve_shape(self.shapes.get(circle_pos)?)? {
+                    ResolvedShape::Circle(_, rad) => Some(rad),
+                    _ => None,
                 }
-                let k = (p1.1 - p2.1) / (p1.0 - p2.0);
-                // y - y0 = k(x - x0)
-                // y = kx - kx0 + y0
-                let m = -k * p1.0 + p1.1;
-                Some(ResolvedShape::Line(k, m))
             }
+            Measurement::Area(vertex_pos) => Some(polygon_area(
+                &self.resolve_polygon(&Polygon(vertex_pos.clone()))?,
+            )),
+            Measurement::Perimeter(vertex_pos) => Some(polygon_perimeter(
+                &self.resolve_polygon(&Polygon(vertex_pos.clone()))?,
+            )),
+        }
+    }
+
+    /// The positions of the vertices, None if any of them can't be resolved
+    pub fn resolve_polygon(&self, polygon: &Polygon) -> Option<Vec<(f64, f64)>> {
+        let mut vertices = Vec::with_capacity(polygon.0.len());
+        for pos in &polygon.0 {
+            vertices.push(self.resolve_point(self.points.get(pos)?)?);
         }
+        Some(vertices)
     }
+
+    pub fn resolve_transformation(
+        &self,
+        transformation: &Transformation,
+    ) -> Option<ResolvedTransformation> {
+        match transformation {
+            Transformation::Reflect(line_id) => {
+                let line = self.resolve_shape(self.shapes.get(line_id)?)?;
+                let ((x1, y1), (x2, y2)) = line.line_points()?;
+                let (dx, dy) = (x2 - x1, y2 - y1);
+                let len_sq = dx * dx + dy * dy;
+                let (a11, a12) = ((dx * dx - dy * dy) / len_sq, 2. * dx * dy / len_sq);
+                let (a21, a22) = (a12, -a11);
+                Some(ResolvedTransformation::Affine(
+                    (a11, a12, a21, a22),
+                    (x1 - a11 * x1 - a12 * y1, y1 - a21 * x1 - a22 * y1),
+                ))
+            }
+            Transformation::Rotate(center_pos, (a_pos, vertex_pos, b_pos)) => {
+                let (center, a, vertex, b) = (
+                    self.resolve_point(self.points.get(center_pos)?)?,
+                    self.resolve_point(self.points.get(a_pos)?)?,
+                    self.resolve_point(self.points.get(vertex_pos)?)?,
+                    self.resolve_point(self.points.get(b_pos)?)?,
+                );
+                let theta =
+                    (b.1 - vertex.1).atan2(b.0 - vertex.0) - (a.1 - vertex.1).atan2(a.0 - vertex.0);
+                let (sin, cos) = theta.sin_cos();
+                Some(ResolvedTransformation::Affine(
+                    (cos, -sin, sin, cos),
+                    (
+                        center.0 - cos * center.0 + sin * center.1,
+                        center.1 - sin * center.0 - cos * center.1,
+                    ),
+                ))
+            }
+            Transformation::Translate(from_pos, to_pos) => {
+                let (from, to) = (
+                    self.resolve_point(self.points.get(from_pos)?)?,
+                    self.resolve_point(self.points.get(to_pos)?)?,
+                );
+                Some(ResolvedTransformation::Affine(
+                    (1., 0., 0., 1.),
+                    (to.0 - from.0, to.1 - from.1),
+                ))
+            }
+            Transformation::Dilate(center_pos, factor) => self.dilation(center_pos, **factor),
+            Transformation::DilateBySlider(center_pos, slider_id) => {
+                self.dilation(center_pos, self.sliders.get(slider_id)?.value)
+            }
+            Transformation::Invert(circle_id) => {
+                match self.resolve_shape(self.shapes.get(circle_id)?)? {
+                    ResolvedShape::Circle(center, rad) if rad > 0. => {
+                        Some(ResolvedTransformation::Inversion(center, rad))
+                    }
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    fn dilation(&self, center_pos: &PointID, k: f64) -> Option<ResolvedTransformation> {
+        let center = self.resolve_point(self.points.get(center_pos)?)?;
+        Some(ResolvedTransformation::Affine(
+            (k, 0., 0., k),
+            ((1. - k) * center.0, (1. - k) * center.1),
+        ))
+    }
+}
+
+/// The shoelace formula
+pub fn polygon_area(vertices: &[(f64, f64)]) -> f64 {
+    let mut sum = 0.;
+    for (i, a) in vertices.iter().enumerate() {
+        let b = vertices[(i + 1) % vertices.len()];
+        sum += a.0 * b.1 - b.0 * a.1;
+    }
+    sum.abs() / 2.
+}
+
+pub fn polygon_perimeter(vertices: &[(f64, f64)]) -> f64 {
+    let mut sum = 0.;
+    for (i, a) in vertices.iter().enumerate() {
+        let b = vertices[(i + 1) % vertices.len()];
+        sum += ((a.0 - b.0) * (a.0 - b.0) + (a.1 - b.1) * (a.1 - b.1)).sqrt();
+    }
+    sum
+}
+
+/// The distance from `at` to the closest segment of a polyline
+pub fn distance_to_polyline(points: &[(f64, f64)], at: (f64, f64)) -> f64 {
+    if points.len() == 1 {
+        let (dx, dy) = (points[0].0 - at.0, points[0].1 - at.1);
+        return (dx * dx + dy * dy).sqrt();
+    }
+    points
+        .windows(2)
+        .map(|segment| {
+            let (a, b) = (segment[0], segment[1]);
+            let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+            let len_sq = dx * dx + dy * dy;
+            let t = if len_sq == 0. {
+                0.
+            } else {
+                (((at.0 - a.0) * dx + (at.1 - a.1) * dy) / len_sq).clamp(0., 1.)
+            };
+            let (cx, cy) = (a.0 + t * dx - at.0, a.1 + t * dy - at.1);
+            (cx * cx + cy * cy).sqrt()
+        })
+        .fold(f64::MAX, f64::min)
+}
+
+/// Even-odd rule, so self-intersecting polygons have holes
+pub fn point_in_polygon(vertices: &[(f64, f64)], at: (f64, f64)) -> bool {
+    let mut inside = false;
+    for (i, a) in vertices.iter().enumerate() {
+        let b = vertices[(i + 1) % vertices.len()];
+        if (a.1 > at.1) != (b.1 > at.1) && at.0 < a.0 + (at.1 - a.1) * (b.0 - a.0) / (b.1 - a.1) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+pub fn line_through(p1: (f64, f64), p2: (f64, f64)) -> ResolvedShape {
+    if p1.0 == p2.0 {
+        return ResolvedShape::LineUp(p1.0);
+    }
+    let k = (p1.1 - p2.1) / (p1.0 - p2.0);
+    // y - y0 = k(x - x0)
+    // y = kx - kx0 + y0
+    let m = -k * p1.0 + p1.1;
+    ResolvedShape::Line(k, m)
 }
 
 pub fn is_same(a: (f64, f64), b: (f64, f64)) -> bool {
@@ -337,6 +1532,26 @@ impl IntoIterator for IntersectionResult {
     }
 }
 
+fn intersect_shapes(a: &ResolvedShape, b: &ResolvedShape) -> IntersectionResult {
+    match (a, b) {
+        (ResolvedShape::Circle(cent1, rad1), ResolvedShape::Circle(cent2, rad2)) => {
+            intersect_two_circles(*cent1, *rad1, *cent2, *rad2)
+        }
+        (ResolvedShape::Circle(cent, rad), line) | (line, ResolvedShape::Circle(cent, rad)) => {
+            match line.line_points() {
+                Some((point1, point2)) => intersect_circle_line(*cent, *rad, point1, point2),
+                None => IntersectionResult::None,
+            }
+        }
+        (line1, line2) => match (line1.line_points(), line2.line_points()) {
+            (Some((point1, point2)), Some((point3, point4))) => {
+                intersect_line_line(point1, point2, point3, point4)
+            }
+            _ => IntersectionResult::None,
+        },
+    }
+}
+
 // Ported from https://gist.github.com/jupdike/bfe5eb23d1c395d8a0a1a4ddd94882ac
 // x1,y1 is the center of the first circle, with radius r1
 // x2,y2 is the center of the second ricle, with radius r2
@@ -487,6 +1702,303 @@ fn test_intersect() {
     );
 }
 
+#[test]
+fn test_transformations() {
+    let mut geo = Geometry::new();
+    let origin = geo.add_point(create_arbitrary((0., 0.)));
+    let diag = geo.add_point(create_arbitrary((1., 1.)));
+    let x = geo.add_point(create_arbitrary((1., 0.)));
+    let y = geo.add_point(create_arbitrary((0., 1.)));
+    let p = geo.add_point(create_arbitrary((2., 0.)));
+    let line = geo.add_shape(Shape::Line(origin, diag));
+
+    let resolve = |geo: &Geometry, t| geo.resolve_point(&Point::Transformed(p, t)).unwrap();
+    assert!(is_same(
+        resolve(&geo, Transformation::Reflect(line)),
+        (0., 2.)
+    ));
+    assert!(is_same(
+        resolve(&geo, Transformation::Rotate(x, (x, origin, y))),
+        (1., 1.)
+    ));
+    assert!(is_same(
+        resolve(&geo, Transformation::Translate(x, diag)),
+        (2., 1.)
+    ));
+    assert!(is_same(resolve(&geo, create_dilation(x, -2.)), (-1., 0.)));
+
+    let circle = geo.add_shape(Shape::Circle(p, x));
+    assert_eq!(
+        geo.resolve_shape(&Shape::Transformed(circle, create_dilation(origin, 3.))),
+        Some(ResolvedShape::Circle((6., 0.), 3.))
+    );
+    assert_eq!(
+        geo.resolve_shape(&Shape::Transformed(
+            line,
+            Transformation::Translate(origin, y)
+        )),
+        Some(ResolvedShape::Line(1., 1.))
+    );
+}
+
+#[test]
+fn test_point_in_polygon() {
+    let square = [(0., 0.), (2., 0.), (2., 2.), (0., 2.)];
+    assert!(point_in_polygon(&square, (1., 1.)));
+    assert!(!point_in_polygon(&square, (3., 1.)));
+    assert!(!point_in_polygon(&square, (1., -1.)));
+
+    let bowtie = [(0., 0.), (2., 2.), (2., 0.), (0., 2.)];
+    assert!(point_in_polygon(&bowtie, (0.5, 1.)));
+    assert!(!point_in_polygon(&bowtie, (1., 1.5)));
+}
+
+#[test]
+fn test_measurements() {
+    use std::f64::consts::PI;
+
+    let mut geo = Geometry::new();
+    let origin = geo.add_point(create_arbitrary((0., 0.)));
+    let x = geo.add_point(create_arbitrary((3., 0.)));
+    let y = geo.add_point(create_arbitrary((0., 4.)));
+    let circle = geo.add_shape(Shape::Circle(x, y));
+    let line = geo.add_shape(Shape::Line(x, y));
+
+    let measure = |m| geo.resolve_measurement(&m).unwrap();
+    assert_eq!(measure(Measurement::Distance(x, y)), 5.);
+    assert!((measure(Measurement::Angle(x, origin, y)) - PI / 2.).abs() < EPSILON);
+    assert!((measure(Measurement::Angle(y, origin, x)) - PI / 2.).abs() < EPSILON);
+    assert_eq!(measure(Measurement::Radius(circle)), 5.);
+    assert_eq!(measure(Measurement::Area(vec![origin, x, y])), 6.);
+    assert_eq!(measure(Measurement::Area(vec![y, x, origin])), 6.);
+    assert_eq!(measure(Measurement::Perimeter(vec![origin, x, y])), 12.);
+    assert_eq!(geo.resolve_measurement(&Measurement::Radius(line)), None);
+}
+
+#[test]
+fn test_locus() {
+    let mut geo = Geometry::new();
+    let origin = geo.add_point(create_arbitrary((0., 0.)));
+    let x = geo.add_point(create_arbitrary((1., 0.)));
+    let y = geo.add_point(create_arbitrary((0., 1.)));
+    let unit = geo.add_shape(Shape::Circle(origin, x));
+    let x_axis = geo.add_shape(Shape::Line(origin, x));
+    let y_axis = geo.add_shape(Shape::Line(origin, y));
+
+    let driver = geo.add_point(create_arbitrary((0., 0.5)));
+    let doubled = geo.add_point(Point::Transformed(driver, create_dilation(origin, 2.)));
+    let doubled_locus = geo.add_locus(Locus {
+        traced: doubled,
+        driver,
+        path: unit,
+    });
+
+    // A unit circle around the driver only meets the x axis when the driver is close to it
+    let next = geo.add_point(Point::Transformed(
+        driver,
+        Transformation::Translate(origin, x),
+    ));
+    let around = geo.add_shape(Shape::Circle(driver, next));
+    let meet = geo.add_point(Point::PrimIntersection(around, x_axis));
+    let meet_locus = geo.add_locus(Locus {
+        traced: meet,
+        driver,
+        path: y_axis,
+    });
+
+    geo.trace_loci();
+    let path = geo.locus_path(doubled_locus).unwrap();
+    assert_eq!(path.len(), 1);
+    assert!(path[0]
+        .iter()
+        .all(|p| (p.0.hypot(p.1) - 2.).abs() < EPSILON));
+
+    let path = geo.locus_path(meet_locus).unwrap();
+    assert_eq!(path.len(), 1);
+    assert!(path[0]
+        .iter()
+        .all(|p| p.1.abs() < EPSILON && p.0.abs() <= 1. + EPSILON));
+
+    // The driver is back where it was
+    assert_eq!(geo.resolve_point(&geo.points[&driver]), Some((0., 0.5)));
+}
+
+#[test]
+fn test_slider() {
+    let mut geo = Geometry::new();
+    let origin = geo.add_point(create_arbitrary((0., 0.)));
+    let x = geo.add_point(create_arbitrary((2., 0.)));
+    let circle = geo.add_shape(Shape::Circle(origin, x));
+
+    let angle = geo.add_slider(Slider::new("a", 0., 360., 1., 89.7));
+    assert_eq!(geo.sliders[&angle].value, 90.);
+    let on_circle = geo.add_point(Point::OnCircle(circle, angle));
+    let pos = geo.resolve_point(&geo.points[&on_circle]).unwrap();
+    assert!(pos.0.abs() < EPSILON && (pos.1 + 2.).abs() < EPSILON);
+
+    let factor = geo.add_slider(Slider::new("k", -3., 3., 0.5, 0.));
+    let image = geo.add_point(Point::Transformed(
+        on_circle,
+        Transformation::DilateBySlider(origin, factor),
+    ));
+    geo.set_slider(factor, 1.4);
+    geo.set_slider(angle, 1000.);
+    assert_eq!(geo.sliders[&factor].value, 1.5);
+    let pos = geo.resolve_point(&geo.points[&image]).unwrap();
+    assert!((pos.0 - 3.).abs() < EPSILON && pos.1.abs() < EPSILON);
+
+    // Playing goes around the whole range in SLIDER_PERIOD, starting over at the end
+    geo.sliders.get_mut(&angle).unwrap().playing = true;
+    assert!(geo.animate_sliders(SLIDER_PERIOD / 4.));
+    assert!((geo.sliders[&angle].value - 90.).abs() < EPSILON);
+    let pos = geo.resolve_point(&geo.points[&image]).unwrap();
+    assert!(pos.0.abs() < EPSILON && (pos.1 + 3.).abs() < EPSILON);
+
+    geo.sliders.get_mut(&angle).unwrap().playing = false;
+    assert!(!geo.animate_sliders(1.));
+
+    // Along a line from its first point to its second, and along its image
+    let line = geo.add_shape(Shape::Line(origin, x));
+    let along = geo.add_slider(Slider::new("t", -5., 5., 0., 1.5));
+    let on_line = geo.add_point(Point::OnLine(line, along));
+    assert_eq!(geo.resolve_point(&geo.points[&on_line]), Some((3., 0.)));
+    let turned = geo.add_shape(Shape::Transformed(
+        line,
+        Transformation::Rotate(origin, (x, origin, on_circle)),
+    ));
+    let on_turned = geo.add_point(Point::OnLine(turned, along));
+    let pos = geo.resolve_point(&geo.points[&on_turned]).unwrap();
+    assert!(pos.0.abs() < EPSILON && (pos.1 + 3.).abs() < EPSILON);
+    assert!(geo.line_anchors(&Shape::Circle(origin, x)).is_none());
+
+    // Sliders are only removed once nothing uses them
+    assert!(!geo.remove_unused_slider(along));
+    geo.redefine_point(on_line, create_arbitrary((3., 0.)))
+        .unwrap();
+    geo.redefine_point(on_turned, create_arbitrary((0., -3.)))
+        .unwrap();
+    assert!(geo.remove_unused_slider(along));
+    assert!(!geo.sliders.contains_key(&along));
+}
+
+#[test]
+fn test_redefine() {
+    let mut geo = Geometry::new();
+    let a = geo.add_point(create_arbitrary((0., 0.)));
+    let b = geo.add_point(create_arbitrary((1., 0.)));
+    let circle = geo.add_shape(Shape::Circle(a, b));
+    let line = geo.add_shape(Shape::Line(a, b));
+    let meet = geo.add_point(Point::PrimIntersection(circle, line));
+    let c = geo.add_point(create_arbitrary((3., 0.)));
+
+    // The circle is moved to a newer center, and everything on it comes after that
+    assert_eq!(
+        geo.redefine_shape(circle, Shape::Circle(c, b)),
+        Ok(Vec::new())
+    );
+    let position = |geo: &Geometry, id: ThingID| geo.order.iter().position(|x| *x == id);
+    assert!(position(&geo, ThingID::PointID(c)) < position(&geo, ThingID::ShapeID(circle)));
+    assert!(position(&geo, ThingID::ShapeID(circle)) < position(&geo, ThingID::PointID(meet)));
+    let pos = geo.resolve_point(&geo.points[&meet]).unwrap();
+    assert!(pos == (1., 0.) || pos == (5., 0.));
+
+    // The line is moved so that it misses the circle, losing the locus of the intersection
+    let locus = geo.add_locus(Locus {
+        traced: meet,
+        driver: c,
+        path: circle,
+    });
+    let d = geo.add_point(create_arbitrary((0., 5.)));
+    let e = geo.add_point(create_arbitrary((1., 5.)));
+    assert_eq!(
+        geo.redefine_shape(line, Shape::Line(d, e)),
+        Ok(vec![ThingID::PointID(meet), ThingID::LocusID(locus)])
+    );
+
+    // A free point becomes an intersection, but not of shapes defined from it
+    let lines_meet = Point::PrimIntersection(line, geo.add_shape(Shape::Line(b, d)));
+    assert!(geo.intersections().contains(&lines_meet));
+    assert_eq!(geo.redefine_point(c, lines_meet), Ok(Vec::new()));
+    assert!(geo.is_defined_from(ThingID::PointID(meet), ThingID::PointID(c)));
+    assert!(!geo.is_defined_from(ThingID::PointID(c), ThingID::PointID(meet)));
+    assert_eq!(
+        geo.redefine_point(b, Point::SecIntersection(circle, line)),
+        Err(RedefineError::Cycle(ThingID::ShapeID(circle)))
+    );
+    assert_eq!(
+        geo.redefine_shape(circle, Shape::Circle(meet, b)),
+        Err(RedefineError::Cycle(ThingID::PointID(meet)))
+    );
+    assert_eq!(
+        geo.redefine_point(a, Point::Transformed(a, Transformation::Translate(b, d))),
+        Err(RedefineError::Cycle(ThingID::PointID(a)))
+    );
+    assert_eq!(
+        geo.redefine_shape(circle, Shape::Circle(PointID(100), b)),
+        Err(RedefineError::Missing)
+    );
+}
+
+#[test]
+fn test_duplicates() {
+    let mut geo = Geometry::new();
+    let a = geo.add_point(create_arbitrary((0., 0.)));
+    let b = geo.add_point(create_arbitrary((2., 0.)));
+    let c = geo.add_point(create_arbitrary((0., 2.)));
+    let line = geo.add_shape(Shape::Line(a, b));
+    let circle = geo.add_shape(Shape::Circle(a, b));
+    let meet = geo.add_point(Point::PrimIntersection(line, circle));
+
+    // The same line, point B, circle and intersection again, constructed in other ways
+    let mid = geo.add_point(Point::Transformed(b, create_dilation(a, 0.5)));
+    let same_line = geo.add_shape(Shape::Line(mid, b));
+    let reflected = geo.add_point(Point::Transformed(c, Transformation::Reflect(same_line)));
+    let same_b = geo.add_point(Point::Transformed(mid, create_dilation(a, 2.)));
+    let same_circle = geo.add_shape(Shape::Circle(a, same_b));
+    let same_meet = geo.add_point(Point::PrimIntersection(same_line, same_circle));
+    let other = geo.add_shape(Shape::Circle(b, a));
+    // Only the same circle while C is as far from A as B is
+    let lucky = geo.add_shape(Shape::Circle(a, c));
+    let locus = geo.add_locus(Locus {
+        traced: same_meet,
+        driver: c,
+        path: same_circle,
+    });
+
+    let duplicates = geo.duplicates();
+    assert_eq!(
+        duplicates,
+        vec![
+            (ThingID::ShapeID(line), ThingID::ShapeID(same_line)),
+            (ThingID::PointID(b), ThingID::PointID(same_b)),
+            (ThingID::ShapeID(circle), ThingID::ShapeID(same_circle)),
+            (ThingID::PointID(meet), ThingID::PointID(same_meet)),
+        ]
+    );
+    // The free points are back where they were
+    assert_eq!(geo.resolve_point(&geo.points[&c]), Some((0., 2.)));
+    for (original, duplicate) in duplicates {
+        geo.replace(duplicate, original);
+    }
+    assert!(geo.duplicates().is_empty());
+    assert_eq!(geo.order.len(), 11);
+    assert_eq!(
+        geo.points[&reflected],
+        Point::Transformed(c, Transformation::Reflect(line))
+    );
+    assert_eq!(geo.loci[&locus].traced, meet);
+    assert_eq!(geo.loci[&locus].path, circle);
+    assert!(geo.shapes.contains_key(&other));
+    assert!(geo.shapes.contains_key(&lucky));
+}
+
+/// How far something `scale` away from the center of an inversion with radius `r` may move
+/// after inverting it twice. Rounding errors are magnified by `(scale / r)²` on the way
+#[cfg(test)]
+fn inversion_tolerance(scale: f64, r: f64) -> f64 {
+    (1e-6 + 1e3 * f64::EPSILON * (scale / r).powi(2)) * scale.max(1.)
+}
+
 #[test]
 quickcheck! {
     fn check_intersect_two_circles(
@@ -551,6 +2063,72 @@ quickcheck! {
         true
     }
 
+    fn check_inversion_involution(cx: f64, cy: f64, r: f64, x: f64, y: f64) -> bool {
+        let r = r.abs();
+        if r < 1e-3 {
+            return true;
+        }
+        let inversion = ResolvedTransformation::Inversion((cx, cy), r);
+
+        match inversion.apply_point((x, y)).and_then(|p| inversion.apply_point(p)) {
+            Some((x_, y_)) => {
+                let scale = (x - cx).hypot(y - cy) + x.abs().max(y.abs());
+                let tolerance = inversion_tolerance(scale, r);
+                (x - x_).abs() < tolerance && (y - y_).abs() < tolerance
+            }
+            None => (x, y) == (cx, cy),
+        }
+    }
+
+    fn check_inversion_involution_shapes(
+        cx: f64,
+        cy: f64,
+        r: f64,
+        ox: f64,
+        oy: f64,
+        rad: f64,
+        k: f64,
+        m: f64
+    ) -> bool {
+        let r = r.abs();
+        if r < 1e-3 || rad.abs() < 1e-3 {
+            return true;
+        }
+        let inversion = ResolvedTransformation::Inversion((cx, cy), r);
+
+        for shape in &[ResolvedShape::Circle((ox, oy), rad.abs()), ResolvedShape::Line(k, m)] {
+            // The shape comes back within a tolerance relative to how far it reaches from the
+            // center of inversion
+            let scale = match shape {
+                ResolvedShape::Circle(o, rad) => (o.0 - cx).hypot(o.1 - cy) + rad,
+                line => line.distance_to((cx, cy)),
+            };
+            if r * r < 1e-4 * scale {
+                // The image is too small to tell apart from a point at the center
+                continue;
+            }
+            let tolerance = inversion_tolerance(scale, r);
+            let close = |a: f64, b: f64| (a - b).abs() < tolerance.max(1e-6 * a.abs().max(b.abs()));
+            let twice = inversion.apply_shape(shape).and_then(|s| inversion.apply_shape(&s));
+            let ok = match (shape, twice) {
+                (ResolvedShape::Circle(o, rad), Some(ResolvedShape::Circle(o_, rad_))) => {
+                    close(o.0, o_.0) && close(o.1, o_.1) && close(*rad, rad_)
+                }
+                // Slopes of steep lines are too sensitive to compare, so the line has to come
+                // back through two of its points instead
+                (ResolvedShape::Line(..), Some(image)) if image.line_points().is_some() => {
+                    let (p, q) = shape.line_points().unwrap();
+                    close(image.distance_to(p), 0.) && close(image.distance_to(q), 0.)
+                }
+                _ => false,
+            };
+            if !ok {
+                return false;
+            }
+        }
+        true
+    }
+
     fn check_intersect_two_lines(
         x1: f64,
         y1: f64,
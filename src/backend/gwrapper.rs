@@ -6,16 +6,24 @@ use std::ops::{Deref, DerefMut};
 
 use super::geometry;
 
-#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+#[derive(Clone, Hash, PartialEq, Eq, Debug)]
 pub enum Thing {
     Point(geometry::Point),
     Shape(geometry::Shape),
+    Polygon(geometry::Polygon),
+    Locus(geometry::Locus),
 }
 
+pub use super::geometry::ThingID;
+
 #[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
-pub enum ThingID {
-    PointID(geometry::PointID),
-    ShapeID(geometry::ShapeID),
+pub struct MeasurementID(u64);
+
+impl Deref for MeasurementID {
+    type Target = u64;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
 }
 
 pub const DEFAULT_GROUP: Group = Group(0);
@@ -42,9 +50,66 @@ pub enum Visibility {
     Hidden,
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Fill {
+    pub color: (u8, u8, u8),
+    /// 0 is fully transparent, 1 is opaque
+    pub opacity: f64,
+}
+
+pub const DEFAULT_FILL: Fill = Fill {
+    color: (100, 150, 255),
+    opacity: 0.3,
+};
+
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub enum PointShape {
+    Circle,
+    Square,
+    Cross,
+}
+
+/// How an object is drawn
+#[derive(Clone, PartialEq, Debug)]
+pub struct Style {
+    /// None is the foreground color of the theme
+    pub color: Option<(u8, u8, u8)>,
+    /// The width of lines, in pixels
+    pub width: f64,
+    /// Lengths of dashes and gaps in pixels, taking turns. Empty is a solid line
+    pub dash: Vec<f64>,
+    pub point_shape: PointShape,
+    /// The radius of points, in pixels
+    pub point_size: f64,
+    /// Only polygons are filled. Polygons without a fill are only outlined
+    pub fill: Option<Fill>,
+}
+
+pub const DEFAULT_LOCUS_COLOR: (u8, u8, u8) = (160, 60, 200);
+pub const DEFAULT_WIDTH: f64 = 1.;
+pub const DEFAULT_POINT_SIZE: f64 = 5.;
+
+impl Default for Style {
+    fn default() -> Style {
+        Style {
+            color: None,
+            width: DEFAULT_WIDTH,
+            dash: Vec::new(),
+            point_shape: PointShape::Circle,
+            point_size: DEFAULT_POINT_SIZE,
+            fill: None,
+        }
+    }
+}
+
 pub struct GWrapper {
     pub geometry: geometry::Geometry,
     pub visibility: HashMap<ThingID, Visibility>,
+    /// Objects without a style are drawn in `default_style`
+    pub styles: HashMap<ThingID, Style>,
+    pub measurements: HashMap<MeasurementID, geometry::Measurement>,
+
+    last_measurement: MeasurementID,
 }
 
 impl Deref for GWrapper {
@@ -65,6 +130,207 @@ impl GWrapper {
         GWrapper {
             geometry,
             visibility: HashMap::new(),
+            styles: HashMap::new(),
+            measurements: HashMap::new(),
+            last_measurement: MeasurementID(0),
         }
     }
+
+    /// How objects are drawn before their style is changed
+    pub fn default_style(id: ThingID) -> Style {
+        match id {
+            ThingID::LocusID(_) => Style {
+                color: Some(DEFAULT_LOCUS_COLOR),
+                ..Style::default()
+            },
+            _ => Style::default(),
+        }
+    }
+
+    pub fn style(&self, id: ThingID) -> Style {
+        self.styles
+            .get(&id)
+            .cloned()
+            .unwrap_or_else(|| GWrapper::default_style(id))
+    }
+
+    pub fn style_mut(&mut self, id: ThingID) -> &mut Style {
+        self.styles
+            .entry(id)
+            .or_insert_with(|| GWrapper::default_style(id))
+    }
+
+    pub fn add_measurement(&mut self, measurement: geometry::Measurement) -> MeasurementID {
+        self.last_measurement = MeasurementID(*self.last_measurement + 1);
+        self.measurements.insert(self.last_measurement, measurement);
+        self.last_measurement
+    }
+
+    /// The current value of a measurement, None if it can't be resolved
+    pub fn measure(&self, id: MeasurementID) -> Option<f64> {
+        self.geometry
+            .resolve_measurement(self.measurements.get(&id)?)
+    }
+
+    /// The corners of the smallest rectangle containing every object that can be resolved.
+    /// Lines are only bounded by their points. None if there is nothing to show.
+    pub fn bounds(&self, include_hidden: bool) -> Option<((f64, f64), (f64, f64))> {
+        let shown =
+            |id: ThingID| include_hidden || self.visibility.get(&id) != Some(&Visibility::Hidden);
+
+        let mut positions = Vec::new();
+        for (id, point) in &self.geometry.points {
+            if shown(ThingID::PointID(*id)) {
+                positions.extend(self.geometry.resolve_point(point));
+            }
+        }
+        for (id, shape) in &self.geometry.shapes {
+            if !shown(ThingID::ShapeID(*id)) {
+                continue;
+            }
+            if let Some(geometry::ResolvedShape::Circle(center, rad)) =
+                self.geometry.resolve_shape(shape)
+            {
+                positions.push((center.0 - rad, center.1 - rad));
+                positions.push((center.0 + rad, center.1 + rad));
+            }
+        }
+        for id in self.geometry.loci.keys() {
+            if shown(ThingID::LocusID(*id)) {
+                for path in self.geometry.locus_path(*id).into_iter().flatten() {
+                    positions.extend(path.iter().cloned());
+                }
+            }
+        }
+
+        let first = *positions.first()?;
+        Some(
+            positions
+                .into_iter()
+                .fold((first, first), |(min, max), pos| {
+                    (
+                        (min.0.min(pos.0), min.1.min(pos.1)),
+                        (max.0.max(pos.0), max.1.max(pos.1)),
+                    )
+                }),
+        )
+    }
+
+    /// Makes everything defined or measured from `old` use `new` instead, and removes `old`
+    pub fn replace(&mut self, old: ThingID, new: ThingID) {
+        self.geometry.replace(old, new);
+        for measurement in self.measurements.values_mut() {
+            measurement.replace(old, new);
+        }
+        self.visibility.remove(&old);
+        self.styles.remove(&old);
+    }
+
+    /// Merges every duplicate into the object it's the same as. That object is shown if the
+    /// duplicate was, and takes the duplicate's style if it has none of its own, so merging
+    /// into a hidden intermediate object doesn't hide what was drawn. Returns how many objects
+    /// were merged.
+    pub fn merge_duplicates(&mut self) -> usize {
+        let duplicates = self.geometry.duplicates();
+        for (original, duplicate) in &duplicates {
+            if self.visibility.get(duplicate) != Some(&Visibility::Hidden) {
+                self.visibility.remove(original);
+            }
+            if !self.styles.contains_key(original) {
+                if let Some(style) = self.styles.get(duplicate).cloned() {
+                    self.styles.insert(*original, style);
+                }
+            }
+            let (_, sliders) = self.geometry.dependencies(*duplicate);
+            self.replace(*duplicate, *original);
+            for slider in sliders {
+                self.geometry.remove_unused_slider(slider);
+            }
+        }
+        duplicates.len()
+    }
+
+    /// Adds the image of an object under a transformation
+    pub fn add_transformed(
+        &mut self,
+        id: ThingID,
+        transformation: geometry::Transformation,
+    ) -> ThingID {
+        match id {
+            ThingID::PointID(id) => ThingID::PointID(
+                self.geometry
+                    .add_point(geometry::Point::Transformed(id, transformation)),
+            ),
+            ThingID::ShapeID(id) => ThingID::ShapeID(
+                self.geometry
+                    .add_shape(geometry::Shape::Transformed(id, transformation)),
+            ),
+            ThingID::PolygonID(id) => {
+                let vertices = self
+                    .geometry
+                    .polygons
+                    .get(&id)
+                    .map(|polygon| polygon.0.clone())
+                    .unwrap_or_default();
+                let images = vertices
+                    .into_iter()
+                    .map(|vertex| {
+                        self.geometry
+                            .add_point(geometry::Point::Transformed(vertex, transformation))
+                    })
+                    .collect();
+                let image =
+                    ThingID::PolygonID(self.geometry.add_polygon(geometry::Polygon(images)));
+                if let Some(style) = self.styles.get(&ThingID::PolygonID(id)).cloned() {
+                    self.styles.insert(image, style);
+                }
+                image
+            }
+            ThingID::LocusID(id) => {
+                let mut locus = match self.geometry.loci.get(&id) {
+                    Some(locus) => *locus,
+                    None => return ThingID::LocusID(id),
+                };
+                // The image traces the image of the traced point
+                locus.traced = self
+                    .geometry
+                    .add_point(geometry::Point::Transformed(locus.traced, transformation));
+                self.visibility
+                    .insert(ThingID::PointID(locus.traced), Visibility::Hidden);
+                ThingID::LocusID(self.geometry.add_locus(locus))
+            }
+        }
+    }
+}
+
+#[test]
+fn test_merge_duplicates() {
+    use super::geometry::{create_arbitrary, Point, Shape, Slider};
+
+    // A hidden circle and point on it, like those a regular polygon is constructed with
+    let mut ctx = GWrapper::new(geometry::Geometry::new());
+    let a = ctx.geometry.add_point(create_arbitrary((0., 0.)));
+    let b = ctx.geometry.add_point(create_arbitrary((2., 0.)));
+    let circle = ctx.geometry.add_shape(Shape::Circle(a, b));
+    let angle = ctx.geometry.add_slider(Slider::new("a", 0., 360., 1., 90.));
+    let on_circle = ctx.geometry.add_point(Point::OnCircle(circle, angle));
+    let hidden = [ThingID::ShapeID(circle), ThingID::PointID(on_circle)];
+    for id in &hidden {
+        ctx.visibility.insert(*id, Visibility::Hidden);
+    }
+
+    // The same circle and point again, drawn and colored by hand
+    let drawn = ThingID::ShapeID(ctx.geometry.add_shape(Shape::Circle(a, b)));
+    let other_angle = ctx.geometry.add_slider(Slider::new("b", 0., 360., 1., 90.));
+    let drawn_point = ctx.geometry.add_point(Point::OnCircle(circle, other_angle));
+    ctx.style_mut(drawn).color = Some((200, 40, 40));
+
+    assert_eq!(ctx.merge_duplicates(), 2);
+    for id in &hidden {
+        assert_eq!(ctx.visibility.get(id), None);
+    }
+    assert_eq!(ctx.style(hidden[0]).color, Some((200, 40, 40)));
+    assert!(!ctx.geometry.points.contains_key(&drawn_point));
+    assert!(ctx.geometry.sliders.contains_key(&angle));
+    assert!(!ctx.geometry.sliders.contains_key(&other_angle));
 }
@@ -1,2 +1,6 @@
+pub mod constructions;
+pub mod format;
 pub mod geometry;
 pub mod gwrapper;
+pub mod protocol;
+pub mod puzzle;
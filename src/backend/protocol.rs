@@ -0,0 +1,225 @@
+// The construction protocol, a list of every step taken to build a figure
+
+use std::collections::HashMap;
+
+use super::format;
+use super::geometry::{Point, Shape, ThingID, Transformation};
+use super::gwrapper::{GWrapper, Visibility};
+
+/// Names every object by its kind and creation order. Points are A, B, ..., Z, A1, B1, ...,
+/// circles c1, c2, ..., lines l1, l2, ..., other shapes s1, s2, ..., polygons poly1, poly2, ...
+/// and loci loc1, loc2, ...
+pub fn names(ctx: &GWrapper) -> HashMap<ThingID, String> {
+    let mut names = HashMap::new();
+    let (mut points, mut circles, mut lines, mut shapes) = (0, 0, 0, 0);
+    let (mut polygons, mut loci) = (0, 0);
+
+    for id in &ctx.order {
+        let name = match id {
+            ThingID::PointID(_) => {
+                let letter = (b'A' + (points % 26) as u8) as char;
+                let name = match points / 26 {
+                    0 => letter.to_string(),
+                    n => format!("{}{}", letter, n),
+                };
+                points += 1;
+                name
+            }
+            ThingID::ShapeID(shape_id) => match ctx.shapes.get(shape_id) {
+                Some(Shape::Circle(..)) => {
+                    circles += 1;
+                    format!("c{}", circles)
+                }
+                Some(Shape::Line(..)) => {
+                    lines += 1;
+                    format!("l{}", lines)
+                }
+                _ => {
+                    shapes += 1;
+                    format!("s{}", shapes)
+                }
+            },
+            ThingID::PolygonID(_) => {
+                polygons += 1;
+                format!("poly{}", polygons)
+            }
+            ThingID::LocusID(_) => {
+                loci += 1;
+                format!("loc{}", loci)
+            }
+        };
+        names.insert(*id, name);
+    }
+    names
+}
+
+/// Describes how an object was constructed, e.g. "circle c1 with center A through B"
+pub fn describe(ctx: &GWrapper, names: &HashMap<ThingID, String>, id: ThingID) -> String {
+    let name = |id: ThingID| names.get(&id).map(|name| &name[..]).unwrap_or("?");
+    let point = |id| name(ThingID::PointID(id));
+    let shape = |id| name(ThingID::ShapeID(id));
+    let slider = |id| {
+        ctx.sliders
+            .get(&id)
+            .map(|slider| &slider.name[..])
+            .unwrap_or("?")
+    };
+
+    let transformation = |t: &Transformation| match t {
+        Transformation::Reflect(line) => format!("reflection in {}", shape(*line)),
+        Transformation::Rotate(center, (a, vertex, b)) => format!(
+            "rotation around {} by the angle {}{}{}",
+            point(*center),
+            point(*a),
+            point(*vertex),
+            point(*b)
+        ),
+        Transformation::Translate(from, to) => {
+            format!("translation by {}{}", point(*from), point(*to))
+        }
+        Transformation::Dilate(center, factor) => {
+            format!("dilation from {} by {}", point(*center), factor)
+        }
+        Transformation::DilateBySlider(center, factor) => {
+            format!("dilation from {} by {}", point(*center), slider(*factor))
+        }
+        Transformation::Invert(circle) => format!("inversion in {}", shape(*circle)),
+    };
+
+    let description = match id {
+        ThingID::PointID(point_id) => match ctx.points.get(&point_id) {
+            Some(Point::Arbitrary((x, y))) => {
+                format!("point {} at ({:.3}, {:.3})", name(id), **x, **y)
+            }
+            Some(Point::PrimIntersection(a, b)) => format!(
+                "point {} at the intersection of {} and {}",
+                name(id),
+                shape(*a),
+                shape(*b)
+            ),
+            Some(Point::SecIntersection(a, b)) => format!(
+                "point {} at the second intersection of {} and {}",
+                name(id),
+                shape(*a),
+                shape(*b)
+            ),
+            Some(Point::Transformed(original, t)) => format!(
+                "point {}, the image of {} under {}",
+                name(id),
+                point(*original),
+                transformation(t)
+            ),
+            Some(Point::OnCircle(circle, angle)) => format!(
+                "point {} on {} at the angle {}",
+                name(id),
+                shape(*circle),
+                slider(*angle)
+            ),
+            Some(Point::OnLine(line, along)) => format!(
+                "point {} on {} at {} along it",
+                name(id),
+                shape(*line),
+                slider(*along)
+            ),
+            None => format!("point {}", name(id)),
+        },
+        ThingID::ShapeID(shape_id) => match ctx.shapes.get(&shape_id) {
+            Some(Shape::Circle(center, through)) => format!(
+                "circle {} with center {} through {}",
+                name(id),
+                point(*center),
+                point(*through)
+            ),
+            Some(Shape::Line(a, b)) => {
+                format!("line {} through {} and {}", name(id), point(*a), point(*b))
+            }
+            Some(Shape::Transformed(original, t)) => format!(
+                "{}, the image of {} under {}",
+                name(id),
+                shape(*original),
+                transformation(t)
+            ),
+            None => format!("shape {}", name(id)),
+        },
+        ThingID::PolygonID(polygon_id) => match ctx.polygons.get(&polygon_id) {
+            Some(polygon) => format!(
+                "polygon {} with the vertices {}",
+                name(id),
+                polygon
+                    .0
+                    .iter()
+                    .map(|vertex| point(*vertex))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            None => format!("polygon {}", name(id)),
+        },
+        ThingID::LocusID(locus_id) => match ctx.loci.get(&locus_id) {
+            Some(locus) => format!(
+                "locus {} of {} as {} moves along {}",
+                name(id),
+                point(locus.traced),
+                point(locus.driver),
+                shape(locus.path)
+            ),
+            None => format!("locus {}", name(id)),
+        },
+    };
+
+    if let Some(Visibility::Hidden) = ctx.visibility.get(&id) {
+        format!("{} (hidden)", description)
+    } else {
+        description
+    }
+}
+
+/// Every step, numbered from 1
+pub fn steps(ctx: &GWrapper) -> Vec<String> {
+    let names = names(ctx);
+    ctx.order
+        .iter()
+        .enumerate()
+        .map(|(i, id)| format!("{}: {}", i + 1, describe(ctx, &names, *id)))
+        .collect()
+}
+
+/// The protocol as plain text, one step per line, followed by how the object is drawn if it
+/// isn't the default
+pub fn export(ctx: &GWrapper) -> String {
+    let mut text = String::new();
+    for (step, id) in steps(ctx).into_iter().zip(&ctx.order) {
+        text.push_str(&step);
+        let style = format::style_words(&ctx.style(*id), &GWrapper::default_style(*id));
+        if !style.is_empty() {
+            text.push_str(&format!(", drawn with {}", style.join(", ")));
+        }
+        text.push('\n');
+    }
+    text
+}
+
+#[test]
+fn test_protocol() {
+    use super::geometry::{create_arbitrary, Geometry};
+
+    let mut ctx = GWrapper::new(Geometry::new());
+    let a = ctx.add_point(create_arbitrary((0., 0.)));
+    let b = ctx.add_point(create_arbitrary((1., 0.)));
+    let c1 = ctx.add_shape(Shape::Circle(a, b));
+    let c2 = ctx.add_shape(Shape::Circle(b, a));
+    let c = ctx.add_point(Point::SecIntersection(c1, c2));
+    ctx.visibility
+        .insert(ThingID::ShapeID(c2), Visibility::Hidden);
+    let l1 = ctx.add_shape(Shape::Line(a, c));
+    ctx.style_mut(ThingID::ShapeID(l1)).width = 3.;
+
+    assert_eq!(
+        export(&ctx),
+        "1: point A at (0.000, 0.000)\n\
+         2: point B at (1.000, 0.000)\n\
+         3: circle c1 with center A through B\n\
+         4: circle c2 with center B through A (hidden)\n\
+         5: point C at the second intersection of c1 and c2\n\
+         6: line l1 through A and C, drawn with width 3\n"
+    );
+}
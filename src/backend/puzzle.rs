@@ -0,0 +1,274 @@
+// Levels for the puzzle mode. A level file is a construction (see `format`) with a few extra
+// lines:
+//
+//     level Perpendicular bisector        the name of the level
+//     text Construct the perpendicular    what to do
+//     goal                                the objects after this are the solution, not shown
+//     target l                            an object the player has to construct
+//
+// Only the circles and lines the player adds count as moves, so the tools making objects any
+// other way can't be used (see `ToolKind::in_puzzles`). A regular polygon or a macro counts the
+// circles and lines it's constructed with.
+
+use super::format::{self, Reader};
+use super::geometry::{self, Point, PointID, Shape};
+use super::gwrapper::{GWrapper, ThingID};
+
+/// How close a constructed object has to be to a target
+pub const MATCH_TOLERANCE: f64 = 1e-6;
+
+pub struct Puzzle {
+    pub name: String,
+    pub text: String,
+    /// The number of moves the level was solved in
+    pub solved_in: Option<usize>,
+
+    goal: GWrapper,
+    targets: Vec<ThingID>,
+    free_points: Vec<PointID>,
+    start_moves: usize,
+}
+
+/// Whether a file is a level rather than a plain construction
+pub fn is_level(text: &str) -> bool {
+    text.lines().any(|line| {
+        matches!(
+            format::split_line(line).first(),
+            Some(&"level") | Some(&"goal") | Some(&"target")
+        )
+    })
+}
+
+/// Reads a level file, returning the starting objects and the puzzle
+pub fn load(text: &str) -> Result<(GWrapper, Puzzle), String> {
+    let mut start = GWrapper::new(geometry::Geometry::new());
+    let mut goal = GWrapper::new(geometry::Geometry::new());
+    // The start of the goal is read the same way, so the IDs of the starting objects agree
+    let (mut start_reader, mut goal_reader) = (Reader::new(), Reader::new());
+
+    let (mut name, mut description) = (String::new(), String::new());
+    let mut in_goal = false;
+    let mut targets = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let err = |err: String| format!("Line {}: {}", i + 1, err);
+        let words = format::split_line(line);
+        match words.first() {
+            Some(&"level") => name = words[1..].join(" "),
+            Some(&"text") => description = words[1..].join(" "),
+            Some(&"goal") => in_goal = true,
+            Some(&"target") => {
+                for target in &words[1..] {
+                    match goal_reader.names.get(*target) {
+                        Some(ThingID::PolygonID(_)) | Some(ThingID::LocusID(_)) => {
+                            return Err(err(format!(
+                                "The target `{}` isn't a point or a shape",
+                                target
+                            )))
+                        }
+                        Some(id) => targets.push(*id),
+                        None => return Err(err(format!("Unknown object `{}`", target))),
+                    }
+                }
+            }
+            _ => {
+                if !in_goal {
+                    start_reader.read_line(&mut start, line).map_err(err)?;
+                }
+                goal_reader.read_line(&mut goal, line).map_err(err)?;
+            }
+        }
+    }
+
+    if targets.is_empty() {
+        return Err("The level has no targets".into());
+    }
+
+    let puzzle = Puzzle {
+        name,
+        text: description,
+        solved_in: None,
+        goal,
+        targets,
+        free_points: start
+            .points
+            .iter()
+            .filter(|(_, point)| matches!(point, Point::Arbitrary(_)))
+            .map(|(id, _)| *id)
+            .collect(),
+        start_moves: count_moves(&start),
+    };
+    Ok((start, puzzle))
+}
+
+fn count_moves(ctx: &GWrapper) -> usize {
+    ctx.shapes
+        .values()
+        .filter(|shape| matches!(shape, Shape::Circle(..) | Shape::Line(..)))
+        .count()
+}
+
+impl Puzzle {
+    pub fn moves(&self, world: &GWrapper) -> usize {
+        count_moves(world).saturating_sub(self.start_moves)
+    }
+
+    /// Checks if the level is solved, and if so records the number of moves
+    pub fn check(&mut self, world: &mut GWrapper) {
+        if self.solved_in.is_none() && self.is_solved(world) {
+            self.solved_in = Some(self.moves(world));
+        }
+    }
+
+    /// Whether every target has been constructed, and stays constructed when the free points
+    /// are moved around
+    pub fn is_solved(&mut self, world: &mut GWrapper) -> bool {
+        let original: Vec<(PointID, Point)> = self
+            .free_points
+            .iter()
+            .filter_map(|id| world.points.get(id).map(|point| (*id, *point)))
+            .collect();
+        let positions: Vec<(f64, f64)> = original
+            .iter()
+            .filter_map(|(_, point)| world.resolve_point(point))
+            .collect();
+
+        // The goal follows the free points wherever the player has moved them
+        for (id, point) in &original {
+            self.goal.points.insert(*id, *point);
+        }
+        self.goal.invalidate_cache();
+
+        let mut candidates: Vec<Vec<ThingID>> = self
+            .targets
+            .iter()
+            .map(|target| {
+                let things: Vec<ThingID> = match target {
+                    ThingID::PointID(_) => world
+                        .points
+                        .keys()
+                        .map(|id| ThingID::PointID(*id))
+                        .collect(),
+                    _ => world
+                        .shapes
+                        .keys()
+                        .map(|id| ThingID::ShapeID(*id))
+                        .collect(),
+                };
+                things
+                    .into_iter()
+                    .filter(|thing| self.matches(world, *target, *thing))
+                    .collect()
+            })
+            .collect();
+
+        for trial in 0..geometry::PERTURBATIONS {
+            if candidates.iter().any(|things| things.is_empty()) {
+                break;
+            }
+
+            for ((id, _), moved) in original.iter().zip(geometry::perturbed(&positions, trial)) {
+                let moved = geometry::create_arbitrary(moved);
+                world.points.insert(*id, moved);
+                self.goal.points.insert(*id, moved);
+            }
+            world.invalidate_cache();
+            self.goal.invalidate_cache();
+
+            for (target, things) in self.targets.iter().zip(candidates.iter_mut()) {
+                things.retain(|thing| self.matches(world, *target, *thing));
+            }
+        }
+
+        for (id, point) in &original {
+            world.points.insert(*id, *point);
+            self.goal.points.insert(*id, *point);
+        }
+        world.invalidate_cache();
+        self.goal.invalidate_cache();
+
+        candidates.iter().all(|things| !things.is_empty())
+    }
+
+    fn matches(&self, world: &GWrapper, target: ThingID, thing: ThingID) -> bool {
+        match (target, thing) {
+            (ThingID::PointID(target), ThingID::PointID(thing)) => {
+                let target = self
+                    .goal
+                    .points
+                    .get(&target)
+                    .and_then(|point| self.goal.resolve_point(point));
+                let thing = world
+                    .points
+                    .get(&thing)
+                    .and_then(|point| world.resolve_point(point));
+                match (target, thing) {
+                    (Some(a), Some(b)) => {
+                        (a.0 - b.0).abs() < MATCH_TOLERANCE && (a.1 - b.1).abs() < MATCH_TOLERANCE
+                    }
+                    (None, None) => true,
+                    _ => false,
+                }
+            }
+            (ThingID::ShapeID(target), ThingID::ShapeID(thing)) => {
+                let target = self
+                    .goal
+                    .shapes
+                    .get(&target)
+                    .and_then(|shape| self.goal.resolve_shape(shape));
+                let thing = world
+                    .shapes
+                    .get(&thing)
+                    .and_then(|shape| world.resolve_shape(shape));
+                match (target, thing) {
+                    (Some(a), Some(b)) => a.approx_eq(&b, MATCH_TOLERANCE),
+                    (None, None) => true,
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+#[test]
+fn test_puzzle() {
+    let level = "
+        level Perpendicular bisector
+        text Construct the perpendicular bisector of AB
+        A = point 0 0
+        B = point 3 1
+        goal
+        c1 = circle A B
+        c2 = circle B A
+        P = intersection c1 c2
+        Q = intersection2 c1 c2
+        l = line P Q
+        target l
+    ";
+    let (mut world, mut puzzle) = load(level).unwrap();
+    assert_eq!(puzzle.name, "Perpendicular bisector");
+
+    let (a, b) = (
+        *world.points.keys().min_by_key(|id| ***id).unwrap(),
+        *world.points.keys().max_by_key(|id| ***id).unwrap(),
+    );
+
+    // A line that only happens to be right
+    let p = world.add_point(geometry::create_arbitrary((2., -1.5)));
+    let q = world.add_point(geometry::create_arbitrary((1., 1.5)));
+    world.add_shape(Shape::Line(p, q));
+    puzzle.check(&mut world);
+    assert_eq!(puzzle.solved_in, None);
+
+    let c1 = world.add_shape(Shape::Circle(a, b));
+    let c2 = world.add_shape(Shape::Circle(b, a));
+    let p = world.add_point(Point::SecIntersection(c1, c2));
+    let q = world.add_point(Point::PrimIntersection(c1, c2));
+    world.add_shape(Shape::Line(p, q));
+    puzzle.check(&mut world);
+    assert_eq!(puzzle.solved_in, Some(4));
+
+    assert!(load("A = point 0 0").is_err());
+    assert!(load("A = point 0 0\nl = line A B\ntarget l").is_err());
+}
@@ -1,27 +1,162 @@
+mod style_editor;
+
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
-use super::backend::{geometry, gwrapper};
+use super::backend::{format, geometry, gwrapper, protocol, puzzle};
 use super::graphics::*;
-use super::icons;
-use super::tool::{SelectedStatus, ToolKind};
+use super::keymap::{Action, Chord, Keymap};
+use super::macros;
+use super::recorder::Recorder;
+use super::theme::Theme;
+use super::tool::{tools, Hover, SelectedStatus, ToolKind};
 use super::transform::Transform;
 
 use ytesrev::drawable::State;
 use ytesrev::prelude::*;
+use ytesrev::sdl2::clipboard::ClipboardUtil;
 use ytesrev::sdl2::event::Event;
 use ytesrev::sdl2::keyboard::Keycode;
 use ytesrev::sdl2::mouse::MouseButton;
 
+/// Radius of the arc marking a measured angle, in pixels
+pub const ANGLE_ARC_RADIUS: f64 = 20.;
+/// The radius of the ring around a point a click would pick, in pixels
+pub const HOVER_RADIUS: f64 = 9.;
+/// How far the arrow keys move the view, in pixels
+pub const PAN_STEP: f64 = 50.;
+/// The name of a new construction's tab
+pub const UNTITLED: &str = "untitled";
+/// How much + and - zoom in and out
+pub const KEY_ZOOM: f64 = 1.25;
+/// The width of the ring around selected points, in pixels
+pub const SELECTION_RING: f64 = 2.;
+/// How many trails are kept while dragging, the oldest being dropped first
+pub const MAX_TRAILS: usize = 5000;
+/// Where the construction protocol is exported to
+pub const PROTOCOL_FILE: &str = "protocol.txt";
+/// New constructions are saved to construction.txt, construction2.txt and so on
+pub const SAVE_FILE: &str = "construction";
+/// How many seconds a message stays over the board
+pub const MESSAGE_TIME: f64 = 4.;
+
 pub struct DrawingBoard {
     pub state: Arc<Mutex<DState>>,
 }
 
+/// A faded copy of a traced object, left behind while dragging
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Trail {
+    Point((f64, f64)),
+    Shape(geometry::ResolvedShape),
+}
+
 pub struct View {
     pub transform: Transform,
     pub mouse_last: Point,
     pub moving_screen: bool,
     pub scrolling: f64,
     pub show_hidden: bool,
+    pub show_grid: bool,
+    /// Whether new free points are put on the closest grid intersection
+    pub snap_to_grid: bool,
+    /// The number of construction steps shown while replaying, None shows everything
+    pub replay: Option<usize>,
+    pub show_protocol: bool,
+    /// Whether the key bindings are listed
+    pub show_help: bool,
+    /// Objects leaving trails when a point is dragged
+    pub traced: HashSet<gwrapper::ThingID>,
+    /// The oldest trails are dropped first, beyond MAX_TRAILS
+    pub trails: VecDeque<Trail>,
+    /// The slider whose handle is being dragged
+    pub dragging_slider: Option<geometry::SliderID>,
+    /// The last message, and for how many more seconds it's shown
+    pub message: Option<(String, f64)>,
+    /// The question being answered, which takes every key until Enter or Escape
+    pub prompt: Option<Prompt>,
+    /// What the tool last showed under the mouse, taken whenever the tool or the construction
+    /// may have changed
+    pub hover: Cell<Option<CachedHover>>,
+}
+
+/// A hover, with the position of the mouse and the zoom it was found at
+type CachedHover = (((f64, f64), f64), Hover);
+
+/// A question at the top of the board, answered by typing and pressing Enter
+pub struct Prompt {
+    pub label: String,
+    /// The text typed in so far
+    pub typed: String,
+    pub asker: Asker,
+}
+
+/// What to do with the system clipboard, which SDL only gives access to through the window, so
+/// it waits until the board is next drawn
+pub enum ClipboardRequest {
+    /// Puts the text on the clipboard
+    Copy(String),
+    /// Pastes the construction text on the clipboard
+    Paste,
+}
+
+/// Who gets the answer to a prompt
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Asker {
+    /// The current tool
+    Tool,
+    /// Closes the shown tab without saving it on `y`
+    CloseTab,
+    /// Names the macro just made and adds it to the library
+    MacroName,
+}
+
+impl View {
+    pub fn new(transform: Transform) -> View {
+        View {
+            transform,
+            mouse_last: Point::new(0, 0),
+            moving_screen: false,
+            scrolling: 0.,
+            show_hidden: true,
+            show_grid: false,
+            snap_to_grid: false,
+            replay: None,
+            show_protocol: false,
+            show_help: false,
+            traced: HashSet::new(),
+            trails: VecDeque::new(),
+            dragging_slider: None,
+            message: None,
+            prompt: None,
+            hover: Cell::new(None),
+        }
+    }
+
+    /// Shows `text` over the board for a while, replacing the last message
+    pub fn message<S: Into<String>>(&mut self, text: S) {
+        self.message = Some((text.into(), MESSAGE_TIME));
+    }
+
+    /// Asks for `label` at the top of the board, starting the answer with `typed`
+    pub fn ask<S: Into<String>>(&mut self, label: S, typed: String, asker: Asker) {
+        self.prompt = Some(Prompt {
+            label: label.into(),
+            typed,
+            asker,
+        });
+    }
+}
+
+/// The `n`th file new constructions are saved to, counting from 1
+pub fn save_path(n: usize) -> PathBuf {
+    match n {
+        1 => PathBuf::from(format!("{}.txt", SAVE_FILE)),
+        n => PathBuf::from(format!("{}{}.txt", SAVE_FILE, n)),
+    }
 }
 
 impl DrawingBoard {
@@ -29,98 +164,209 @@ impl DrawingBoard {
         DrawingBoard { state }
     }
 
-    fn try_draw(&self, canvas: &mut Canvas<Window>, settings: DrawSettings) -> Result<(), String> {
+    fn try_draw(&self, canvas: &mut Canvas<Window>) -> Result<(), String> {
         let state = self.state.lock().unwrap();
 
         let (w, h) = canvas.window().size();
 
-        for (id, obj) in &state.world.shapes {
-            let mut alpha = 255;
-            if let Some(gwrapper::Visibility::Hidden) =
-                state.world.visibility.get(&gwrapper::ThingID::ShapeID(*id))
-            {
-                if !state.view.show_hidden {
-                    continue;
+        // While replaying, only the first steps of the construction are shown
+        let steps: HashMap<gwrapper::ThingID, usize> = state
+            .world
+            .order
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (*id, i))
+            .collect();
+        let shown = |id: gwrapper::ThingID| match state.view.replay {
+            Some(n) => steps.get(&id).map(|step| *step < n).unwrap_or(true),
+            None => true,
+        };
+
+        let theme = &state.theme;
+        if state.view.show_grid {
+            draw_grid(canvas, &state.view.transform, theme, (w, h))?;
+        }
+
+        let zoom = state.view.transform.point_zoom();
+
+        // Trails and ghosts of new objects don't have a style of their own
+        let default_style = gwrapper::Style::default();
+        canvas.set_draw_color(theme.ink(default_style.color, theme.trail_alpha));
+        for trail in &state.view.trails {
+            match trail {
+                Trail::Point(pos) => {
+                    let p_px = state.view.transform.transform_po_to_px(*pos);
+                    draw_point(canvas, p_px, &default_style, zoom)?;
+                }
+                Trail::Shape(shape) => {
+                    draw_shape(canvas, &state.view.transform, *shape, &default_style)?
                 }
-                alpha = 64;
             }
+        }
 
-            canvas.set_draw_color(Color::RGBA(0, 0, 0, alpha));
-            if let Some(selected) = state
-                .current_tool
-                .selected(&state.world)
-                .get(&gwrapper::ThingID::ShapeID(*id))
-            {
-                let col = match selected {
-                    SelectedStatus::Primary => (0, 255, 0),
-                    SelectedStatus::Active => (128, 195, 255),
+        // How opaque an object is drawn, None if it isn't drawn at all
+        let alpha = |id: gwrapper::ThingID| {
+            if !shown(id) {
+                return None;
+            }
+            match state.world.visibility.get(&id) {
+                Some(gwrapper::Visibility::Hidden) if !state.view.show_hidden => None,
+                Some(gwrapper::Visibility::Hidden) => Some(theme.hidden_alpha),
+                _ => Some(255),
+            }
+        };
+        let selected = state.current_tool.selected(&state.world);
+        let selected_color = |id: gwrapper::ThingID, alpha: u8| {
+            selected.get(&id).map(|status| {
+                let Color { r, g, b, .. } = match status {
+                    SelectedStatus::Primary => theme.primary,
+                    SelectedStatus::Active => theme.active,
                 };
-                canvas.set_draw_color(Color::RGBA(col.0, col.1, col.2, alpha));
+                Color::RGBA(r, g, b, alpha)
+            })
+        };
+        // Selected objects are drawn in the color of the selection
+        let color = |id: gwrapper::ThingID, style: &gwrapper::Style, alpha: u8| {
+            selected_color(id, alpha).unwrap_or_else(|| theme.ink(style.color, alpha))
+        };
+
+        for (id, polygon) in &state.world.polygons {
+            let id = gwrapper::ThingID::PolygonID(*id);
+            let alpha = match alpha(id) {
+                Some(alpha) => alpha,
+                None => continue,
+            };
+            let style = state.world.style(id);
+
+            if let Some(vertices) = state.world.resolve_polygon(polygon) {
+                let mut vertices_px: Vec<_> = vertices
+                    .into_iter()
+                    .map(|vertex| state.view.transform.transform_po_to_px(vertex))
+                    .collect();
+
+                if let Some(fill) = style.fill {
+                    let (r, g, b) = fill.color;
+                    let opacity = fill.opacity * alpha as f64;
+                    canvas.set_draw_color(Color::RGBA(r, g, b, opacity as u8));
+                    fill_polygon(canvas, &vertices_px)?;
+                }
+
+                canvas.set_draw_color(color(id, &style, alpha));
+                if let Some(first) = vertices_px.first().cloned() {
+                    vertices_px.push(first);
+                }
+                stroke_path(canvas, &vertices_px, style.width, &style.dash)?;
             }
+        }
+
+        for (id, obj) in &state.world.shapes {
+            let id = gwrapper::ThingID::ShapeID(*id);
+            let alpha = match alpha(id) {
+                Some(alpha) => alpha,
+                None => continue,
+            };
+            let style = state.world.style(id);
 
+            canvas.set_draw_color(color(id, &style, alpha));
             if let Some(ro) = state.world.resolve_shape(obj) {
-                match ro {
-                    geometry::ResolvedShape::Circle(center, rad) => {
-                        let center_px = state.view.transform.transform_po_to_px(center);
-                        draw_circle(canvas, center_px, rad * state.view.transform.scale)?;
-                    }
-                    geometry::ResolvedShape::Line(k, m) => {
-                        let start_x = state.view.transform.transform_px_to_po((0., 0.)).0;
-                        let start_y = k * start_x + m;
-                        let start_point = (start_x, start_y);
+                draw_shape(canvas, &state.view.transform, ro, &style)?;
+            }
+        }
 
-                        let end_x = state.view.transform.transform_px_to_po((w as f64, 0.)).0;
-                        let end_y = k * end_x + m;
-                        let end_point = (end_x, end_y);
+        for id in state.world.loci.keys() {
+            let locus_id = *id;
+            let id = gwrapper::ThingID::LocusID(locus_id);
+            let alpha = match alpha(id) {
+                Some(alpha) => alpha,
+                None => continue,
+            };
+            let style = state.world.style(id);
 
-                        let start_px = state.view.transform.transform_po_to_px(start_point);
-                        let end_px = state.view.transform.transform_po_to_px(end_point);
+            canvas.set_draw_color(color(id, &style, alpha));
 
-                        utils::line_aa(canvas, (start_px.0, start_px.1), (end_px.0, end_px.1));
-                    }
-                    geometry::ResolvedShape::LineUp(x) => {
-                        let x_px = state.view.transform.transform_po_to_px((x, 0.)).0;
-                        utils::line_aa(canvas, (x_px, 0.), (x_px, h as f64));
+            // Jumps longer than the window are where the traced point switches branch or goes
+            // off to infinity
+            let max_jump = (w as f64).hypot(h as f64);
+            for path in state.world.locus_path(locus_id).into_iter().flatten() {
+                let path_px: Vec<_> = path
+                    .iter()
+                    .map(|pos| state.view.transform.transform_po_to_px(*pos))
+                    .collect();
+                let mut start = 0;
+                for i in 1..path_px.len() + 1 {
+                    let (a, b) = (path_px[i - 1], path_px.get(i).cloned());
+                    if b.map_or(true, |b| (b.0 - a.0).hypot(b.1 - a.1) >= max_jump) {
+                        stroke_path(canvas, &path_px[start..i], style.width, &style.dash)?;
+                        start = i;
                     }
                 }
             }
         }
 
         for (id, point) in state.world.points.iter() {
+            let id = gwrapper::ThingID::PointID(*id);
+            let alpha = match alpha(id) {
+                Some(alpha) => alpha,
+                None => continue,
+            };
             if let Some(rpoint) = state.world.resolve_point(point) {
                 let p_px = state.view.transform.transform_po_to_px(rpoint);
+                let style = state.world.style(id);
 
-                let mut image = &*icons::CIRCLE_NORMAL;
-
-                if let Some(selected) = state
-                    .current_tool
-                    .selected(&state.world)
-                    .get(&gwrapper::ThingID::PointID(*id))
-                {
-                    image = match selected {
-                        SelectedStatus::Primary => &*icons::CIRCLE_PRIMARY,
-                        SelectedStatus::Active => &*icons::CIRCLE_ACTIVE,
-                    }
+                canvas.set_draw_color(theme.ink(style.color, alpha));
+                draw_point(canvas, p_px, &style, zoom)?;
+                if let Some(color) = selected_color(id, alpha) {
+                    canvas.set_draw_color(color);
+                    let radius = style.point_size * zoom + SELECTION_RING / 2.;
+                    stroke_circle(canvas, p_px, radius, SELECTION_RING, &[])?;
                 }
+            }
+        }
 
-                let mut to_draw = &image.normal;
-                if let Some(gwrapper::Visibility::Hidden) =
-                    state.world.visibility.get(&gwrapper::ThingID::PointID(*id))
-                {
-                    if !state.view.show_hidden {
-                        continue;
-                    }
-                    to_draw = &image.transparent;
-                }
+        // Nothing can be clicked while replaying or moving the screen
+        if state.view.replay.is_none() && !state.view.moving_screen {
+            let mouse = state.view.mouse_last;
+            let mouse_po = state
+                .view
+                .transform
+                .transform_px_to_po((mouse.x() as f64, mouse.y() as f64));
+            // Finding what a click would do can be slow, so it's only done again when needed
+            let key = (mouse_po, state.view.transform.scale);
+            let hover = match state.view.hover.take() {
+                Some((at, hover)) if at == key => hover,
+                _ => state
+                    .current_tool
+                    .hover(&state.world, &state.view, mouse_po),
+            };
+            draw_hover(canvas, &state, &hover)?;
+            state.view.hover.set(Some((key, hover)));
+        }
 
-                to_draw.draw(
-                    canvas,
-                    &Position::Center(Point::new(p_px.0 as i32, p_px.1 as i32)),
-                    settings,
-                );
+        for measurement in state.world.measurements.values() {
+            if measurement.dependencies().into_iter().all(&shown) {
+                draw_measurement(canvas, &state, measurement)?;
             }
         }
 
+        draw_sliders(canvas, &state.world, theme, h)?;
+        style_editor::draw(canvas, &state)?;
+
+        if state.view.show_protocol {
+            draw_protocol(canvas, &state, (w, h))?;
+        }
+        if let Some(step) = state.view.replay {
+            draw_replay_slider(canvas, step, state.world.order.len(), theme, (w, h))?;
+        }
+
+        if let Some(ref puzzle) = state.puzzle {
+            draw_puzzle_status(canvas, puzzle, &state.world, theme, h)?;
+        }
+
+        if state.view.show_help {
+            draw_help(canvas, &state.keymap, theme, (w, h))?;
+        }
+        draw_message(canvas, &state.view, theme, w)?;
+
         Ok(())
     }
 
@@ -128,15 +374,925 @@ impl DrawingBoard {
         let (x, y) = (position.x(), position.y());
 
         let state = &mut *self.state.lock().unwrap();
+        state.view.hover.take();
+
+        // Clicking elsewhere gives up on the prompt
+        if let Some(prompt) = state.view.prompt.take() {
+            give_up(state, prompt.asker);
+        }
+
+        let (px, py) = (x as f64, y as f64);
+        let (x0, x1) = SLIDER_TRACK;
+        for (id, row_y) in slider_rows(&state.world, state.view.transform.win_size.1) {
+            if (py - row_y).abs() > SLIDER_ROW / 2. {
+                continue;
+            }
+            if (px - SLIDER_BUTTON).abs() < SLIDER_ROW / 2. {
+                if let Some(slider) = state.world.geometry.sliders.get_mut(&id) {
+                    slider.playing = !slider.playing;
+                }
+                return;
+            }
+            if px > x0 - SLIDER_ROW / 2. && px < x1 + SLIDER_ROW / 2. {
+                state.view.dragging_slider = Some(id);
+                drag_slider(state, id, px);
+                return;
+            }
+        }
+
+        if style_editor::click(state, (px, py)) {
+            return;
+        }
+
+        // The construction can't be changed while replaying it
+        if state.view.replay.is_some() {
+            let ((x0, y0), (x1, _)) = replay_slider(state.view.transform.win_size);
+            let total = state.world.order.len();
+            if (y as f64 - y0).abs() < REPLAY_SLIDER_GRAB && x as f64 >= x0 && x as f64 <= x1 {
+                let step = ((x as f64 - x0) / (x1 - x0) * total as f64).round() as usize;
+                state.view.replay = Some(step.min(total));
+            }
+            return;
+        }
 
         let mouse_po = state
             .view
             .transform
             .transform_px_to_po((x as f64, y as f64));
 
+        let kind = state.current_tool.kind();
+        if state.puzzle.is_some() && !kind.in_puzzles() {
+            let message = format!("The {} tool can't be used in puzzles", kind.name());
+            state.view.message(message);
+            return;
+        }
+
         state
             .current_tool
             .click(&mut state.world, &mut state.view, mouse_po);
+
+        if let Some(ref mut puzzle) = state.puzzle {
+            puzzle.check(&mut state.world);
+        }
+    }
+}
+
+fn draw_shape(
+    canvas: &mut Canvas<Window>,
+    transform: &Transform,
+    shape: geometry::ResolvedShape,
+    style: &gwrapper::Style,
+) -> Result<(), String> {
+    // Dashes of lines are counted from where they cross an axis, so they stay put when panning
+    match shape {
+        geometry::ResolvedShape::Circle(center, rad) => {
+            let center_px = transform.transform_po_to_px(center);
+            let r = rad * transform.scale;
+            stroke_circle(canvas, center_px, r, style.width, &style.dash)
+        }
+        geometry::ResolvedShape::Line(k, m) => {
+            let through = transform.transform_po_to_px((0., m));
+            stroke_line(canvas, through, (1., k), style.width, &style.dash)
+        }
+        geometry::ResolvedShape::LineUp(x) => {
+            let through = transform.transform_po_to_px((x, 0.));
+            stroke_line(canvas, through, (0., 1.), style.width, &style.dash)
+        }
+    }
+}
+
+/// A point in the current draw color, shaped and sized by its style
+fn draw_point(
+    canvas: &mut Canvas<Window>,
+    (x, y): (f64, f64),
+    style: &gwrapper::Style,
+    zoom: f64,
+) -> Result<(), String> {
+    let r = style.point_size * zoom;
+    match style.point_shape {
+        gwrapper::PointShape::Circle => fill_polygon(canvas, &circle_path((x, y), r)),
+        gwrapper::PointShape::Square => {
+            let r = r * 0.9;
+            fill_polygon(
+                canvas,
+                &[
+                    (x - r, y - r),
+                    (x + r, y - r),
+                    (x + r, y + r),
+                    (x - r, y + r),
+                ],
+            )
+        }
+        gwrapper::PointShape::Cross => {
+            let width = style.width.max(2.);
+            stroke_path(canvas, &[(x - r, y - r), (x + r, y + r)], width, &[])?;
+            stroke_path(canvas, &[(x - r, y + r), (x + r, y - r)], width, &[])
+        }
+    }
+}
+
+/// Rings the object a click would pick, and draws a faded copy of what it would make
+fn draw_hover(canvas: &mut Canvas<Window>, state: &DState, hover: &Hover) -> Result<(), String> {
+    let transform = &state.view.transform;
+    let world = &state.world;
+
+    // The highlight is solid and as wide as the object, so it covers it
+    let highlight = |id| gwrapper::Style {
+        dash: Vec::new(),
+        ..world.style(id)
+    };
+
+    let zoom = transform.point_zoom();
+
+    let theme = &state.theme;
+    canvas.set_draw_color(theme.hover);
+    match hover.target {
+        Some(gwrapper::ThingID::PointID(id)) => {
+            if let Some(pos) = world.points.get(&id).and_then(|p| world.resolve_point(p)) {
+                let size = world.style(gwrapper::ThingID::PointID(id)).point_size * zoom;
+                let radius = HOVER_RADIUS.max(size + HOVER_RADIUS - gwrapper::DEFAULT_POINT_SIZE);
+                stroke_circle(canvas, transform.transform_po_to_px(pos), radius, 1., &[])?;
+            }
+        }
+        Some(gwrapper::ThingID::ShapeID(id)) => {
+            if let Some(shape) = world.shapes.get(&id).and_then(|s| world.resolve_shape(s)) {
+                let style = highlight(gwrapper::ThingID::ShapeID(id));
+                draw_shape(canvas, transform, shape, &style)?;
+            }
+        }
+        Some(gwrapper::ThingID::PolygonID(id)) => {
+            if let Some(vertices) = world
+                .polygons
+                .get(&id)
+                .and_then(|p| world.resolve_polygon(p))
+            {
+                let mut vertices: Vec<_> = vertices
+                    .into_iter()
+                    .map(|vertex| transform.transform_po_to_px(vertex))
+                    .collect();
+                if let Some(first) = vertices.first().cloned() {
+                    vertices.push(first);
+                }
+                let style = highlight(gwrapper::ThingID::PolygonID(id));
+                stroke_path(canvas, &vertices, style.width, &[])?;
+            }
+        }
+        Some(gwrapper::ThingID::LocusID(id)) => {
+            let style = highlight(gwrapper::ThingID::LocusID(id));
+            for path in world.locus_path(id).into_iter().flatten() {
+                let path: Vec<_> = path
+                    .iter()
+                    .map(|pos| transform.transform_po_to_px(*pos))
+                    .collect();
+                stroke_path(canvas, &path, style.width, &[])?;
+            }
+        }
+        None => {}
+    }
+
+    let style = gwrapper::Style::default();
+    canvas.set_draw_color(theme.ink(style.color, theme.ghost_alpha));
+    if let Some(shape) = hover.shape {
+        draw_shape(canvas, transform, shape, &style)?;
+    }
+    if let Some(pos) = hover.point {
+        draw_point(canvas, transform.transform_po_to_px(pos), &style, zoom)?;
+    }
+    Ok(())
+}
+
+/// Grid lines at every multiple of the grid spacing, and the axes with a number at every line
+fn draw_grid(
+    canvas: &mut Canvas<Window>,
+    transform: &Transform,
+    theme: &Theme,
+    (w, h): (u32, u32),
+) -> Result<(), String> {
+    let (w, h) = (w as f64, h as f64);
+    let spacing = transform.grid_spacing();
+    let (left, top) = transform.transform_px_to_po((0., 0.));
+    let (right, bottom) = transform.transform_px_to_po((w, h));
+    let origin = transform.transform_po_to_px((0., 0.));
+
+    // Multiples of the spacing from `from` to `to`, as (index, value)
+    let lines = |from: f64, to: f64| {
+        ((from / spacing).ceil() as i64..(to / spacing).floor() as i64 + 1)
+            .map(move |i| (i, i as f64 * spacing))
+    };
+
+    canvas.set_draw_color(theme.grid);
+    for (_, x) in lines(left, right) {
+        let x_px = transform.transform_po_to_px((x, 0.)).0;
+        stroke_path(canvas, &[(x_px, 0.), (x_px, h)], 1., &[])?;
+    }
+    for (_, y) in lines(top, bottom) {
+        let y_px = transform.transform_po_to_px((0., y)).1;
+        stroke_path(canvas, &[(0., y_px), (w, y_px)], 1., &[])?;
+    }
+
+    canvas.set_draw_color(theme.axis);
+    stroke_path(canvas, &[(0., origin.1), (w, origin.1)], 1., &[])?;
+    stroke_path(canvas, &[(origin.0, 0.), (origin.0, h)], 1., &[])?;
+
+    // The numbers stay in the window when an axis is outside of it
+    let decimals = (-spacing.log10().floor()).max(0.) as usize;
+    let (char_w, char_h) = (text::CHAR_SIZE.0 as f64, text::CHAR_SIZE.1 as f64);
+    let label_y = (origin.1 + 4.).max(0.).min(h - char_h - 4.);
+    for (i, x) in lines(left, right) {
+        if i != 0 {
+            let label = format!("{:.*}", decimals, x);
+            let x_px = transform.transform_po_to_px((x, 0.)).0;
+            let label_x = x_px - label.len() as f64 * char_w / 2.;
+            text::draw_text(canvas, &label, (label_x as i32, label_y as i32))?;
+        }
+    }
+    for (i, y) in lines(top, bottom) {
+        if i != 0 {
+            let label = format!("{:.*}", decimals, y);
+            let width = label.len() as f64 * char_w;
+            let y_px = transform.transform_po_to_px((0., y)).1;
+            let label_x = (origin.0 - width - 4.).max(4.).min(w - width - 4.);
+            text::draw_text(
+                canvas,
+                &label,
+                (label_x as i32, (y_px - char_h / 2.) as i32),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Moves a slider to the value under `x` on its track, stopping it if it was playing
+fn drag_slider(state: &mut DState, id: geometry::SliderID, x: f64) {
+    let (x0, x1) = SLIDER_TRACK;
+    let value = match state.world.sliders.get(&id) {
+        Some(slider) => {
+            let fraction = ((x - x0) / (x1 - x0)).clamp(0., 1.);
+            slider.min + (slider.max - slider.min) * fraction
+        }
+        None => return,
+    };
+    if let Some(slider) = state.world.geometry.sliders.get_mut(&id) {
+        slider.playing = false;
+    }
+    state.world.set_slider(id, value);
+    record_trails(state);
+}
+
+/// Leaves a trail of every traced object where it is now
+fn record_trails(state: &mut DState) {
+    for id in &state.view.traced {
+        let trail = match id {
+            gwrapper::ThingID::PointID(id) => state
+                .world
+                .points
+                .get(id)
+                .and_then(|point| state.world.resolve_point(point))
+                .map(Trail::Point),
+            gwrapper::ThingID::ShapeID(id) => state
+                .world
+                .shapes
+                .get(id)
+                .and_then(|shape| state.world.resolve_shape(shape))
+                .map(Trail::Shape),
+            _ => None,
+        };
+        state.view.trails.extend(trail);
+    }
+    while state.view.trails.len() > MAX_TRAILS {
+        state.view.trails.pop_front();
+    }
+}
+
+/// How close to the replay slider a click has to be, in pixels
+const REPLAY_SLIDER_GRAB: f64 = 10.;
+
+/// The ends of the replay slider, at the bottom of the window
+fn replay_slider((w, h): (f64, f64)) -> ((f64, f64), (f64, f64)) {
+    ((w * 0.35, h - 20.), (w * 0.65, h - 20.))
+}
+
+fn draw_replay_slider(
+    canvas: &mut Canvas<Window>,
+    step: usize,
+    total: usize,
+    theme: &Theme,
+    (w, h): (u32, u32),
+) -> Result<(), String> {
+    let ((x0, y), (x1, _)) = replay_slider((w as f64, h as f64));
+    canvas.set_draw_color(theme.foreground);
+    stroke_path(canvas, &[(x0, y), (x1, y)], 1., &[])?;
+
+    let at = if total == 0 {
+        x1
+    } else {
+        x0 + (x1 - x0) * step as f64 / total as f64
+    };
+    canvas.fill_rect(Rect::new(at as i32 - 3, y as i32 - 8, 7, 17))?;
+
+    let label_height = (text::CHAR_SIZE.1 + 2 * text::LABEL_PADDING) as f64;
+    text::draw_label(
+        canvas,
+        &format!("Step {} / {}", step, total),
+        (at, y - 8. - label_height),
+        theme.foreground,
+        theme.label_background,
+    )
+}
+
+/// The height of a row in the slider panel
+const SLIDER_ROW: f64 = 24.;
+/// The x coordinate of the play buttons
+const SLIDER_BUTTON: f64 = 20.;
+/// The x coordinates of the ends of the slider tracks
+const SLIDER_TRACK: (f64, f64) = (40., 240.);
+
+/// The y coordinate of every slider in the panel in the bottom left corner, above the puzzle
+/// status
+fn slider_rows(world: &gwrapper::GWrapper, height: f64) -> Vec<(geometry::SliderID, f64)> {
+    let mut ids: Vec<geometry::SliderID> = world.sliders.keys().cloned().collect();
+    ids.sort_by_key(|id| **id);
+    let n = ids.len();
+    ids.into_iter()
+        .enumerate()
+        .map(|(i, id)| (id, height - 60. - SLIDER_ROW * (n - i) as f64))
+        .collect()
+}
+
+/// The value of a slider, with as many decimals as its step needs
+fn slider_text(slider: &geometry::Slider) -> String {
+    let decimals = if slider.step > 0. {
+        (-slider.step.log10()).ceil().clamp(0., 6.) as usize
+    } else {
+        2
+    };
+    format!("{} = {:.*}", slider.name, decimals, slider.value)
+}
+
+fn draw_sliders(
+    canvas: &mut Canvas<Window>,
+    world: &gwrapper::GWrapper,
+    theme: &Theme,
+    height: u32,
+) -> Result<(), String> {
+    let (x0, x1) = SLIDER_TRACK;
+    for (id, y) in slider_rows(world, height as f64) {
+        let slider = &world.sliders[&id];
+        canvas.set_draw_color(theme.foreground);
+
+        if slider.playing {
+            for dx in &[-5, 2] {
+                canvas.fill_rect(Rect::new(SLIDER_BUTTON as i32 + dx, y as i32 - 6, 4, 13))?;
+            }
+        } else {
+            let (x, y) = (SLIDER_BUTTON, y);
+            fill_polygon(canvas, &[(x - 5., y - 7.), (x + 7., y), (x - 5., y + 7.)])?;
+        }
+
+        stroke_path(canvas, &[(x0, y), (x1, y)], 1., &[])?;
+        let at = x0 + (x1 - x0) * slider.fraction();
+        canvas.fill_rect(Rect::new(at as i32 - 3, y as i32 - 8, 7, 17))?;
+
+        let text = slider_text(slider);
+        let (width, _) = text::text_size(&text);
+        text::draw_label(
+            canvas,
+            &text,
+            (x1 + SLIDER_ROW / 2. + width as f64 / 2., y),
+            theme.foreground,
+            theme.label_background,
+        )?;
+    }
+    Ok(())
+}
+
+/// Lists the construction steps in the bottom right corner, the later ones grayed out while
+/// replaying
+fn draw_protocol(
+    canvas: &mut Canvas<Window>,
+    state: &DState,
+    (w, h): (u32, u32),
+) -> Result<(), String> {
+    let steps = protocol::steps(&state.world);
+    let current = state.view.replay.unwrap_or(steps.len());
+
+    let line_height = (text::CHAR_SIZE.1 + 2 * text::LABEL_PADDING) as f64;
+    let max_lines = ((h as f64 / 2. / line_height) as usize).max(1);
+    let end = current.max(max_lines).min(steps.len());
+    let start = end.saturating_sub(max_lines);
+
+    let width = steps[start..end]
+        .iter()
+        .map(|step| text::text_size(step).0)
+        .max()
+        .unwrap_or(0) as f64;
+    let bottom = h as f64 - 50.;
+    for (i, step) in steps[start..end].iter().enumerate() {
+        let color = if start + i < current {
+            state.theme.foreground
+        } else {
+            state.theme.later_step
+        };
+        let at = (
+            w as f64 - line_height - width + text::text_size(step).0 as f64 / 2.,
+            bottom - line_height * (end - start - i) as f64,
+        );
+        text::draw_label(canvas, step, at, color, state.theme.label_background)?;
+    }
+    Ok(())
+}
+
+/// Shows the prompt being typed in, or else the last message, at the top of the board
+fn draw_message(
+    canvas: &mut Canvas<Window>,
+    view: &View,
+    theme: &Theme,
+    width: u32,
+) -> Result<(), String> {
+    let message = match (&view.prompt, &view.message) {
+        (Some(prompt), _) => format!("{}: {}_", prompt.label, prompt.typed),
+        (None, Some((message, _))) => message.clone(),
+        (None, None) => return Ok(()),
+    };
+    let line_height = (text::CHAR_SIZE.1 + 2 * text::LABEL_PADDING) as f64;
+    let at = (width as f64 / 2., line_height);
+    text::draw_label(
+        canvas,
+        &message,
+        at,
+        theme.foreground,
+        theme.label_background,
+    )
+}
+
+/// Lists every key binding in columns over the middle of the board
+fn draw_help(
+    canvas: &mut Canvas<Window>,
+    keymap: &Keymap,
+    theme: &Theme,
+    (w, h): (u32, u32),
+) -> Result<(), String> {
+    // Padded to the same length, so the labels line up
+    let lines = keymap.describe();
+    let chars = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+    let lines: Vec<String> = lines
+        .iter()
+        .map(|line| format!("{:<1$}", line, chars))
+        .collect();
+
+    let line_height = (text::CHAR_SIZE.1 + 2 * text::LABEL_PADDING) as f64;
+    let column_width = (chars as u32 * text::CHAR_SIZE.0) as f64 + line_height;
+    let max_rows = ((h as f64 - 100.) / line_height).max(1.) as usize;
+    let columns = lines.len().div_ceil(max_rows).max(1);
+    let rows = lines.len().div_ceil(columns);
+
+    let left = (w as f64 - column_width * columns as f64) / 2.;
+    let top = (h as f64 - line_height * rows as f64) / 2.;
+    for (i, line) in lines.iter().enumerate() {
+        let at = (
+            left + column_width * ((i / rows) as f64 + 0.5),
+            top + line_height * (i % rows) as f64,
+        );
+        text::draw_label(canvas, line, at, theme.foreground, theme.label_background)?;
+    }
+    Ok(())
+}
+
+/// Shows the level and the number of moves in the bottom left corner
+fn draw_puzzle_status(
+    canvas: &mut Canvas<Window>,
+    puzzle: &puzzle::Puzzle,
+    world: &gwrapper::GWrapper,
+    theme: &Theme,
+    height: u32,
+) -> Result<(), String> {
+    let (status, color) = match puzzle.solved_in {
+        Some(moves) => (format!("Solved in {} moves!", moves), theme.solved),
+        None => (format!("Moves: {}", puzzle.moves(world)), theme.puzzle),
+    };
+    let title = if puzzle.text.is_empty() {
+        puzzle.name.clone()
+    } else {
+        format!("{}: {}", puzzle.name, puzzle.text)
+    };
+
+    let line_height = (text::CHAR_SIZE.1 + 2 * text::LABEL_PADDING) as f64;
+    for (i, (line, color)) in [(title, theme.puzzle), (status, color)].iter().enumerate() {
+        let (width, _) = text::text_size(line);
+        let at = (
+            line_height / 2. + width as f64 / 2.,
+            height as f64 - line_height * (2.5 - i as f64),
+        );
+        text::draw_label(canvas, line, at, *color, theme.label_background)?;
+    }
+    Ok(())
+}
+
+fn draw_measurement(
+    canvas: &mut Canvas<Window>,
+    state: &DState,
+    measurement: &geometry::Measurement,
+) -> Result<(), String> {
+    let value = match state.world.resolve_measurement(measurement) {
+        Some(value) => value,
+        None => return Ok(()),
+    };
+    let resolve = |id: &geometry::PointID| {
+        state
+            .world
+            .points
+            .get(id)
+            .and_then(|point| state.world.resolve_point(point))
+            .map(|pos| state.view.transform.transform_po_to_px(pos))
+            .unwrap_or((0., 0.))
+    };
+
+    canvas.set_draw_color(state.theme.measurement);
+    let (text, at) = match measurement {
+        geometry::Measurement::Distance(a, b) => {
+            let (a, b) = (resolve(a), resolve(b));
+            (
+                format!("{:.3}", value),
+                ((a.0 + b.0) / 2., (a.1 + b.1) / 2.),
+            )
+        }
+        geometry::Measurement::Angle(a, vertex, b) => {
+            let (a, vertex, b) = (resolve(a), resolve(vertex), resolve(b));
+            let start = (a.1 - vertex.1).atan2(a.0 - vertex.0);
+            let (ax, ay) = (a.0 - vertex.0, a.1 - vertex.1);
+            let (bx, by) = (b.0 - vertex.0, b.1 - vertex.1);
+            let sweep = (ax * by - ay * bx).atan2(ax * bx + ay * by);
+
+            let arc_point = |t: f64| {
+                let theta = start + sweep * t;
+                (
+                    vertex.0 + ANGLE_ARC_RADIUS * theta.cos(),
+                    vertex.1 + ANGLE_ARC_RADIUS * theta.sin(),
+                )
+            };
+            let steps = 16;
+            let arc: Vec<_> = (0..steps + 1)
+                .map(|i| arc_point(i as f64 / steps as f64))
+                .collect();
+            stroke_path(canvas, &arc, 1., &[])?;
+
+            let label_at = arc_point(0.5);
+            (
+                format!("{:.1}°", value.to_degrees()),
+                (
+                    vertex.0 + (label_at.0 - vertex.0) * 2.,
+                    vertex.1 + (label_at.1 - vertex.1) * 2.,
+                ),
+            )
+        }
+        geometry::Measurement::Radius(id) => {
+            let center = match state
+                .world
+                .shapes
+                .get(id)
+                .and_then(|shape| state.world.resolve_shape(shape))
+            {
+                Some(geometry::ResolvedShape::Circle(center, _)) => {
+                    state.view.transform.transform_po_to_px(center)
+                }
+                _ => return Ok(()),
+            };
+            let edge = (center.0 + value * state.view.transform.scale, center.1);
+            stroke_path(canvas, &[center, edge], 1., &[])?;
+            (
+                format!("r = {:.3}", value),
+                (
+                    (center.0 + edge.0) / 2.,
+                    center.1 - text::CHAR_SIZE.1 as f64,
+                ),
+            )
+        }
+        geometry::Measurement::Area(vertices) => (
+            format!("A = {:.3}", value),
+            centroid(vertices.iter().map(resolve).collect()),
+        ),
+        geometry::Measurement::Perimeter(vertices) => {
+            let centroid = centroid(vertices.iter().map(resolve).collect());
+            (
+                format!("P = {:.3}", value),
+                (
+                    centroid.0,
+                    centroid.1 + (text::CHAR_SIZE.1 + 2 * text::LABEL_PADDING) as f64,
+                ),
+            )
+        }
+    };
+
+    let theme = &state.theme;
+    text::draw_label(canvas, &text, at, theme.measurement, theme.label_background)
+}
+
+fn centroid(vertices: Vec<(f64, f64)>) -> (f64, f64) {
+    let mut sum = (0., 0.);
+    for vertex in &vertices {
+        sum = (sum.0 + vertex.0, sum.1 + vertex.1);
+    }
+    let n = vertices.len().max(1) as f64;
+    (sum.0 / n, sum.1 / n)
+}
+
+/// Does what a key binding asks for
+fn perform(state: &mut DState, action: Action) {
+    match action {
+        Action::Tool(kind) => {
+            state.current_tool = kind.into_tool();
+        }
+        Action::Cancel => {
+            state.current_tool = state.current_tool.kind().into_tool();
+            state.macro_inputs = None;
+        }
+        Action::MoveScreen => {
+            state.view.moving_screen = true;
+        }
+        Action::StepBack => {
+            let total = state.world.order.len();
+            state.view.replay = Some(state.view.replay.unwrap_or(total).saturating_sub(1));
+        }
+        Action::StepForward => {
+            let total = state.world.order.len();
+            state.view.replay = match state.view.replay {
+                Some(step) if step + 1 < total => Some(step + 1),
+                _ => None,
+            };
+        }
+        Action::FirstStep => {
+            state.view.replay = Some(0);
+        }
+        Action::LastStep => {
+            state.view.replay = None;
+        }
+        Action::ToggleProtocol => {
+            state.view.show_protocol = !state.view.show_protocol;
+        }
+        Action::ExportProtocol => {
+            let message = match fs::write(PROTOCOL_FILE, protocol::export(&state.world)) {
+                Ok(()) => format!("Exported the construction protocol to {}", PROTOCOL_FILE),
+                Err(err) => format!("Can't export the construction protocol: {}", err),
+            };
+            state.view.message(message);
+        }
+        Action::Save => {
+            let path = state
+                .path
+                .clone()
+                .unwrap_or_else(|| state.unused_save_path());
+            let text = format::write(&state.world);
+            match fs::write(&path, &text) {
+                Ok(()) => {
+                    state.saved = text;
+                    state
+                        .view
+                        .message(format!("Saved the construction to {}", path.display()));
+                    // Puzzles keep the name of their level
+                    if state.path.is_none() && state.puzzle.is_none() {
+                        state.name = path.display().to_string();
+                    }
+                    state.path = Some(path);
+                }
+                Err(err) => state
+                    .view
+                    .message(format!("Can't save the construction: {}", err)),
+            }
+        }
+        Action::NewTab => state.open(Document::new(
+            UNTITLED.to_string(),
+            None,
+            gwrapper::GWrapper::new(geometry::Geometry::new()),
+            None,
+        )),
+        Action::CloseTab => {
+            if state.others.is_empty() {
+                state.view.message("Can't close the only tab");
+            } else if state.is_modified() {
+                let label = format!("{} has unsaved changes, close it anyway? (y/n)", state.name);
+                state.view.ask(label, String::new(), Asker::CloseTab);
+            } else {
+                state.close_tab();
+            }
+        }
+        Action::NextTab => state.step_tab(1),
+        Action::PreviousTab => state.step_tab(-1),
+        Action::Copy => {
+            let selection: HashSet<_> = style_editor::selection(state).into_iter().collect();
+            if selection.is_empty() {
+                state
+                    .view
+                    .message("Select something to copy with the selector first");
+            } else {
+                let text = format::write_selection(&state.world, &selection);
+                state.clipboard = Some(ClipboardRequest::Copy(text));
+            }
+        }
+        Action::Paste => state.clipboard = Some(ClipboardRequest::Paste),
+        Action::MakeMacro => {
+            let selection: HashSet<_> = style_editor::selection(state).into_iter().collect();
+            match state.macro_inputs.take() {
+                None if selection.is_empty() => {
+                    state.view.message("Select the inputs of the macro first")
+                }
+                None => {
+                    state.view.message(
+                        "Now select the outputs of the macro and press the macro key again",
+                    );
+                    state.macro_inputs = Some(selection);
+                    state.current_tool = ToolKind::Selector.into_tool();
+                }
+                Some(inputs) => {
+                    // Named once it's made, suggesting a name no macro has
+                    let name = macros::unused_name(&state.macros, "macro");
+                    match macros::capture(&state.world, &name, &inputs, &selection) {
+                        Ok(definition) => {
+                            state.unnamed_macro = Some(definition);
+                            state.view.ask("Macro name", name, Asker::MacroName);
+                        }
+                        Err(err) => state.view.message(format!("Can't make a macro: {}", err)),
+                    }
+                }
+            }
+        }
+        Action::MergeDuplicates => {
+            let merged = state.world.merge_duplicates();
+            // The tool may have picked a merged object
+            state.current_tool = state.current_tool.kind().into_tool();
+            state
+                .view
+                .message(format!("Merged {} duplicate objects", merged));
+        }
+        Action::PlaySliders => {
+            // Pauses everything if anything is playing, otherwise plays everything
+            let play = !state.world.sliders.values().any(|slider| slider.playing);
+            for slider in state.world.geometry.sliders.values_mut() {
+                slider.playing = play;
+            }
+        }
+        Action::ToggleRecording => match state.recorder.take() {
+            Some(recorder) => state.view.message(format!(
+                "Recorded {} frames to {}",
+                recorder.frames,
+                recorder.dir.display()
+            )),
+            None => match Recorder::start() {
+                Ok(recorder) => {
+                    state
+                        .view
+                        .message(format!("Recording to {}", recorder.dir.display()));
+                    state.recorder = Some(recorder);
+                }
+                Err(err) => state
+                    .view
+                    .message(format!("Can't start recording: {}", err)),
+            },
+        },
+        Action::Fit => {
+            if let Some((min, max)) = state.world.bounds(state.view.show_hidden) {
+                state.view.transform.fit(min, max);
+            }
+        }
+        // The view moves the way the arrow points
+        Action::PanLeft => state.view.transform.pan((PAN_STEP, 0.)),
+        Action::PanRight => state.view.transform.pan((-PAN_STEP, 0.)),
+        Action::PanUp => state.view.transform.pan((0., PAN_STEP)),
+        Action::PanDown => state.view.transform.pan((0., -PAN_STEP)),
+        Action::ZoomIn | Action::ZoomOut => {
+            let factor = if action == Action::ZoomIn {
+                KEY_ZOOM
+            } else {
+                1. / KEY_ZOOM
+            };
+            let (w, h) = state.view.transform.win_size;
+            state.view.transform.zoom_at(factor, (w / 2., h / 2.));
+        }
+        Action::ToggleHidden => {
+            state.view.show_hidden = !state.view.show_hidden;
+        }
+        Action::ToggleGrid => {
+            state.view.show_grid = !state.view.show_grid;
+        }
+        Action::ToggleSnap => {
+            state.view.snap_to_grid = !state.view.snap_to_grid;
+        }
+        Action::ToggleTheme => {
+            // The next theme, going back to the first after the last
+            let i = state.themes.iter().position(|theme| *theme == state.theme);
+            let next = i.map_or(0, |i| (i + 1) % state.themes.len());
+            state.theme = state.themes[next];
+        }
+        Action::ToggleHelp => {
+            state.view.show_help = !state.view.show_help;
+        }
+    }
+}
+
+/// Copies to or pastes from `clipboard`
+fn use_clipboard(state: &mut DState, clipboard: &ClipboardUtil, request: ClipboardRequest) {
+    let text = match request {
+        ClipboardRequest::Copy(text) => {
+            if let Err(err) = clipboard.set_clipboard_text(&text) {
+                state.view.message(format!("Can't copy: {}", err));
+            }
+            return;
+        }
+        ClipboardRequest::Paste if !clipboard.has_clipboard_text() => return,
+        ClipboardRequest::Paste => clipboard.clipboard_text(),
+    };
+    match text.and_then(|text| format::paste(&mut state.world, &text)) {
+        // The pasted objects are selected once their inputs are attached or left free
+        Ok((pasted, inputs)) => {
+            if !inputs.is_empty() {
+                state.view.message(format!(
+                    "Click a point for each of the {} inputs, or elsewhere to keep it free",
+                    inputs.len()
+                ));
+            }
+            state.current_tool = Box::new(tools::Rebinder {
+                inputs,
+                selector: tools::Selector {
+                    selected: pasted.into_iter().collect(),
+                },
+            })
+        }
+        Err(err) => state.view.message(format!("Can't paste: {}", err)),
+    }
+}
+
+/// Edits the prompt, which takes every key until Enter or Escape
+fn type_in(state: &mut DState, event: Event) {
+    match event {
+        Event::TextInput { text, .. } => {
+            if let Some(ref mut prompt) = state.view.prompt {
+                prompt.typed.push_str(&text);
+            }
+        }
+        Event::KeyDown {
+            keycode: Some(key), ..
+        } => match key {
+            Keycode::Backspace => {
+                if let Some(ref mut prompt) = state.view.prompt {
+                    prompt.typed.pop();
+                }
+            }
+            Keycode::Return | Keycode::KpEnter => {
+                if let Some(prompt) = state.view.prompt.take() {
+                    answer(state, prompt);
+                }
+            }
+            Keycode::Escape => {
+                if let Some(prompt) = state.view.prompt.take() {
+                    give_up(state, prompt.asker);
+                }
+            }
+            _ => {}
+        },
+        _ => {}
+    }
+}
+
+/// Gives the text typed in for `prompt` to whoever asked
+fn answer(state: &mut DState, prompt: Prompt) {
+    match prompt.asker {
+        Asker::Tool => {
+            state
+                .current_tool
+                .answer(&mut state.world, &mut state.view, &prompt.typed);
+        }
+        Asker::CloseTab => {
+            if prompt.typed.trim().eq_ignore_ascii_case("y") {
+                state.close_tab();
+            }
+        }
+        Asker::MacroName => {
+            let name = prompt.typed.trim();
+            if let Err(err) = macros::check_name(&state.macros, name) {
+                // Asked again, until it's given a name or given up
+                state.view.message(err);
+                state.view.ask(prompt.label, prompt.typed, prompt.asker);
+                return;
+            }
+            if let Some(mut definition) = state.unnamed_macro.take() {
+                definition.name = name.to_string();
+                state.macros.push(definition);
+                match macros::save(&state.macros) {
+                    Ok(()) => state.view.message(format!("Made the macro {}", name)),
+                    Err(err) => state
+                        .view
+                        .message(format!("Can't save the macros: {}", err)),
+                }
+            }
+        }
+    }
+}
+
+/// Forgets what `asker` was waiting for, after Escape or a click elsewhere
+fn give_up(state: &mut DState, asker: Asker) {
+    match asker {
+        Asker::Tool => state.current_tool = state.current_tool.kind().into_tool(),
+        Asker::CloseTab => {}
+        Asker::MacroName => state.unnamed_macro = None,
     }
 }
 
@@ -155,49 +1311,102 @@ impl Drawable for DrawingBoard {
         State::Working
     }
 
-    fn draw(&self, canvas: &mut Canvas<Window>, position: &Position, settings: DrawSettings) {
+    fn draw(&self, canvas: &mut Canvas<Window>, position: &Position, _settings: DrawSettings) {
         if let Position::Rect(r) = position {
             canvas.set_clip_rect(*r);
-            self.try_draw(canvas, settings).expect("Can't draw");
+            canvas.set_draw_color(self.state.lock().unwrap().theme.background);
+            canvas.fill_rect(*r).expect("Can't draw the background");
+            self.try_draw(canvas).expect("Can't draw");
             canvas.set_clip_rect(None);
+
+            let state = &mut *self.state.lock().unwrap();
+            if let Some(request) = state.clipboard.take() {
+                use_clipboard(state, &canvas.window().subsystem().clipboard(), request);
+                state.view.hover.take();
+            }
+            let captured = match state.recorder {
+                Some(ref mut recorder) => recorder.capture(canvas, *r),
+                None => Ok(()),
+            };
+            if let Err(err) = captured {
+                state.recorder = None;
+                state.view.message(format!("Can't record: {}", err));
+            }
         }
     }
 
     fn update(&mut self, dt: f64) {
         let state = &mut *self.state.lock().unwrap();
 
+        if let Some(ref mut recorder) = state.recorder {
+            recorder.tick(dt);
+            if let Some(err) = recorder.error() {
+                state.view.message(err);
+            }
+        }
+        let expired = match state.view.message {
+            Some((_, ref mut left)) => {
+                *left -= dt;
+                *left <= 0.
+            }
+            None => false,
+        };
+        if expired {
+            state.view.message = None;
+        }
+
+        if state.world.animate_sliders(dt) {
+            record_trails(state);
+            state.view.hover.take();
+        }
+        state.world.trace_loci();
+
         state.view.scrolling = state.view.scrolling * (0.01_f64).powf(dt);
-        state.view.transform.scale *= (0.1_f64).powf(state.view.scrolling);
+        let mouse = state.view.mouse_last;
+        state.view.transform.zoom_at(
+            (0.1_f64).powf(state.view.scrolling),
+            (mouse.x() as f64, mouse.y() as f64),
+        );
     }
 
     fn event(&mut self, event: Event) {
         let state = &mut *self.state.lock().unwrap();
+        // Any key or mouse event may change the tool or the construction
+        state.view.hover.take();
+
+        if state.view.prompt.is_some() {
+            type_in(state, event);
+            return;
+        }
 
         match event {
             Event::KeyDown {
-                keycode: Some(Keycode::Escape),
+                keycode: Some(key),
+                keymod,
                 ..
             } => {
-                state.current_tool = state.current_tool.kind().into_tool();
-            }
-            Event::KeyDown {
-                keycode: Some(Keycode::Space),
-                ..
-            } => {
-                state.view.moving_screen = true;
+                if let Some(action) = state.keymap.action(Chord::new(key, keymod)).cloned() {
+                    perform(state, action);
+                }
             }
             Event::KeyUp {
-                keycode: Some(Keycode::Space),
-                ..
-            } => {
+                keycode: Some(key), ..
+            } if state.keymap.releases(key, &Action::MoveScreen) => {
                 state.view.moving_screen = false;
             }
             Event::MouseButtonUp { .. } => {
+                state.view.dragging_slider = None;
                 if state.current_tool.kind() == ToolKind::Mover {
                     state.current_tool = state.current_tool.kind().into_tool();
                 }
+                if let Some(ref mut puzzle) = state.puzzle {
+                    puzzle.check(&mut state.world);
+                }
             }
             Event::MouseMotion { x, y, .. } => {
+                if let Some(id) = state.view.dragging_slider {
+                    drag_slider(state, id, x as f64);
+                }
                 if state.view.moving_screen {
                     let (dx, dy) = (x - state.view.mouse_last.x, y - state.view.mouse_last.y);
                     let (dtx, dty) = (
@@ -226,6 +1435,7 @@ impl Drawable for DrawingBoard {
                             );
                         }
                         state.world.geometry.invalidate_cache();
+                        record_trails(state);
                     }
                 }
                 state.view.mouse_last = Point::new(x, y);
@@ -0,0 +1,260 @@
+// A panel on the right of the board changing the style of the objects picked with the selector.
+// Every row is one part of the style, and clicking a choice sets it for the whole selection.
+
+use backend::gwrapper::{self, Fill, PointShape, Style, ThingID};
+use graphics::*;
+use theme::Theme;
+use tool::{SelectedStatus, ToolKind};
+
+use ytesrev::prelude::*;
+
+/// Colors to pick from, for lines and fills
+pub const PALETTE: &[(u8, u8, u8)] = &[
+    (0, 0, 0),
+    (120, 120, 120),
+    (200, 40, 40),
+    (230, 130, 20),
+    (40, 150, 40),
+    (30, 100, 220),
+    (160, 60, 200),
+];
+const WIDTHS: &[f64] = &[1., 2., 3., 5.];
+const DASHES: &[&[f64]] = &[&[], &[8., 4.], &[2., 4.], &[10., 4., 2., 4.]];
+const POINT_SHAPES: &[PointShape] = &[PointShape::Circle, PointShape::Square, PointShape::Cross];
+const POINT_SIZES: &[f64] = &[3., 5., 7.];
+
+/// The size of a choice, in pixels
+const CELL: f64 = 24.;
+/// The space left of the choices for the name of a row
+const LABEL_WIDTH: f64 = 40.;
+const MARGIN: f64 = 20.;
+
+/// One choice in the editor
+#[derive(Clone, PartialEq, Debug)]
+pub enum Change {
+    /// None follows the theme
+    Color(Option<(u8, u8, u8)>),
+    Width(f64),
+    Dash(Vec<f64>),
+    PointShape(PointShape),
+    PointSize(f64),
+    /// None removes the fill
+    Fill(Option<(u8, u8, u8)>),
+}
+
+impl Change {
+    pub fn apply(&self, style: &mut Style) {
+        match self {
+            Change::Color(color) => style.color = *color,
+            Change::Width(width) => style.width = *width,
+            Change::Dash(dash) => style.dash = dash.clone(),
+            Change::PointShape(shape) => style.point_shape = *shape,
+            Change::PointSize(size) => style.point_size = *size,
+            Change::Fill(None) => style.fill = None,
+            Change::Fill(Some(color)) => {
+                let opacity = style
+                    .fill
+                    .map_or(gwrapper::DEFAULT_FILL.opacity, |fill| fill.opacity);
+                style.fill = Some(Fill {
+                    color: *color,
+                    opacity,
+                });
+            }
+        }
+    }
+}
+
+/// The name of every row and its choices
+fn rows() -> Vec<(&'static str, Vec<Change>)> {
+    let mut colors = vec![Change::Color(None)];
+    colors.extend(PALETTE.iter().map(|color| Change::Color(Some(*color))));
+    let mut fills = vec![Change::Fill(None)];
+    fills.extend(PALETTE.iter().map(|color| Change::Fill(Some(*color))));
+    vec![
+        ("color", colors),
+        ("width", WIDTHS.iter().map(|w| Change::Width(*w)).collect()),
+        (
+            "dash",
+            DASHES.iter().map(|d| Change::Dash(d.to_vec())).collect(),
+        ),
+        (
+            "point",
+            POINT_SHAPES
+                .iter()
+                .map(|s| Change::PointShape(*s))
+                .collect(),
+        ),
+        (
+            "size",
+            POINT_SIZES.iter().map(|s| Change::PointSize(*s)).collect(),
+        ),
+        ("fill", fills),
+    ]
+}
+
+/// The objects picked with the selector, which the editor changes
+pub fn selection(state: &DState) -> Vec<ThingID> {
+    if state.current_tool.kind() != ToolKind::Selector {
+        return Vec::new();
+    }
+    state
+        .current_tool
+        .selected(&state.world)
+        .into_iter()
+        .filter(|(_, status)| *status == SelectedStatus::Primary)
+        .map(|(id, _)| id)
+        .collect()
+}
+
+/// The name of a row at its center, and the center of every choice in it
+type Row = ((&'static str, (f64, f64)), Vec<(Change, (f64, f64))>);
+
+/// Every row of the panel, which sits above the construction protocol
+fn layout((w, h): (f64, f64)) -> Vec<Row> {
+    let rows = rows();
+    let cells = rows.iter().map(|(_, row)| row.len()).max().unwrap_or(0);
+    let left = w - MARGIN - CELL * cells as f64;
+    let top = h / 2. - 60. - CELL * rows.len() as f64;
+
+    rows.into_iter()
+        .enumerate()
+        .map(|(i, (name, changes))| {
+            let y = top + CELL * (i as f64 + 0.5);
+            let cells = changes
+                .into_iter()
+                .enumerate()
+                .map(|(j, change)| (change, (left + CELL * (j as f64 + 0.5), y)))
+                .collect();
+            ((name, (left - LABEL_WIDTH / 2., y)), cells)
+        })
+        .collect()
+}
+
+/// Sets the part of the style under `at` for every selected object. Returns whether anything
+/// was clicked.
+pub fn click(state: &mut DState, at: (f64, f64)) -> bool {
+    let selection = selection(state);
+    if selection.is_empty() {
+        return false;
+    }
+    for (_, cells) in layout(state.view.transform.win_size) {
+        for (change, center) in cells {
+            if (at.0 - center.0).abs() < CELL / 2. && (at.1 - center.1).abs() < CELL / 2. {
+                for id in &selection {
+                    change.apply(state.world.style_mut(*id));
+                }
+                return true;
+            }
+        }
+    }
+    false
+}
+
+pub fn draw(canvas: &mut Canvas<Window>, state: &DState) -> Result<(), String> {
+    let selection = selection(state);
+    if selection.is_empty() {
+        return Ok(());
+    }
+    let styles: Vec<Style> = selection.iter().map(|id| state.world.style(*id)).collect();
+
+    for ((name, at), cells) in layout(state.view.transform.win_size) {
+        text::draw_label(
+            canvas,
+            name,
+            at,
+            state.theme.foreground,
+            state.theme.label_background,
+        )?;
+
+        for (change, (x, y)) in cells {
+            let half = CELL / 2.;
+            canvas.set_draw_color(state.theme.label_background);
+            canvas.fill_rect(Rect::new(
+                (x - half) as i32,
+                (y - half) as i32,
+                CELL as u32,
+                CELL as u32,
+            ))?;
+
+            draw_choice(canvas, &change, &state.theme, (x, y))?;
+
+            // Choices the whole selection already has are framed
+            let current = styles.iter().all(|style| {
+                let mut changed = style.clone();
+                change.apply(&mut changed);
+                changed == *style
+            });
+            if current {
+                canvas.set_draw_color(state.theme.hover);
+                canvas.draw_rect(Rect::new(
+                    (x - half) as i32 + 1,
+                    (y - half) as i32 + 1,
+                    CELL as u32 - 2,
+                    CELL as u32 - 2,
+                ))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A small picture of what a choice looks like
+fn draw_choice(
+    canvas: &mut Canvas<Window>,
+    change: &Change,
+    theme: &Theme,
+    (x, y): (f64, f64),
+) -> Result<(), String> {
+    let r = CELL / 2. - 5.;
+    let line = [(x - r, y), (x + r, y)];
+    let square = [
+        (x - r, y - r),
+        (x + r, y - r),
+        (x + r, y + r),
+        (x - r, y + r),
+    ];
+    let example = Style::default();
+
+    canvas.set_draw_color(theme.foreground);
+    match change {
+        Change::Color(color) => {
+            canvas.set_draw_color(theme.ink(*color, 255));
+            fill_polygon(canvas, &square)
+        }
+        Change::Width(width) => stroke_path(canvas, &line, *width, &[]),
+        // Shorter dashes, so the pattern fits
+        Change::Dash(dash) => {
+            let dash: Vec<f64> = dash.iter().map(|len| len / 2.).collect();
+            stroke_path(canvas, &line, 2., &dash)
+        }
+        Change::PointShape(shape) => {
+            let style = Style {
+                point_shape: *shape,
+                ..example
+            };
+            super::draw_point(canvas, (x, y), &style, 1.)
+        }
+        Change::PointSize(size) => {
+            let style = Style {
+                point_size: *size,
+                ..example
+            };
+            super::draw_point(canvas, (x, y), &style, 1.)
+        }
+        Change::Fill(None) => {
+            let mut outline = square.to_vec();
+            outline.push(square[0]);
+            stroke_path(canvas, &outline, 1., &[])?;
+            stroke_path(canvas, &[square[3], square[1]], 1., &[])
+        }
+        Change::Fill(Some((red, green, blue))) => {
+            let alpha = (gwrapper::DEFAULT_FILL.opacity * 255.) as u8;
+            canvas.set_draw_color(Color::RGBA(*red, *green, *blue, alpha));
+            fill_polygon(canvas, &square)?;
+            canvas.set_draw_color(theme.foreground);
+            let mut outline = square.to_vec();
+            outline.push(square[0]);
+            stroke_path(canvas, &outline, 1., &[])
+        }
+    }
+}
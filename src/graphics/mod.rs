@@ -1,49 +1,230 @@
+mod stroke;
+pub mod text;
+
+pub use self::stroke::{clip_segment, stroke_circle, stroke_line, stroke_path};
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::f64;
 use std::f64::consts::PI;
+use std::mem;
+use std::path::PathBuf;
 use std::sync::{
     mpsc::{channel, Receiver},
     Arc, Mutex,
 };
 
-use backend::gwrapper::GWrapper;
-use drawing_board::{DrawingBoard, View};
+use backend::format;
+use backend::gwrapper::{GWrapper, ThingID};
+use backend::puzzle::Puzzle;
+use drawing_board::{save_path, ClipboardRequest, DrawingBoard, View};
+use keymap::Keymap;
+use macros::Macro;
+use recorder::Recorder;
+use theme::Theme;
 use transform::Transform;
 use tool::{Tool, ToolKind};
-use toolbar::{default_toolbar, Button, ToolBar};
+use toolbar::{default_toolbar, macro_tool, Button, ToolBar};
 use ytesrev::drawable::KnownSize;
 use ytesrev::prelude::*;
 use ytesrev::sdl2::event::Event;
 
 pub const WINDOW_SIZE: (u32, u32) = (1200, 800);
 
+/// Everything about the window, with the construction of the shown tab
 pub struct DState {
     pub world: GWrapper,
     pub current_tool: Box<dyn Tool>,
     pub view: View,
+    /// The level being played, if any
+    pub puzzle: Option<Puzzle>,
+    /// Shown on the tab
+    pub name: String,
+    /// Where the construction is saved to, None until it's first saved
+    pub path: Option<PathBuf>,
+    /// The construction as it was last saved or opened, to tell if it has unsaved changes
+    pub saved: String,
+    /// The inputs of the macro being made from the construction, once they are picked
+    pub macro_inputs: Option<HashSet<ThingID>>,
+    /// The macro made last, while its name is asked for
+    pub unnamed_macro: Option<Macro>,
+    /// The constructions in the other tabs, in order
+    pub others: Vec<Document>,
+    /// Where the shown construction is among the tabs
+    pub tab: usize,
+    pub recorder: Option<Recorder>,
+    pub keymap: Keymap,
+    pub theme: Theme,
+    /// The themes the theme key goes through
+    pub themes: Vec<Theme>,
+    /// The library, every macro having a tool
+    pub macros: Vec<Macro>,
+    /// Copying or pasting, done when the board is next drawn
+    pub clipboard: Option<ClipboardRequest>,
+}
+
+/// A construction in a tab that isn't shown
+pub struct Document {
+    pub world: GWrapper,
+    pub current_tool: Box<dyn Tool>,
+    pub view: View,
+    pub puzzle: Option<Puzzle>,
+    pub name: String,
+    pub path: Option<PathBuf>,
+    pub saved: String,
+    pub macro_inputs: Option<HashSet<ThingID>>,
+    pub unnamed_macro: Option<Macro>,
+}
+
+impl Document {
+    pub fn new(
+        name: String,
+        path: Option<PathBuf>,
+        world: GWrapper,
+        puzzle: Option<Puzzle>,
+    ) -> Document {
+        Document {
+            saved: format::write(&world),
+            world,
+            current_tool: ToolKind::Selector.into_tool(),
+            view: View::new(Transform::new_from_winsize((
+                WINDOW_SIZE.0 as f64,
+                WINDOW_SIZE.1 as f64,
+            ))),
+            puzzle,
+            name,
+            path,
+            macro_inputs: None,
+            unnamed_macro: None,
+        }
+    }
+}
+
+impl DState {
+    /// The names of all tabs, in order
+    pub fn tab_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.others.iter().map(|doc| doc.name.clone()).collect();
+        names.insert(self.tab, self.name.clone());
+        names
+    }
+
+    /// Shows the construction in the tab `to`
+    pub fn switch_tab(&mut self, to: usize) {
+        if to == self.tab || to > self.others.len() {
+            return;
+        }
+        // Where `to` is among the others, and where the shown one goes
+        let (from, back) = if to < self.tab {
+            (to, self.tab - 1)
+        } else {
+            (to - 1, self.tab)
+        };
+        let mut doc = self.others.remove(from);
+        self.swap(&mut doc);
+        self.others.insert(back, doc);
+        self.tab = to;
+    }
+
+    /// Shows the tab after the current one, or before it for a negative `step`
+    pub fn step_tab(&mut self, step: isize) {
+        let count = self.others.len() as isize + 1;
+        let to = ((self.tab as isize + step) % count + count) % count;
+        self.switch_tab(to as usize);
+    }
+
+    /// Shows `doc` in a new tab after the last one
+    pub fn open(&mut self, mut doc: Document) {
+        self.swap(&mut doc);
+        self.others.insert(self.tab, doc);
+        self.tab = self.others.len();
+    }
+
+    /// Whether the shown construction changed since it was last saved or opened
+    pub fn is_modified(&self) -> bool {
+        format::write(&self.world) != self.saved
+    }
+
+    /// Closes the shown tab and shows the next one, unless it's the only tab
+    pub fn close_tab(&mut self) {
+        if self.others.is_empty() {
+            return;
+        }
+        let next = if self.tab < self.others.len() {
+            self.tab
+        } else {
+            self.tab - 1
+        };
+        let mut doc = self.others.remove(next);
+        self.swap(&mut doc);
+        self.tab = next;
+    }
+
+    /// The first save file no tab is saved to
+    pub fn unused_save_path(&self) -> PathBuf {
+        let taken = |path: &PathBuf| {
+            self.path.as_ref() == Some(path)
+                || self
+                    .others
+                    .iter()
+                    .any(|doc| doc.path.as_ref() == Some(path))
+        };
+        (1..).map(save_path).find(|path| !taken(path)).unwrap()
+    }
+
+    fn swap(&mut self, doc: &mut Document) {
+        mem::swap(&mut self.world, &mut doc.world);
+        mem::swap(&mut self.current_tool, &mut doc.current_tool);
+        mem::swap(&mut self.view, &mut doc.view);
+        mem::swap(&mut self.puzzle, &mut doc.puzzle);
+        mem::swap(&mut self.name, &mut doc.name);
+        mem::swap(&mut self.path, &mut doc.path);
+        mem::swap(&mut self.saved, &mut doc.saved);
+        mem::swap(&mut self.macro_inputs, &mut doc.macro_inputs);
+        mem::swap(&mut self.unnamed_macro, &mut doc.unnamed_macro);
+    }
 }
 
 pub struct DScene {
     inner: Split<ToolBar, DrawingBoard>,
     state: Arc<Mutex<DState>>,
     tool_change: Receiver<Button>,
+    /// The number of tools before the macros
+    builtin_tools: usize,
 }
 
-pub fn create_layout(world: GWrapper) -> DScene {
+/// Shows the first of `documents`, with the rest in other tabs
+pub fn create_layout(
+    documents: Vec<Document>,
+    keymap: Keymap,
+    themes: Vec<Theme>,
+    macros: Vec<Macro>,
+) -> DScene {
     let (send, recv) = channel::<Button>();
-    let tool_bar = default_toolbar(send);
+    // A custom theme is used from the start
+    let theme = *themes.last().expect("No themes");
+    let tool_bar = default_toolbar(send, theme);
+    let builtin_tools = tool_bar.tools.len();
 
+    let mut documents = documents.into_iter();
+    let first = documents.next().expect("No documents");
     let state = DState {
-        world: world,
-        current_tool: ToolKind::Selector.into_tool(),
-        view: View {
-            transform: Transform::new_from_winsize((
-                WINDOW_SIZE.0 as f64,
-                WINDOW_SIZE.1 as f64,
-            )),
-            mouse_last: Point::new(0, 0),
-            moving_screen: false,
-            scrolling: 0.,
-            show_hidden: true,
-        },
+        world: first.world,
+        current_tool: first.current_tool,
+        view: first.view,
+        puzzle: first.puzzle,
+        name: first.name,
+        path: first.path,
+        saved: first.saved,
+        macro_inputs: first.macro_inputs,
+        unnamed_macro: first.unnamed_macro,
+        others: documents.collect(),
+        tab: 0,
+        recorder: None,
+        keymap,
+        theme,
+        themes,
+        macros,
+        clipboard: None,
     };
 
     let state_arc_mutex = Arc::new(Mutex::new(state));
@@ -59,6 +240,14 @@ pub fn create_layout(world: GWrapper) -> DScene {
         ),
         state: state_arc_mutex.clone(),
         tool_change: recv,
+        builtin_tools,
+    }
+}
+
+impl DScene {
+    /// Shows a message over the drawing board
+    pub fn message<S: Into<String>>(&self, text: S) {
+        self.state.lock().unwrap().view.message(text);
     }
 }
 
@@ -72,6 +261,18 @@ impl Scene for DScene {
                 (*callback.function)(state);
             }
         }
+        if let Ok(ref state) = self.state.lock() {
+            self.inner.first.theme = state.theme;
+            self.inner.first.tabs = state.tab_names();
+            self.inner.first.tab = state.tab;
+
+            // A macro was made since the last update
+            let tools = &mut self.inner.first.tools;
+            if tools.len() != self.builtin_tools + state.macros.len() {
+                tools.truncate(self.builtin_tools);
+                tools.extend(state.macros.iter().cloned().map(macro_tool));
+            }
+        }
 
         let tb_height = self.inner.first.height();
         self.inner.amount = Box::new(move |_| tb_height as u32);
@@ -79,6 +280,12 @@ impl Scene for DScene {
 
     fn draw(&self, canvas: &mut Canvas<Window>, settings: DrawSettings) {
         let (w, h) = canvas.window().size();
+        // On high density displays a window size unit is several pixels. Everything is laid out
+        // in window units, like the mouse, and scaled up to the pixels drawn.
+        let (pw, ph) = canvas.output_size().unwrap_or((w, h));
+        canvas
+            .set_scale(pw as f32 / w.max(1) as f32, ph as f32 / h.max(1) as f32)
+            .expect("Can't scale the window");
         self.inner
             .draw(canvas, &Position::Rect(Rect::new(0, 0, w, h)), settings);
     }
@@ -120,51 +327,57 @@ impl Scene for DScene {
 pub const STEPS_BY_RADIUS: f64 = 1.5;
 pub const MAX_STEPS: usize = 500;
 
-pub fn draw_circle(canvas: &mut Canvas<Window>, pos: (f64, f64), r: f64) -> Result<(), String> {
-    let (width, height) = canvas.window().size();
-    let (width, height) = (width as i32, height as i32);
-
-    let points = draw_circle_points(pos, r);
+/// The corners of a regular polygon with enough sides to look like a circle, first and last
+/// being the same
+pub fn circle_path((x, y): (f64, f64), r: f64) -> Vec<(f64, f64)> {
+    let steps = ((STEPS_BY_RADIUS * r) as usize).clamp(8, MAX_STEPS);
+    (0..steps + 1)
+        .map(|i| {
+            let theta = (i as f64 / steps as f64) * 2. * PI;
+            (x + r * theta.cos(), y + r * theta.sin())
+        })
+        .collect()
+}
 
-    if points.len() == 0 {
+/// Scanline fill with the current draw color, using the even-odd rule
+pub fn fill_polygon(canvas: &mut Canvas<Window>, vertices: &[(f64, f64)]) -> Result<(), String> {
+    if vertices.len() < 3 {
         return Ok(());
     }
+    let (width, height) = canvas.window().size();
+
+    let min_y = vertices.iter().map(|v| v.1).fold(f64::INFINITY, f64::min);
+    let max_y = vertices
+        .iter()
+        .map(|v| v.1)
+        .fold(f64::NEG_INFINITY, f64::max);
 
-    let mut last = *points.last().unwrap();
+    for y in (min_y.max(0.) as i32)..(max_y.min(height as f64) as i32 + 1) {
+        let fy = y as f64 + 0.5;
+
+        let mut xs = Vec::new();
+        for (i, a) in vertices.iter().enumerate() {
+            let b = vertices[(i + 1) % vertices.len()];
+            if (a.1 > fy) != (b.1 > fy) {
+                xs.push(a.0 + (fy - a.1) * (b.0 - a.0) / (b.1 - a.1));
+            }
+        }
+        xs.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
 
-    for point in points {
-        if last.x() < 0 && point.x() < 0
-            || last.y() < 0 && point.y() < 0
-            || last.x() > width && point.x() > width
-            || last.y() > height && point.y() > height
-        {
-            continue;
+        for span in xs.chunks(2) {
+            if let [start, end] = span {
+                let start = start.max(-1.).round() as i32;
+                let end = end.min(width as f64 + 1.).round() as i32;
+                if start < end {
+                    canvas.draw_line(Point::new(start, y), Point::new(end - 1, y))?;
+                }
+            }
         }
-        utils::line_aa(
-            canvas,
-            (last.x() as f64, last.y() as f64),
-            (point.x() as f64, point.y() as f64),
-        );
-        last = point;
     }
 
     Ok(())
 }
 
-pub fn draw_circle_points((x, y): (f64, f64), r: f64) -> Vec<Point> {
-    let steps = (STEPS_BY_RADIUS as f64 * r) as usize;
-    let steps = steps.min(MAX_STEPS);
-
-    let mut points = Vec::with_capacity(steps);
-
-    for i in 0..steps {
-        let theta = (i as f64 / steps as f64) * 2. * PI;
-        let (x_, y_) = (x + r * theta.cos(), y + r * theta.sin());
-        points.push(Point::new(x_ as i32, y_ as i32));
-    }
-    points
-}
-
 pub fn get_best<T, F: Fn(&T) -> f64>(objs: Vec<T>, f: F) -> Option<(f64, T)> {
     let mut best: Option<(f64, T)> = None;
 
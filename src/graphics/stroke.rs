@@ -0,0 +1,403 @@
+// Antialiased strokes of any width, with dashes. Everything is clipped to the window before it
+// is rasterized, so huge circles and lines far outside it cost nothing at deep zoom.
+
+use std::collections::HashMap;
+use std::f64;
+use std::f64::consts::PI;
+
+use ytesrev::prelude::*;
+
+/// How far the corners of circles drawn as polygons may be from the circle, in pixels
+pub const CIRCLE_TOLERANCE: f64 = 0.2;
+/// The most corners a visible arc of a circle is drawn with
+pub const MAX_ARC_STEPS: usize = 2000;
+/// Dash patterns repeating more often than every this many pixels are drawn solid
+pub const MIN_DASH_PERIOD: f64 = 1.;
+
+/// How much of every pixel a path covers. Where segments of a path overlap, they are only
+/// blended once.
+struct Coverage {
+    pixels: HashMap<(i32, i32), f64>,
+    size: (i32, i32),
+}
+
+impl Coverage {
+    fn new((w, h): (u32, u32)) -> Coverage {
+        Coverage {
+            pixels: HashMap::new(),
+            size: (w as i32, h as i32),
+        }
+    }
+
+    /// A segment with round ends. Thinner strokes than a pixel are drawn a pixel wide but fainter.
+    fn segment(&mut self, a: (f64, f64), b: (f64, f64), width: f64) {
+        let faint = width.clamp(0., 1.);
+        let reach = width.max(1.) / 2. + 0.5;
+        let len = (b.0 - a.0).hypot(b.1 - a.1);
+        let (dx, dy) = if len > 0. {
+            ((b.0 - a.0) / len, (b.1 - a.1) / len)
+        } else {
+            (1., 0.)
+        };
+
+        let top = (a.1.min(b.1) - reach).floor().max(0.) as i32;
+        let bottom = (a.1.max(b.1) + reach).ceil().min(self.size.1 as f64) as i32;
+        for y in top..bottom {
+            let py = y as f64 + 0.5;
+            // Where the row crosses the rectangle around the segment, along and across it
+            let along = span(dx, (py - a.1) * dy - a.0 * dx, -reach, len + reach);
+            let across = span(-dy, (py - a.1) * dx + a.0 * dy, -reach, reach);
+            let (left, right) = match (along, across) {
+                (Some(along), Some(across)) => (along.0.max(across.0), along.1.min(across.1)),
+                _ => continue,
+            };
+            let left = left.floor().max(0.) as i32;
+            let right = right.ceil().min(self.size.0 as f64) as i32;
+
+            for x in left..right {
+                let px = x as f64 + 0.5;
+                let t = ((px - a.0) * dx + (py - a.1) * dy).max(0.).min(len);
+                let dist = (px - a.0 - dx * t).hypot(py - a.1 - dy * t);
+                let cover = (reach - dist).min(1.) * faint;
+                if cover > 0. {
+                    let pixel = self.pixels.entry((x, y)).or_insert(0.);
+                    *pixel = pixel.max(cover);
+                }
+            }
+        }
+    }
+
+    /// Draws the pixels with the same coverage together, as there are only 256 alphas
+    fn draw(&self, canvas: &mut Canvas<Window>) -> Result<(), String> {
+        let color = canvas.draw_color();
+        let mut by_alpha: HashMap<u8, Vec<Point>> = HashMap::new();
+        for (&(x, y), cover) in &self.pixels {
+            let alpha = (color.a as f64 * cover).round() as u8;
+            by_alpha.entry(alpha).or_default().push(Point::new(x, y));
+        }
+        for (alpha, points) in &by_alpha {
+            canvas.set_draw_color(Color::RGBA(color.r, color.g, color.b, *alpha));
+            canvas.draw_points(&points[..])?;
+        }
+        canvas.set_draw_color(color);
+        Ok(())
+    }
+}
+
+/// The x where `slope * x + offset` is between `min` and `max`, None if there is none
+fn span(slope: f64, offset: f64, min: f64, max: f64) -> Option<(f64, f64)> {
+    if slope == 0. {
+        return if min <= offset && offset <= max {
+            Some((f64::NEG_INFINITY, f64::INFINITY))
+        } else {
+            None
+        };
+    }
+    let (a, b) = ((min - offset) / slope, (max - offset) / slope);
+    Some((a.min(b), a.max(b)))
+}
+
+/// The part of the segment from `a` to `b` inside the rectangle from `min` to `max`, as distances
+/// from `a` along the segment
+pub fn clip_segment(
+    a: (f64, f64),
+    b: (f64, f64),
+    min: (f64, f64),
+    max: (f64, f64),
+) -> Option<(f64, f64)> {
+    let len = (b.0 - a.0).hypot(b.1 - a.1);
+    let (mut start, mut end) = (0_f64, len);
+    if len == 0. {
+        let inside = min.0 <= a.0 && a.0 <= max.0 && min.1 <= a.1 && a.1 <= max.1;
+        return if inside { Some((0., 0.)) } else { None };
+    }
+    let (dx, dy) = ((b.0 - a.0) / len, (b.1 - a.1) / len);
+    for &(dir, from, low, high) in &[(dx, a.0, min.0, max.0), (dy, a.1, min.1, max.1)] {
+        if dir == 0. {
+            if from < low || from > high {
+                return None;
+            }
+            continue;
+        }
+        let (t0, t1) = ((low - from) / dir, (high - from) / dir);
+        start = start.max(t0.min(t1));
+        end = end.min(t0.max(t1));
+    }
+    if start <= end {
+        Some((start, end))
+    } else {
+        None
+    }
+}
+
+/// The angles of the arcs of a circle inside the rectangle from `min` to `max`, each from a
+/// smaller to a larger angle
+pub fn visible_arcs(
+    (x, y): (f64, f64),
+    r: f64,
+    min: (f64, f64),
+    max: (f64, f64),
+) -> Vec<(f64, f64)> {
+    if r.is_nan() || r <= 0. || x + r < min.0 || x - r > max.0 || y + r < min.1 || y - r > max.1 {
+        return Vec::new();
+    }
+    let inside = |theta: f64| {
+        let (px, py) = (x + r * theta.cos(), y + r * theta.sin());
+        min.0 <= px && px <= max.0 && min.1 <= py && py <= max.1
+    };
+
+    // Where the circle crosses the edges of the rectangle
+    let mut angles = Vec::new();
+    for &(edge, center, vertical) in &[
+        (min.0, x, true),
+        (max.0, x, true),
+        (min.1, y, false),
+        (max.1, y, false),
+    ] {
+        let d = edge - center;
+        if d.abs() > r {
+            continue;
+        }
+        let e = ((r - d) * (r + d)).sqrt();
+        for &e in &[e, -e] {
+            let theta = if vertical { e.atan2(d) } else { d.atan2(e) };
+            angles.push((theta + 2. * PI) % (2. * PI));
+        }
+    }
+    // A center that isn't a number gives crossings that aren't either
+    angles.retain(|theta| theta.is_finite());
+    if angles.is_empty() {
+        return if inside(0.) {
+            vec![(0., 2. * PI)]
+        } else {
+            Vec::new()
+        };
+    }
+    angles.sort_by(f64::total_cmp);
+
+    let mut arcs: Vec<(f64, f64)> = Vec::new();
+    for (i, start) in angles.iter().enumerate() {
+        let end = angles.get(i + 1).cloned().unwrap_or(angles[0] + 2. * PI);
+        if end - start > 0. && inside((start + end) / 2.) {
+            match arcs.last_mut() {
+                Some(last) if last.1 == *start => last.1 = end,
+                _ => arcs.push((*start, end)),
+            }
+        }
+    }
+    arcs
+}
+
+/// Where `at` is in a dash pattern: which of the lengths, and how much of it is left
+fn dash_at(dash: &[f64], at: f64) -> (usize, f64) {
+    let period: f64 = dash.iter().sum();
+    let mut at = (at % period + period) % period;
+    for (i, len) in dash.iter().enumerate() {
+        if at < *len {
+            return (i, len - at);
+        }
+        at -= len;
+    }
+    (0, dash[0])
+}
+
+/// The lengths of dashes and gaps taking turns, an odd number of lengths being repeated so that
+/// every other length is a dash. None for a solid line, which is also what patterns with
+/// negative lengths or a period under MIN_DASH_PERIOD are drawn as.
+fn dash_pattern(dash: &[f64]) -> Option<Vec<f64>> {
+    let period: f64 = dash.iter().sum();
+    if period.is_nan() || period < MIN_DASH_PERIOD || dash.iter().any(|len| *len < 0.) {
+        return None;
+    }
+    if dash.len() % 2 == 1 {
+        Some(dash.iter().chain(dash).cloned().collect())
+    } else {
+        Some(dash.to_vec())
+    }
+}
+
+/// Strokes a path with the current draw color, `width` pixels wide and dashed by `dash`, the
+/// lengths of dashes and gaps taking turns. The dashes carry on from one segment to the next,
+/// starting `offset` pixels into the pattern.
+fn stroke(
+    canvas: &mut Canvas<Window>,
+    path: &[(f64, f64)],
+    width: f64,
+    dash: &[f64],
+    offset: f64,
+) -> Result<(), String> {
+    let size = canvas.window().size();
+    let margin = width + 1.;
+    let (min, max) = (
+        (-margin, -margin),
+        (size.0 as f64 + margin, size.1 as f64 + margin),
+    );
+    let pattern = dash_pattern(dash);
+
+    let mut coverage = Coverage::new(size);
+    let mut along = offset;
+    for segment in path.windows(2) {
+        let (a, b) = (segment[0], segment[1]);
+        let len = (b.0 - a.0).hypot(b.1 - a.1);
+        let at = |t: f64| {
+            if len == 0. {
+                a
+            } else {
+                (a.0 + (b.0 - a.0) * t / len, a.1 + (b.1 - a.1) * t / len)
+            }
+        };
+
+        if let Some((start, end)) = clip_segment(a, b, min, max) {
+            if let Some(ref dash) = pattern {
+                let (mut i, mut left) = dash_at(dash, along + start);
+                let mut t = start;
+                while t < end {
+                    let step = left.min(end - t);
+                    if i % 2 == 0 {
+                        coverage.segment(at(t), at(t + step), width);
+                    }
+                    t += step;
+                    left -= step;
+                    if left <= 0. {
+                        i = (i + 1) % dash.len();
+                        left = dash[i];
+                    }
+                }
+            } else {
+                coverage.segment(at(start), at(end), width);
+            }
+        }
+        along += len;
+    }
+    coverage.draw(canvas)
+}
+
+/// Strokes a path with the current draw color, `width` pixels wide and dashed by `dash`, the
+/// lengths of dashes and gaps taking turns. The dashes carry on from one segment to the next.
+pub fn stroke_path(
+    canvas: &mut Canvas<Window>,
+    path: &[(f64, f64)],
+    width: f64,
+    dash: &[f64],
+) -> Result<(), String> {
+    stroke(canvas, path, width, dash, 0.)
+}
+
+/// Strokes the parts of a circle inside the window. The dashes start at the right of the circle,
+/// so they stay in place when only a part of it is shown.
+pub fn stroke_circle(
+    canvas: &mut Canvas<Window>,
+    (x, y): (f64, f64),
+    r: f64,
+    width: f64,
+    dash: &[f64],
+) -> Result<(), String> {
+    let (w, h) = canvas.window().size();
+    let margin = width + 1.;
+    let arcs = visible_arcs(
+        (x, y),
+        r,
+        (-margin, -margin),
+        (w as f64 + margin, h as f64 + margin),
+    );
+
+    // The corners are close enough that the chords between them stay near the circle
+    let step = if r > CIRCLE_TOLERANCE {
+        2. * (1. - CIRCLE_TOLERANCE / r).acos()
+    } else {
+        PI / 4.
+    };
+    for (start, end) in arcs {
+        let steps = (((end - start) / step).ceil() as usize).clamp(1, MAX_ARC_STEPS);
+        let path: Vec<_> = (0..steps + 1)
+            .map(|i| {
+                let theta = start + (end - start) * i as f64 / steps as f64;
+                (x + r * theta.cos(), y + r * theta.sin())
+            })
+            .collect();
+        stroke(canvas, &path, width, dash, r * start)?;
+    }
+    Ok(())
+}
+
+/// Strokes the line through `through` going in `direction`, as far as it's inside the window.
+/// The dashes are counted from `through`.
+pub fn stroke_line(
+    canvas: &mut Canvas<Window>,
+    through: (f64, f64),
+    direction: (f64, f64),
+    width: f64,
+    dash: &[f64],
+) -> Result<(), String> {
+    let len = direction.0.hypot(direction.1);
+    if len.is_nan() || len <= 0. {
+        return Ok(());
+    }
+    let (dx, dy) = (direction.0 / len, direction.1 / len);
+    let (w, h) = canvas.window().size();
+    let (w, h) = (w as f64, h as f64);
+
+    // Far enough both ways to cross the whole window
+    let reach = (through.0 - w / 2.).hypot(through.1 - h / 2.) + w + h + width;
+    let path = [
+        (through.0 - dx * reach, through.1 - dy * reach),
+        (through.0 + dx * reach, through.1 + dy * reach),
+    ];
+    stroke(canvas, &path, width, dash, -reach)
+}
+
+#[test]
+fn test_clip_segment() {
+    let (min, max) = ((0., 0.), (100., 50.));
+    assert_eq!(
+        clip_segment((10., 10.), (20., 20.), min, max),
+        Some((0., 200_f64.sqrt()))
+    );
+    assert_eq!(
+        clip_segment((-50., 25.), (150., 25.), min, max),
+        Some((50., 150.))
+    );
+    assert_eq!(clip_segment((50., -10.), (50., -20.), min, max), None);
+    assert_eq!(clip_segment((90., -20.), (120., 10.), min, max), None);
+
+    // A line crossing a corner
+    let (start, end) = clip_segment((-10., 10.), (10., -10.), min, max).unwrap();
+    assert!((start - 200_f64.sqrt()).abs() < 1e-9 && (end - start).abs() < 1e-9);
+}
+
+#[test]
+fn test_visible_arcs() {
+    let (min, max) = ((0., 0.), (100., 100.));
+    assert_eq!(visible_arcs((50., 50.), 10., min, max), vec![(0., 2. * PI)]);
+    // Around the whole rectangle, or away from it
+    assert!(visible_arcs((50., 50.), 100., min, max).is_empty());
+    assert!(visible_arcs((300., 50.), 100., min, max).is_empty());
+
+    // Cut by the left edge, leaving the part right of it
+    let arcs = visible_arcs((0., 50.), 10., min, max);
+    assert_eq!(arcs.len(), 1);
+    let (start, end) = arcs[0];
+    assert!((start - 3. * PI / 2.).abs() < 1e-9 && (end - 5. * PI / 2.).abs() < 1e-9);
+
+    // A huge circle only passing through a corner is drawn with few corners
+    let r = 1e9;
+    let arcs = visible_arcs((-r + 10., 50.), r, min, max);
+    assert_eq!(arcs.len(), 1);
+    assert!((arcs[0].1 - arcs[0].0) * r < 200.);
+    assert!(visible_arcs((f64::NAN, 50.), 10., min, max).is_empty());
+
+    assert_eq!(dash_at(&[4., 2.], 5.), (1, 1.));
+    assert_eq!(dash_at(&[4., 2.], -1.), (1, 1.));
+}
+
+#[test]
+fn test_dash_pattern() {
+    assert_eq!(dash_pattern(&[4., 2.]), Some(vec![4., 2.]));
+    assert_eq!(dash_pattern(&[3.]), Some(vec![3., 3.]));
+    assert_eq!(dash_pattern(&[]), None);
+    assert_eq!(dash_pattern(&[4., -2.]), None);
+    // Tiny dashes would take forever to step through along a long line
+    assert_eq!(dash_pattern(&[1e-9, 1e-9]), None);
+    assert_eq!(dash_pattern(&[0.3, 0.3]), None);
+    assert_eq!(dash_pattern(&[f64::NAN, 2.]), None);
+}
@@ -1,46 +1,483 @@
-use std::io::Cursor;
-use ytesrev::image::PngImage;
-
-macro_rules! load_image {
-    ($path:expr) => {
-        PngImage::load_from_path(Cursor::new(
-            include_bytes!(concat!("../../resources/", $path)) as &[u8],
-        )).unwrap()
-    };
+// The toolbar icons, drawn from vector data so they are sharp at any size and follow the theme.
+// There are no image files: the builders below are the source of the icons. Every icon is made in
+// a 64 by 64 box and drawn in the foreground color of the theme unless a part has a color of its
+// own.
+
+use std::f64::consts::PI;
+
+use backend::{geometry, gwrapper};
+use graphics::{circle_path, clip_segment, fill_polygon, stroke_path};
+
+use ytesrev::prelude::*;
+
+/// The size icons are made in
+pub const ICON_SIZE: f64 = 64.;
+/// The radius of the points in icons
+const POINT_RADIUS: f64 = 4.;
+const GRID_OPACITY: f64 = 0.35;
+/// The space around the figure of a macro
+const FIGURE_MARGIN: f64 = 8.;
+
+/// The color a part is drawn in, and how opaque it is
+#[derive(Clone, Copy, Debug)]
+enum Ink {
+    Foreground(f64),
+    Color((u8, u8, u8), f64),
 }
 
-lazy_static! {
-    pub static ref TOOL_POINT: PngImage = load_image!("tool_point.png");
-    pub static ref TOOL_LINE: PngImage = load_image!("tool_line.png");
-    pub static ref TOOL_CIRCLE: PngImage = load_image!("tool_circle.png");
-    pub static ref TOOL_MOVER: PngImage = load_image!("tool_mover.png");
-    pub static ref TOOL_SELECTOR: PngImage = load_image!("tool_selector.png");
-    pub static ref TOOL_PEEK: PngImage = load_image!("tool_peek.png");
-    pub static ref SELECTED_HIDE: PngImage = load_image!("selected_hide.png");
-    pub static ref SELECTED_SHOW: PngImage = load_image!("selected_show.png");
-    pub static ref CIRCLE_NORMAL: CircleImage = CircleImage::new(load_image!("circle_normal.png"));
-    pub static ref CIRCLE_PRIMARY: CircleImage =
-        CircleImage::new(load_image!("circle_primary.png"));
-    pub static ref CIRCLE_ACTIVE: CircleImage = CircleImage::new(load_image!("circle_active.png"));
-}
-
-pub struct CircleImage {
-    pub normal: PngImage,
-    pub transparent: PngImage,
-}
-
-impl CircleImage {
-    pub fn new(normal: PngImage) -> CircleImage {
-        // Make transparent
-        let mut transparent = normal.clone();
-
-        for i in 0..transparent.data.len() / 4 {
-            transparent.data[i * 4 + 3] /= 4;
+#[derive(Clone, Debug)]
+enum Part {
+    Disc((f64, f64), f64),
+    /// An ellipse with the radii along the axes, and the width of the stroke
+    Ring((f64, f64), (f64, f64), f64),
+    Stroke(Vec<(f64, f64)>, f64),
+    Fill(Vec<(f64, f64)>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Icon {
+    parts: Vec<(Ink, Part)>,
+    ink: Ink,
+}
+
+impl Icon {
+    pub fn new() -> Icon {
+        Icon {
+            parts: Vec::new(),
+            ink: Ink::Foreground(1.),
+        }
+    }
+
+    /// Draws the following parts in the foreground color
+    fn pen(mut self, opacity: f64) -> Icon {
+        self.ink = Ink::Foreground(opacity);
+        self
+    }
+
+    /// Draws the following parts in a color of their own
+    fn colored(mut self, color: (u8, u8, u8), opacity: f64) -> Icon {
+        self.ink = Ink::Color(color, opacity);
+        self
+    }
+
+    fn part(mut self, part: Part) -> Icon {
+        self.parts.push((self.ink, part));
+        self
+    }
+
+    /// A point of a construction
+    fn point(self, center: (f64, f64)) -> Icon {
+        self.disc(center, POINT_RADIUS)
+    }
+
+    fn disc(self, center: (f64, f64), r: f64) -> Icon {
+        self.part(Part::Disc(center, r))
+    }
+
+    fn ring(self, center: (f64, f64), r: f64, width: f64) -> Icon {
+        self.part(Part::Ring(center, (r, r), width))
+    }
+
+    fn ellipse(self, center: (f64, f64), radii: (f64, f64), width: f64) -> Icon {
+        self.part(Part::Ring(center, radii, width))
+    }
+
+    fn line(self, path: &[(f64, f64)], width: f64) -> Icon {
+        self.part(Part::Stroke(path.to_vec(), width))
+    }
+
+    /// A closed line around a polygon
+    fn outline(self, vertices: &[(f64, f64)], width: f64) -> Icon {
+        let mut path = vertices.to_vec();
+        path.extend(vertices.first());
+        self.part(Part::Stroke(path, width))
+    }
+
+    fn fill(self, vertices: &[(f64, f64)]) -> Icon {
+        self.part(Part::Fill(vertices.to_vec()))
+    }
+
+    /// The plus in the corner of tools making new objects
+    fn plus(self) -> Icon {
+        self.line(&[(45.2, 28.8), (45.2, 61.6)], 4.8)
+            .line(&[(28.8, 45.2), (61.6, 45.2)], 4.8)
+    }
+
+    /// Draws the icon scaled to fill `at`
+    pub fn draw(
+        &self,
+        canvas: &mut Canvas<Window>,
+        at: Rect,
+        foreground: Color,
+    ) -> Result<(), String> {
+        let scale = at.width().min(at.height()) as f64 / ICON_SIZE;
+        let place = |(x, y): (f64, f64)| (at.x() as f64 + x * scale, at.y() as f64 + y * scale);
+
+        for (ink, part) in &self.parts {
+            let (r, g, b, opacity) = match *ink {
+                Ink::Foreground(opacity) => (foreground.r, foreground.g, foreground.b, opacity),
+                Ink::Color((r, g, b), opacity) => (r, g, b, opacity),
+            };
+            let alpha = foreground.a as f64 * opacity;
+            canvas.set_draw_color(Color::RGBA(r, g, b, alpha as u8));
+
+            match part {
+                Part::Disc(center, radius) => {
+                    fill_polygon(canvas, &circle_path(place(*center), radius * scale))?
+                }
+                Part::Ring(center, (rx, ry), width) => {
+                    // A circle stretched along the y axis
+                    let (x, y) = place(*center);
+                    let path: Vec<_> = circle_path((x, y), rx * scale)
+                        .into_iter()
+                        .map(|(px, py)| (px, y + (py - y) * ry / rx))
+                        .collect();
+                    stroke_path(canvas, &path, width * scale, &[])?
+                }
+                Part::Stroke(path, width) => {
+                    let path: Vec<_> = path.iter().map(|pos| place(*pos)).collect();
+                    stroke_path(canvas, &path, width * scale, &[])?
+                }
+                Part::Fill(vertices) => {
+                    let vertices: Vec<_> = vertices.iter().map(|pos| place(*pos)).collect();
+                    fill_polygon(canvas, &vertices)?
+                }
+            }
         }
+        Ok(())
+    }
+}
+
+/// The path in the locus icon, a limaçon around the circle the point is on
+fn locus_path() -> Vec<(f64, f64)> {
+    (0..61)
+        .map(|i| {
+            let theta = i as f64 / 60. * 2. * PI;
+            let r = 13. + 13. * theta.cos();
+            (24. + r * theta.cos(), 32. + r * theta.sin())
+        })
+        .collect()
+}
+
+lazy_static! {
+    pub static ref TOOL_POINT: Icon = Icon::new().disc((19.3, 19.8), 10.6).plus();
+    pub static ref TOOL_LINE: Icon = Icon::new()
+        .point((11.3, 30.2))
+        .point((52.9, 12.2))
+        .line(&[(0., 35.1), (64., 7.4)], 1.)
+        .plus();
+    pub static ref TOOL_CIRCLE: Icon = Icon::new()
+        .point((15.1, 12.9))
+        .point((30.3, 27.1))
+        .ring((15.1, 12.9), 20.7, 1.)
+        .plus();
+    pub static ref TOOL_MOVER: Icon = Icon::new()
+        .pen(0.25)
+        .plus()
+        .pen(1.)
+        .line(&[(45.1, 45.1), (45.1, 28.8)], 5.)
+        .line(&[(40.3, 33.6), (45.1, 28.8), (50., 33.6)], 5.)
+        .line(&[(45.1, 45.1), (61.4, 45.1)], 5.)
+        .line(&[(56.6, 40.2), (61.4, 45.1), (56.6, 49.9)], 5.)
+        .line(&[(45.1, 45.1), (45.1, 61.4)], 5.)
+        .line(&[(40.3, 56.6), (45.1, 61.4), (50., 56.6)], 5.)
+        .line(&[(45.1, 45.1), (28.8, 45.1)], 5.)
+        .line(&[(33.6, 40.2), (28.8, 45.1), (33.6, 49.9)], 5.)
+        .disc((21.3, 19.6), 10.6);
+    pub static ref TOOL_SELECTOR: Icon = Icon::new()
+        .outline(&[(3.9, 5.4), (24.6, 5.4), (24.6, 26.9), (3.9, 26.9)], 3.)
+        .outline(&[(30.6, 5.4), (40.1, 22.9), (21.1, 22.9)], 3.)
+        .ring((22.1, 28.9), 10.6, 3.)
+        .fill(&[(45.1, 47.8), (45.1, 30.9), (58.3, 41.5)])
+        .outline(&[(45.1, 47.8), (45.1, 30.9), (58.3, 41.5)], 3.)
+        .line(&[(50.4, 41.9), (59.8, 61.7)], 5.);
+    pub static ref TOOL_PEEK: Icon = Icon::new()
+        .ring((20., 30.), 9., 3.)
+        .ring((44., 30.), 9., 3.)
+        .line(&[(29., 30.), (35., 30.)], 3.)
+        .disc((22., 32.), 3.5)
+        .disc((46., 32.), 3.5)
+        .line(&[(2., 44.), (62., 44.)], 3.);
+    pub static ref TOOL_REFLECT: Icon = Icon::new()
+        .line(&[(32., 2.), (32., 62.)], 1.5)
+        .fill(&[(8., 44.), (22., 44.), (22., 20.)])
+        .outline(&[(56., 44.), (42., 44.), (42., 20.)], 2.);
+    pub static ref TOOL_ROTATE: Icon = Icon::new()
+        .point((14., 50.))
+        .fill(&[(30., 52.), (54., 52.), (54., 38.)])
+        .outline(&[(22., 34.), (34., 14.), (24., 6.)], 2.)
+        .line(&[(40., 30.), (44., 22.), (42., 14.), (36., 8.)], 1.5)
+        .fill(&[(33., 4.), (38., 6.), (34., 11.)]);
+    pub static ref TOOL_TRANSLATE: Icon = Icon::new()
+        .fill(&[(4., 58.), (22., 58.), (22., 36.)])
+        .outline(&[(40., 28.), (58., 28.), (58., 6.)], 2.)
+        .line(&[(16., 32.), (36., 12.)], 1.5)
+        .fill(&[(40., 8.), (30., 12.), (36., 18.)]);
+    pub static ref TOOL_DILATE: Icon = Icon::new()
+        .point((8., 56.))
+        .fill(&[(16., 48.), (28., 48.), (28., 32.)])
+        .outline(&[(24., 40.), (56., 40.), (56., 6.)], 2.)
+        .line(&[(8., 56.), (56., 6.)], 0.75);
+    pub static ref TOOL_DILATE_SLIDER: Icon = Icon::new()
+        .point((8., 48.))
+        .fill(&[(16., 40.), (28., 40.), (28., 26.)])
+        .outline(&[(24., 34.), (56., 34.), (56., 4.)], 2.)
+        .line(&[(8., 48.), (56., 4.)], 0.75)
+        .line(&[(8., 58.), (56., 58.)], 1.5)
+        .fill(&[(24., 52.), (28., 52.), (28., 63.), (24., 63.)]);
+    pub static ref TOOL_INVERT: Icon = Icon::new()
+        .ring((24., 32.), 18., 1.5)
+        .disc((24., 32.), 2.)
+        .line(&[(24., 32.), (60., 32.)], 0.75)
+        .point((33., 32.))
+        .ring((56., 32.), POINT_RADIUS, 2.);
+    pub static ref TOOL_DISTANCE: Icon = Icon::new()
+        .point((10., 40.))
+        .point((54., 40.))
+        .line(&[(10., 24.), (54., 24.)], 1.5)
+        .line(&[(10., 18.), (10., 30.)], 1.5)
+        .line(&[(54., 18.), (54., 30.)], 1.5)
+        .fill(&[(10., 24.), (17., 21.), (17., 27.)])
+        .fill(&[(54., 24.), (47., 21.), (47., 27.)]);
+    pub static ref TOOL_ANGLE: Icon = Icon::new()
+        .line(&[(8., 54.), (58., 54.)], 1.5)
+        .line(&[(8., 54.), (44., 10.)], 1.5)
+        .line(
+            &[
+                (32., 54.),
+                (31.6, 50.1),
+                (30.5, 46.4),
+                (28.7, 43.1),
+                (26.9, 40.8),
+            ],
+            2.,
+        )
+        .point((8., 54.));
+    pub static ref TOOL_RADIUS: Icon = Icon::new()
+        .ring((32., 32.), 26., 1.5)
+        .point((32., 32.))
+        .line(&[(32., 32.), (58., 32.)], 1.5)
+        .fill(&[(58., 32.), (51., 29.), (51., 35.)]);
+    pub static ref TOOL_AREA: Icon = Icon::new()
+        .pen(0.35)
+        .fill(QUADRILATERAL)
+        .pen(1.)
+        .outline(QUADRILATERAL, 1.5)
+        .point((8., 52.))
+        .point((30., 8.))
+        .point((58., 30.))
+        .point((46., 56.));
+    pub static ref TOOL_PERIMETER: Icon = Icon::new()
+        .outline(QUADRILATERAL, 3.)
+        .point((8., 52.))
+        .point((30., 8.))
+        .point((58., 30.))
+        .point((46., 56.));
+    pub static ref TOOL_POLYGON: Icon =
+        polygon(&[(32., 6.), (58., 25.), (48., 56.), (16., 56.), (6., 25.)], 5);
+    pub static ref TOOL_REGULAR_3: Icon = polygon(&[(6., 54.5), (58., 54.5), (32., 9.5)], 2);
+    pub static ref TOOL_REGULAR_4: Icon = polygon(&[(6., 58.), (58., 58.), (58., 6.), (6., 6.)], 2);
+    pub static ref TOOL_REGULAR_5: Icon = polygon(
+        &[
+            (15.9, 56.7),
+            (48.1, 56.7),
+            (58., 26.2),
+            (32., 7.3),
+            (6., 26.2),
+        ],
+        2
+    );
+    pub static ref TOOL_REGULAR_6: Icon = polygon(
+        &[
+            (19., 54.5),
+            (45., 54.5),
+            (58., 32.),
+            (45., 9.5),
+            (19., 9.5),
+            (6., 32.),
+        ],
+        2
+    );
+    pub static ref TOOL_REGULAR_8: Icon = polygon(
+        &[
+            (21.2, 58.),
+            (42.8, 58.),
+            (58., 42.8),
+            (58., 21.2),
+            (42.8, 6.),
+            (21.2, 6.),
+            (6., 21.2),
+            (6., 42.8),
+        ],
+        2
+    );
+    pub static ref TOOL_REDEFINE: Icon = Icon::new()
+        .pen(0.4)
+        .ring((24., 36.), 18., 1.5)
+        .point((24., 36.))
+        .pen(1.)
+        .ring((40., 28.), 18., 1.5)
+        .line(&[(27.6, 34.2), (36.4, 29.8)], 2.5)
+        .line(&[(30.1, 28.5), (36.4, 29.8), (33.7, 35.6)], 2.5)
+        .point((40., 28.));
+    pub static ref TOOL_ATTACH: Icon = Icon::new()
+        .ring((26., 36.), 20., 1.5)
+        .line(&[(4., 22.), (60., 22.)], 1.5)
+        .point((40.3, 22.))
+        .line(&[(49.3, 35.8), (44.1, 27.9)], 2.5)
+        .line(&[(50.2, 29.9), (44.1, 27.9), (43.5, 34.3)], 2.5)
+        .pen(0.4)
+        .point((52., 40.));
+    pub static ref TOOL_LOCUS: Icon = Icon::new()
+        .pen(0.4)
+        .ring((24., 32.), 13., 1.5)
+        .colored(gwrapper::DEFAULT_LOCUS_COLOR, 1.)
+        .line(&locus_path(), 2.5)
+        .pen(1.)
+        .point((24., 19.))
+        .colored(gwrapper::DEFAULT_LOCUS_COLOR, 1.)
+        .point((29.3, 48.2));
+    pub static ref TOOL_ON_CIRCLE: Icon = Icon::new()
+        .ring((30., 30.), 20., 1.5)
+        .line(&[(30., 30.), (44.1, 15.9)], 0.75)
+        .line(&[(30., 30.), (50., 30.)], 0.75)
+        .line(&[(8., 58.), (56., 58.)], 1.5)
+        .fill(&[(36., 52.), (40., 52.), (40., 63.), (36., 63.)])
+        .point((44.1, 15.9));
+    pub static ref TOOL_GRID: Icon = grid(&[8., 20., 44., 56.])
+        .pen(1.)
+        .line(&[(32., 2.), (32., 62.)], 2.)
+        .line(&[(2., 32.), (62., 32.)], 2.);
+    pub static ref TOOL_SNAP: Icon = grid(&[12., 32., 52.])
+        .pen(1.)
+        .line(&[(44., 44.), (35., 35.)], 1.5)
+        .fill(&[(32., 32.), (40., 35.), (35., 40.)])
+        .point((32., 32.))
+        .pen(0.4)
+        .ring((46., 46.), POINT_RADIUS, 1.);
+    pub static ref SELECTED_HIDE: Icon = Icon::new()
+        .ellipse((32., 32.), (29.7, 14.8), 4.6)
+        .ring((32., 32.), 14.8, 4.6)
+        .line(&[(2.3, 61.7), (61.7, 2.3)], 5.);
+    pub static ref SELECTED_SHOW: Icon = Icon::new()
+        .ellipse((32., 32.), (29.7, 14.8), 4.6)
+        .ring((32., 32.), 14.8, 4.6)
+        .disc((32., 32.), 4.8);
+    pub static ref SELECTED_TRACE: Icon = trail().pen(1.).disc((53., 22.), 5.5);
+    pub static ref TRAILS_CLEAR: Icon = trail()
+        .pen(0.6)
+        .point((53., 22.))
+        .pen(1.)
+        .line(&[(8., 8.), (56., 56.)], 5.);
+}
 
-        CircleImage {
-            normal,
-            transparent,
+/// The figure of a macro scaled to fit: its inputs faded, and the points and shapes it makes
+pub fn figure(
+    inputs: &[(f64, f64)],
+    points: &[(f64, f64)],
+    shapes: &[geometry::ResolvedShape],
+) -> Icon {
+    // Everything but the lines fits, and the lines are cut off at the edges
+    let mut corners: Vec<(f64, f64)> = inputs.iter().chain(points).cloned().collect();
+    for shape in shapes {
+        if let geometry::ResolvedShape::Circle((x, y), r) = shape {
+            corners.push((x - r, y - r));
+            corners.push((x + r, y + r));
         }
     }
+    let (min, max) = match corners.first() {
+        Some(first) => corners.iter().fold((*first, *first), |(min, max), pos| {
+            (
+                (min.0.min(pos.0), min.1.min(pos.1)),
+                (max.0.max(pos.0), max.1.max(pos.1)),
+            )
+        }),
+        None => return Icon::new(),
+    };
+    let size = (max.0 - min.0).max(max.1 - min.1);
+    let scale = if size > 0. {
+        (ICON_SIZE - 2. * FIGURE_MARGIN) / size
+    } else {
+        1.
+    };
+    let center = ((min.0 + max.0) / 2., (min.1 + max.1) / 2.);
+    let place = |(x, y): (f64, f64)| {
+        (
+            ICON_SIZE / 2. + (x - center.0) * scale,
+            ICON_SIZE / 2. + (y - center.1) * scale,
+        )
+    };
+
+    let mut icon = Icon::new();
+    for shape in shapes {
+        icon = match *shape {
+            geometry::ResolvedShape::Circle(center, r) => icon.ring(place(center), r * scale, 2.),
+            geometry::ResolvedShape::Line(k, m) => figure_line(icon, place((0., m)), (1., k)),
+            geometry::ResolvedShape::LineUp(x) => figure_line(icon, place((x, 0.)), (0., 1.)),
+        };
+    }
+    let icon = points
+        .iter()
+        .fold(icon, |icon, pos| icon.point(place(*pos)));
+    inputs
+        .iter()
+        .fold(icon.pen(0.4), |icon, pos| icon.point(place(*pos)))
+}
+
+/// The part of the line through `through` inside the icon
+fn figure_line(icon: Icon, through: (f64, f64), (dx, dy): (f64, f64)) -> Icon {
+    let len = dx.hypot(dy);
+    let (dx, dy) = (dx / len, dy / len);
+    // Far enough to cross the whole icon from anywhere the line can be seen
+    let far = 4. * ICON_SIZE + through.0.abs() + through.1.abs();
+    let a = (through.0 - dx * far, through.1 - dy * far);
+    let b = (through.0 + dx * far, through.1 + dy * far);
+    match clip_segment(a, b, (0., 0.), (ICON_SIZE, ICON_SIZE)) {
+        Some((start, end)) => icon.line(
+            &[
+                (a.0 + dx * start, a.1 + dy * start),
+                (a.0 + dx * end, a.1 + dy * end),
+            ],
+            2.,
+        ),
+        None => icon,
+    }
+}
+
+const QUADRILATERAL: &[(f64, f64)] = &[(8., 52.), (30., 8.), (58., 30.), (46., 56.)];
+
+/// A filled polygon, with a point at the first `points` vertices
+fn polygon(vertices: &[(f64, f64)], points: usize) -> Icon {
+    let fill = gwrapper::DEFAULT_FILL;
+    let icon = Icon::new()
+        .colored(fill.color, 0.5)
+        .fill(vertices)
+        .pen(1.)
+        .outline(vertices, 1.5);
+    vertices
+        .iter()
+        .take(points)
+        .fold(icon, |icon, vertex| icon.point(*vertex))
+}
+
+/// Faint lines at `at` both ways
+fn grid(at: &[f64]) -> Icon {
+    at.iter().fold(Icon::new().pen(GRID_OPACITY), |icon, x| {
+        icon.line(&[(*x, 4.), (*x, 60.)], 1.5)
+            .line(&[(4., *x), (60., *x)], 1.5)
+    })
+}
+
+/// A point leaving a fading trail
+fn trail() -> Icon {
+    [
+        (10., 50., 0.15),
+        (18., 40., 0.25),
+        (28., 32., 0.35),
+        (40., 26., 0.5),
+    ]
+    .iter()
+    .fold(Icon::new(), |icon, (x, y, opacity)| {
+        icon.pen(*opacity).point((*x, *y))
+    })
 }
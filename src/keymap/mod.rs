@@ -0,0 +1,423 @@
+// Key bindings. The defaults can be changed in a config file, where every line binds a key
+// chord to an action:
+//
+//     Ctrl+E export_protocol      a chord is modifiers followed by a key
+//     5 tool regular 5            tools are selected by their name
+//     Shift+G none                unbinds a chord
+//
+// Keys are letters, digits, symbols like + or =, and the names in NAMED_KEYS. Everything after a
+// # is a comment.
+
+use std::env;
+use std::path::PathBuf;
+
+use backend::format;
+use macros::Macro;
+use tool::ToolKind;
+use ytesrev::sdl2::keyboard::{
+    Keycode, Mod, LALTMOD, LCTRLMOD, LSHIFTMOD, RALTMOD, RCTRLMOD, RSHIFTMOD,
+};
+
+/// Where the key bindings are read from, relative to the config directory
+pub const CONFIG_FILE: &str = "drawmetry/keys.txt";
+
+/// Keys that aren't a single character
+const NAMED_KEYS: &[(&str, Keycode)] = &[
+    ("Escape", Keycode::Escape),
+    ("Space", Keycode::Space),
+    ("Tab", Keycode::Tab),
+    ("Return", Keycode::Return),
+    ("Backspace", Keycode::Backspace),
+    ("Delete", Keycode::Delete),
+    ("Insert", Keycode::Insert),
+    ("Home", Keycode::Home),
+    ("End", Keycode::End),
+    ("PageUp", Keycode::PageUp),
+    ("PageDown", Keycode::PageDown),
+    ("Left", Keycode::Left),
+    ("Right", Keycode::Right),
+    ("Up", Keycode::Up),
+    ("Down", Keycode::Down),
+    ("KeypadPlus", Keycode::KpPlus),
+    ("KeypadMinus", Keycode::KpMinus),
+    ("F1", Keycode::F1),
+    ("F2", Keycode::F2),
+    ("F3", Keycode::F3),
+    ("F4", Keycode::F4),
+    ("F5", Keycode::F5),
+    ("F6", Keycode::F6),
+    ("F7", Keycode::F7),
+    ("F8", Keycode::F8),
+    ("F9", Keycode::F9),
+    ("F10", Keycode::F10),
+    ("F11", Keycode::F11),
+    ("F12", Keycode::F12),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Chord {
+    pub key: Keycode,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl Chord {
+    pub fn new(key: Keycode, keymod: Mod) -> Chord {
+        Chord {
+            key,
+            ctrl: keymod.intersects(LCTRLMOD | RCTRLMOD),
+            shift: keymod.intersects(LSHIFTMOD | RSHIFTMOD),
+            alt: keymod.intersects(LALTMOD | RALTMOD),
+        }
+    }
+
+    /// Reads a chord like "Ctrl+Shift+Z"
+    pub fn parse(text: &str) -> Result<Chord, String> {
+        let (mut ctrl, mut shift, mut alt) = (false, false, false);
+        let mut rest = text;
+        loop {
+            let lower = rest.to_lowercase();
+            if lower.starts_with("ctrl+") && rest.len() > 5 {
+                ctrl = true;
+                rest = &rest[5..];
+            } else if lower.starts_with("shift+") && rest.len() > 6 {
+                shift = true;
+                rest = &rest[6..];
+            } else if lower.starts_with("alt+") && rest.len() > 4 {
+                alt = true;
+                rest = &rest[4..];
+            } else {
+                break;
+            }
+        }
+
+        let key = match NAMED_KEYS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(rest))
+        {
+            Some((_, key)) => Some(*key),
+            // Other keycodes are the lowercase character of the key
+            None if rest.chars().count() == 1 => {
+                Keycode::from_i32(rest.to_lowercase().chars().next().unwrap() as i32)
+            }
+            None => None,
+        };
+        match key {
+            Some(key) => Ok(Chord {
+                key,
+                ctrl,
+                shift,
+                alt,
+            }),
+            None => Err(format!("Unknown key `{}`", rest)),
+        }
+    }
+
+    /// The inverse of `parse`
+    pub fn name(&self) -> String {
+        let key = match NAMED_KEYS.iter().find(|(_, key)| *key == self.key) {
+            Some((name, _)) => name.to_string(),
+            None => ::std::char::from_u32(self.key as u32)
+                .map(|ch| ch.to_uppercase().to_string())
+                .unwrap_or_else(|| "?".to_string()),
+        };
+        let mut name = String::new();
+        for &(held, modifier) in &[
+            (self.ctrl, "Ctrl+"),
+            (self.shift, "Shift+"),
+            (self.alt, "Alt+"),
+        ] {
+            if held {
+                name.push_str(modifier);
+            }
+        }
+        name + &key
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Action {
+    Tool(ToolKind),
+    /// Starts over with the current tool
+    Cancel,
+    /// Moves the view with the mouse while held
+    MoveScreen,
+    StepBack,
+    StepForward,
+    FirstStep,
+    LastStep,
+    ToggleProtocol,
+    ExportProtocol,
+    Save,
+    NewTab,
+    /// Closes the shown tab, unless it's the only one
+    CloseTab,
+    NextTab,
+    PreviousTab,
+    /// Copies the selected objects and what they are constructed from
+    Copy,
+    /// Adds what was copied to the shown construction
+    Paste,
+    /// Takes the selection as the inputs of a new macro, and then as its outputs
+    MakeMacro,
+    /// Merges every point or shape that is the same as an older one into it
+    MergeDuplicates,
+    PlaySliders,
+    ToggleRecording,
+    Fit,
+    PanLeft,
+    PanRight,
+    PanUp,
+    PanDown,
+    ZoomIn,
+    ZoomOut,
+    ToggleHidden,
+    ToggleGrid,
+    ToggleSnap,
+    ToggleTheme,
+    ToggleHelp,
+}
+
+/// The name of every action without arguments, as used in the config file
+const ACTIONS: &[(&str, Action)] = &[
+    ("cancel", Action::Cancel),
+    ("move_screen", Action::MoveScreen),
+    ("step_back", Action::StepBack),
+    ("step_forward", Action::StepForward),
+    ("first_step", Action::FirstStep),
+    ("last_step", Action::LastStep),
+    ("protocol", Action::ToggleProtocol),
+    ("export_protocol", Action::ExportProtocol),
+    ("save", Action::Save),
+    ("new_tab", Action::NewTab),
+    ("close_tab", Action::CloseTab),
+    ("next_tab", Action::NextTab),
+    ("previous_tab", Action::PreviousTab),
+    ("copy", Action::Copy),
+    ("paste", Action::Paste),
+    ("make_macro", Action::MakeMacro),
+    ("merge_duplicates", Action::MergeDuplicates),
+    ("play_sliders", Action::PlaySliders),
+    ("record", Action::ToggleRecording),
+    ("fit", Action::Fit),
+    ("pan_left", Action::PanLeft),
+    ("pan_right", Action::PanRight),
+    ("pan_up", Action::PanUp),
+    ("pan_down", Action::PanDown),
+    ("zoom_in", Action::ZoomIn),
+    ("zoom_out", Action::ZoomOut),
+    ("toggle_hidden", Action::ToggleHidden),
+    ("grid", Action::ToggleGrid),
+    ("snap", Action::ToggleSnap),
+    ("theme", Action::ToggleTheme),
+    ("help", Action::ToggleHelp),
+];
+
+const DEFAULT_BINDINGS: &str = "
+    P tool point
+    C tool circle
+    L tool line
+    M tool mover
+    S tool selector
+    Escape cancel
+    Space move_screen
+    PageUp step_back
+    PageDown step_forward
+    Home first_step
+    End last_step
+    Tab protocol
+    E export_protocol
+    Ctrl+S save
+    Ctrl+T new_tab
+    Ctrl+W close_tab
+    Ctrl+Tab next_tab
+    Ctrl+Shift+Tab previous_tab
+    Ctrl+C copy
+    Ctrl+V paste
+    Ctrl+M make_macro
+    Ctrl+D merge_duplicates
+    A play_sliders
+    R record
+    F fit
+    Left pan_left
+    Right pan_right
+    Up pan_up
+    Down pan_down
+    = zoom_in
+    Shift+= zoom_in
+    + zoom_in
+    KeypadPlus zoom_in
+    - zoom_out
+    KeypadMinus zoom_out
+    H toggle_hidden
+    G grid
+    Shift+G snap
+    T theme
+    F1 help
+    Shift+/ help
+";
+
+impl Action {
+    /// Tools are named as in `ToolKind::from_words`, macros being looked up in `macros`
+    pub fn parse(words: &[&str], macros: &[Macro]) -> Result<Action, String> {
+        if words.first() == Some(&"tool") {
+            let tool = &words[1..];
+            return ToolKind::from_words(tool, macros).map(Action::Tool);
+        }
+        match words {
+            [name] => ACTIONS
+                .iter()
+                .find(|(action, _)| action == name)
+                .map(|(_, action)| action.clone())
+                .ok_or_else(|| format!("Unknown action `{}`", name)),
+            _ => Err(format!("Unknown action `{}`", words.join(" "))),
+        }
+    }
+
+    /// The inverse of `parse`
+    pub fn name(&self) -> String {
+        match self {
+            Action::Tool(kind) => format!("tool {}", kind.name()),
+            _ => ACTIONS
+                .iter()
+                .find(|(_, action)| action == self)
+                .map(|(name, _)| name.to_string())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+pub struct Keymap {
+    /// In the order they were bound
+    pub bindings: Vec<(Chord, Action)>,
+}
+
+impl Keymap {
+    pub fn new() -> Keymap {
+        let mut keymap = Keymap {
+            bindings: Vec::new(),
+        };
+        keymap
+            .read(DEFAULT_BINDINGS, &[])
+            .expect("Invalid default key bindings");
+        keymap
+    }
+
+    /// The default bindings, changed by the config file if there is one, which can bind the
+    /// tools of `macros`
+    pub fn load(macros: &[Macro]) -> Result<Keymap, String> {
+        let mut keymap = Keymap::new();
+        if let Some(path) = config_path(CONFIG_FILE) {
+            if let Ok(text) = ::std::fs::read_to_string(&path) {
+                keymap
+                    .read(&text, macros)
+                    .map_err(|err| format!("{}: {}", path.display(), err))?;
+            }
+        }
+        Ok(keymap)
+    }
+
+    /// Reads bindings, replacing the old ones of the same chords
+    pub fn read(&mut self, text: &str, macros: &[Macro]) -> Result<(), String> {
+        for (i, line) in text.lines().enumerate() {
+            let words = format::split_line(line);
+            if words.is_empty() {
+                continue;
+            }
+
+            let err = |err: String| format!("Line {}: {}", i + 1, err);
+            let chord = Chord::parse(words[0]).map_err(err)?;
+            self.bindings.retain(|(bound, _)| *bound != chord);
+            if words[1..] != ["none"] {
+                let action = Action::parse(&words[1..], macros).map_err(err)?;
+                self.bindings.push((chord, action));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn action(&self, chord: Chord) -> Option<&Action> {
+        self.bindings
+            .iter()
+            .find(|(bound, _)| *bound == chord)
+            .map(|(_, action)| action)
+    }
+
+    /// Whether releasing `key` ends an action that lasts while a key is held
+    pub fn releases(&self, key: Keycode, action: &Action) -> bool {
+        self.bindings
+            .iter()
+            .any(|(chord, bound)| chord.key == key && bound == action)
+    }
+
+    /// Every binding as "chord  action", for the help overlay
+    pub fn describe(&self) -> Vec<String> {
+        self.bindings
+            .iter()
+            .map(|(chord, action)| format!("{:<12} {}", chord.name(), action.name()))
+            .collect()
+    }
+}
+
+/// A config file in $XDG_CONFIG_HOME, or in ~/.config
+pub fn config_path(file: &str) -> Option<PathBuf> {
+    let dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(dir.join(file))
+}
+
+#[test]
+fn test_keymap() {
+    let mut keymap = Keymap::new();
+    let chord = |text| Chord::parse(text).unwrap();
+
+    assert_eq!(
+        keymap.action(chord("p")),
+        Some(&Action::Tool(ToolKind::Point))
+    );
+    assert_eq!(keymap.action(chord("shift+g")), Some(&Action::ToggleSnap));
+    assert_eq!(keymap.action(chord("Ctrl+G")), None);
+    assert_eq!(keymap.action(chord("Shift+=")), Some(&Action::ZoomIn));
+
+    keymap
+        .read(
+            "Ctrl+Shift+F5 tool regular 5  # a comment\nP none\n\nSpace fit",
+            &[],
+        )
+        .unwrap();
+    assert_eq!(
+        keymap.action(chord("Shift+Ctrl+F5")),
+        Some(&Action::Tool(ToolKind::RegularPolygon(5)))
+    );
+    assert_eq!(keymap.action(chord("P")), None);
+    assert_eq!(keymap.action(chord("Space")), Some(&Action::Fit));
+    assert!(!keymap.releases(Keycode::Space, &Action::MoveScreen));
+
+    for (chord, action) in &keymap.bindings {
+        assert_eq!(&Chord::parse(&chord.name()).unwrap(), chord);
+        assert_eq!(
+            &Action::parse(&action.name().split(' ').collect::<Vec<_>>(), &[]).unwrap(),
+            action
+        );
+    }
+
+    assert!(keymap.read("Hyper+X fit", &[]).is_err());
+    assert!(keymap.read("X teleport", &[]).is_err());
+    assert!(keymap.read("X tool regular", &[]).is_err());
+    assert!(keymap.read("X tool regular 7", &[]).is_err());
+    assert!(keymap.read("X tool regular 12", &[]).is_ok());
+
+    // Macros are bound by the name they have in the library
+    let library = vec![Macro::new("copy", "input A = point 0 0\n").unwrap()];
+    assert!(keymap.read("X tool macro copy", &[]).is_err());
+    keymap.read("X tool macro copy", &library).unwrap();
+    let action = keymap.action(chord("X")).unwrap();
+    assert_eq!(action, &Action::Tool(ToolKind::Macro(library[0].clone())));
+    assert_eq!(
+        &Action::parse(&action.name().split(' ').collect::<Vec<_>>(), &library).unwrap(),
+        action
+    );
+}
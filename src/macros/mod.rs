@@ -0,0 +1,334 @@
+// Macros, tools made from a part of a construction. A macro is the construction of some output
+// objects from input points, and using it constructs the outputs again from other points. The
+// library file has every macro as construction lines between its name and `end`:
+//
+//     macro midpoint
+//     input A = point 0 0
+//     input B = point 4 0
+//     hidden c1 = circle A B
+//     hidden c2 = circle B A
+//     ...
+//     M = intersection l1 l2
+//     end
+//
+// The inputs are asked for in the order of their `input` lines, and everything else but the
+// outputs is hidden.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+use backend::format::{self, Copied, Reader};
+use backend::geometry::{self, Point, PointID, ThingID};
+use backend::gwrapper::{GWrapper, Visibility};
+use backend::protocol;
+use icons::{self, Icon};
+use keymap;
+
+/// Where the macros are kept, relative to the config directory
+pub const CONFIG_FILE: &str = "drawmetry/macros.txt";
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Macro {
+    /// A single word
+    pub name: String,
+    /// The construction lines
+    pub text: String,
+    pub inputs: usize,
+}
+
+impl Macro {
+    /// Reads the construction lines of a macro
+    pub fn new(name: &str, text: &str) -> Result<Macro, String> {
+        let mut ctx = GWrapper::new(geometry::Geometry::new());
+        let mut reader = Reader::new();
+        for (i, line) in text.lines().enumerate() {
+            reader
+                .read_line(&mut ctx, line)
+                .map_err(|err| format!("Line {} of {}: {}", i + 1, name, err))?;
+        }
+        Ok(Macro {
+            name: name.to_string(),
+            text: text.to_string(),
+            inputs: reader.inputs.len(),
+        })
+    }
+
+    /// Constructs the outputs again from `inputs`, which have to be as many as the macro has.
+    /// Returns the objects made.
+    pub fn apply(&self, ctx: &mut GWrapper, inputs: &[PointID]) -> Result<Vec<ThingID>, String> {
+        if inputs.len() != self.inputs {
+            return Err(format!(
+                "{} takes {} inputs, got {}",
+                self.name,
+                self.inputs,
+                inputs.len()
+            ));
+        }
+        let (made, placeholders) = format::paste(ctx, &self.text)?;
+        // The placeholders are newer than the inputs, so they can be replaced by them
+        for (placeholder, input) in placeholders.iter().zip(inputs) {
+            ctx.replace(ThingID::PointID(*placeholder), ThingID::PointID(*input));
+        }
+        Ok(made
+            .into_iter()
+            .filter(|id| match id {
+                ThingID::PointID(point) => !placeholders.contains(point),
+                _ => true,
+            })
+            .collect())
+    }
+
+    /// The icon of the macro's tool, the figure it was made from
+    pub fn icon(&self) -> Icon {
+        let mut ctx = GWrapper::new(geometry::Geometry::new());
+        let mut reader = Reader::new();
+        for line in self.text.lines() {
+            // It was read when the macro was made
+            let _ = reader.read_line(&mut ctx, line);
+        }
+        let position = |id: &PointID| ctx.points.get(id).and_then(|p| ctx.resolve_point(p));
+
+        let inputs: Vec<_> = reader.inputs.iter().filter_map(&position).collect();
+        let (mut points, mut shapes) = (Vec::new(), Vec::new());
+        for id in &ctx.order {
+            if ctx.visibility.get(id) == Some(&Visibility::Hidden) {
+                continue;
+            }
+            match id {
+                ThingID::PointID(point) if !reader.inputs.contains(point) => {
+                    points.extend(position(point))
+                }
+                ThingID::ShapeID(shape) => {
+                    shapes.extend(ctx.shapes.get(shape).and_then(|s| ctx.resolve_shape(s)))
+                }
+                _ => {}
+            }
+        }
+        icons::figure(&inputs, &points, &shapes)
+    }
+
+    /// Whether the macro only constructs circles, lines and their intersections
+    pub fn uses_circles_and_lines(&self) -> bool {
+        let mut ctx = GWrapper::new(geometry::Geometry::new());
+        let mut reader = Reader::new();
+        for line in self.text.lines() {
+            let _ = reader.read_line(&mut ctx, line);
+        }
+        ctx.points.values().all(|point| {
+            !matches!(
+                point,
+                Point::Transformed(..) | Point::OnCircle(..) | Point::OnLine(..)
+            )
+        }) && ctx
+            .shapes
+            .values()
+            .all(|shape| !matches!(shape, geometry::Shape::Transformed(..)))
+    }
+}
+
+/// A macro constructing `outputs` from `inputs`, like they are constructed in `ctx`
+pub fn capture(
+    ctx: &GWrapper,
+    name: &str,
+    inputs: &HashSet<ThingID>,
+    outputs: &HashSet<ThingID>,
+) -> Result<Macro, String> {
+    let names = protocol::names(ctx);
+    let name_of = |id: &ThingID| names.get(id).cloned().unwrap_or_else(|| "?".to_string());
+    if outputs.is_empty() {
+        return Err("A macro needs outputs".into());
+    }
+
+    let mut things = HashMap::new();
+    for input in inputs {
+        if outputs.contains(input) {
+            return Err(format!("`{}` is an input and an output", name_of(input)));
+        }
+        let position = match input {
+            ThingID::PointID(id) => ctx.points.get(id).and_then(|p| ctx.resolve_point(p)),
+            _ => return Err(format!("`{}` is an input but not a point", name_of(input))),
+        };
+        match position {
+            Some(position) => things.insert(*input, Copied::Input(position)),
+            None => return Err(format!("`{}` doesn't exist", name_of(input))),
+        };
+    }
+
+    let mut sliders = HashSet::new();
+    let mut todo: Vec<ThingID> = outputs.iter().cloned().collect();
+    while let Some(id) = todo.pop() {
+        if things.contains_key(&id) {
+            continue;
+        }
+        if let ThingID::PointID(point) = id {
+            if let Some(Point::Arbitrary(_)) = ctx.points.get(&point) {
+                return Err(format!(
+                    "`{}` isn't constructed from the inputs",
+                    name_of(&id)
+                ));
+            }
+        }
+        let copied = if outputs.contains(&id) {
+            Copied::Whole
+        } else {
+            Copied::Hidden
+        };
+        things.insert(id, copied);
+        let (parents, used) = ctx.dependencies(id);
+        todo.extend(parents);
+        sliders.extend(used);
+    }
+
+    Macro::new(name, &format::write_copied(ctx, &things, &sliders))
+}
+
+/// `prefix` followed by the first number no macro is named with
+pub fn unused_name(macros: &[Macro], prefix: &str) -> String {
+    (1..)
+        .map(|i| format!("{}{}", prefix, i))
+        .find(|name| !macros.iter().any(|m| &m.name == name))
+        .unwrap()
+}
+
+/// Whether `name` can be given to a new macro, being one word no macro has
+pub fn check_name(macros: &[Macro], name: &str) -> Result<(), String> {
+    if format::split_line(name) != [name] {
+        Err(format!("`{}` isn't a single word", name))
+    } else if macros.iter().any(|m| m.name == name) {
+        Err(format!("There is already a macro named {}", name))
+    } else {
+        Ok(())
+    }
+}
+
+/// Reads a library of macros
+pub fn read(text: &str) -> Result<Vec<Macro>, String> {
+    let mut macros = Vec::new();
+    // The name and lines of the macro being read
+    let mut current: Option<(String, String)> = None;
+    for (i, line) in text.lines().enumerate() {
+        let words = format::split_line(line);
+        let err = |msg: &str| Err(format!("Line {}: {}", i + 1, msg));
+        match (words.first(), current.take()) {
+            (Some(&"macro"), None) => match words.len() {
+                2 => current = Some((words[1].to_string(), String::new())),
+                _ => return err("Expected `macro name`"),
+            },
+            (Some(&"macro"), Some(_)) => return err("Expected `end` before the next macro"),
+            (Some(&"end"), Some((name, text))) => macros.push(Macro::new(&name, &text)?),
+            (Some(&"end"), None) => return err("`end` without a macro"),
+            (_, Some((name, mut text))) => {
+                text.push_str(line.trim());
+                text.push('\n');
+                current = Some((name, text));
+            }
+            (None, None) => {}
+            (Some(_), None) => return err("Expected `macro name`"),
+        }
+    }
+    match current {
+        Some((name, _)) => Err(format!("{} has no `end`", name)),
+        None => Ok(macros),
+    }
+}
+
+/// Writes a library so that `read` gives it back
+pub fn write(macros: &[Macro]) -> String {
+    let mut text = String::new();
+    for m in macros {
+        text.push_str(&format!("macro {}\n{}end\n", m.name, m.text));
+    }
+    text
+}
+
+/// The macros in the library file, none if there is no library yet
+pub fn load() -> Result<Vec<Macro>, String> {
+    match keymap::config_path(CONFIG_FILE) {
+        Some(path) => match fs::read_to_string(&path) {
+            Ok(text) => read(&text).map_err(|err| format!("{}: {}", path.display(), err)),
+            Err(_) => Ok(Vec::new()),
+        },
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Writes the library file
+pub fn save(macros: &[Macro]) -> Result<(), String> {
+    let path = keymap::config_path(CONFIG_FILE).ok_or("There is no config directory")?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    }
+    fs::write(&path, write(macros)).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+#[test]
+fn test_macro() {
+    // The midpoint of A and B, with a slider that isn't used
+    let ctx = format::read(
+        "\
+a = slider 0 360 1 0
+A = point 0 0
+B = point 4 0
+c1 = circle A B
+c2 = circle B A
+C = intersection c1 c2
+D = intersection2 c1 c2
+l1 = line C D
+l2 = line A B
+M = intersection l1 l2
+X = point 5 5
+",
+    )
+    .unwrap();
+    let id = |i: usize| ctx.order[i];
+    let set = |ids: &[ThingID]| ids.iter().cloned().collect::<HashSet<_>>();
+    let midpoint = capture(&ctx, "midpoint", &set(&[id(0), id(1)]), &set(&[id(8)])).unwrap();
+    assert_eq!(midpoint.inputs, 2);
+    assert!(midpoint
+        .text
+        .starts_with("input A = point 0 0\ninput B = point 4 0\n"));
+    let last = midpoint.text.lines().last().unwrap();
+    assert!(last.ends_with(" = intersection l1 l2") && !last.starts_with("hidden"));
+    assert!(!midpoint.text.contains("slider"));
+
+    let mut other = format::read("P = point 2 2\nQ = point 2 8").unwrap();
+    let (p, q) = match (other.order[0], other.order[1]) {
+        (ThingID::PointID(p), ThingID::PointID(q)) => (p, q),
+        _ => unreachable!(),
+    };
+    let made = midpoint.apply(&mut other, &[p, q]).unwrap();
+    assert_eq!(made.len(), 7);
+    let m = match made[6] {
+        ThingID::PointID(m) => m,
+        _ => unreachable!(),
+    };
+    let pos = other.resolve_point(&other.points[&m]).unwrap();
+    assert!(geometry::is_same(pos, (2., 5.)));
+    assert_eq!(
+        made.iter()
+            .filter(|id| !other.visibility.contains_key(id))
+            .count(),
+        1
+    );
+    assert!(midpoint.apply(&mut other, &[p]).is_err());
+
+    // X isn't constructed from A
+    assert!(capture(&ctx, "x", &set(&[id(0)]), &set(&[id(9)])).is_err());
+    assert!(capture(&ctx, "x", &set(&[id(2)]), &set(&[id(8)])).is_err());
+    assert!(capture(&ctx, "x", &set(&[id(0), id(1)]), &set(&[])).is_err());
+
+    let library = vec![
+        midpoint.clone(),
+        Macro::new("two", "input A = point 0 0\n").unwrap(),
+    ];
+    assert_eq!(read(&write(&library)).unwrap(), library);
+    assert_eq!(unused_name(&library, "macro"), "macro1");
+    assert!(check_name(&library, "macro1").is_ok());
+    assert!(check_name(&library, "two").is_err());
+    assert!(check_name(&library, "").is_err());
+    assert!(check_name(&library, "a b").is_err());
+    assert!(check_name(&library, "a#b").is_err());
+    assert!(read("macro a\nA = point 0 0\n").is_err());
+    assert!(read("A = point 0 0\n").is_err());
+    assert!(read("macro a b\nend\n").is_err());
+}
@@ -1,4 +1,5 @@
 extern crate ordered_float;
+extern crate png;
 extern crate ytesrev;
 
 #[macro_use]
@@ -12,18 +13,61 @@ mod backend;
 mod drawing_board;
 mod graphics;
 mod icons;
+mod keymap;
+mod macros;
+mod recorder;
+mod theme;
 mod tool;
 mod toolbar;
 mod transform;
 
-use backend::{geometry, gwrapper};
+use std::path::{Path, PathBuf};
+use std::{env, fs, process};
+
+use backend::{format, geometry, gwrapper, puzzle};
 use ytesrev::prelude::*;
 use ytesrev::sdl2::event::Event;
 use ytesrev::window::{WindowSettings, WSETTINGS_MAIN};
 
 fn main() {
-    let world = gwrapper::GWrapper::new(geometry::Geometry::new());
-    let all = graphics::create_layout(world);
+    // Every saved construction given is opened in a tab, and levels are played as puzzles
+    let mut documents = Vec::new();
+    for path in env::args().skip(1) {
+        match open(&path) {
+            Ok(document) => documents.push(document),
+            Err(err) => {
+                eprintln!("Can't load {}: {}", path, err);
+                process::exit(1);
+            }
+        }
+    }
+    if documents.is_empty() {
+        documents.push(graphics::Document::new(
+            drawing_board::UNTITLED.to_string(),
+            None,
+            gwrapper::GWrapper::new(geometry::Geometry::new()),
+            None,
+        ));
+    }
+    // Shown once the window is open
+    let mut errors = Vec::new();
+    // Loaded first, since keys can be bound to macro tools
+    let macros = macros::load().unwrap_or_else(|err| {
+        errors.push(format!("Can't load the macros: {}", err));
+        Vec::new()
+    });
+    let keymap = keymap::Keymap::load(&macros).unwrap_or_else(|err| {
+        errors.push(format!("Can't load the key bindings: {}", err));
+        keymap::Keymap::new()
+    });
+    let themes = theme::load().unwrap_or_else(|err| {
+        errors.push(format!("Can't load the theme: {}", err));
+        vec![theme::LIGHT, theme::DARK]
+    });
+    let all = graphics::create_layout(documents, keymap, themes, macros);
+    if !errors.is_empty() {
+        all.message(errors.join(". "));
+    }
 
     let mut manager = WindowManager::init_window(
         all,
@@ -48,3 +92,23 @@ fn main() {
 
     manager.start();
 }
+
+/// Reads a saved construction, which is saved back to the same file, or a level
+fn open(path: &str) -> Result<graphics::Document, String> {
+    let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let name = Path::new(path)
+        .file_name()
+        .map_or(path.to_string(), |name| name.to_string_lossy().into_owned());
+    if puzzle::is_level(&text) {
+        let (world, puzzle) = puzzle::load(&text)?;
+        Ok(graphics::Document::new(name, None, world, Some(puzzle)))
+    } else {
+        let world = format::read(&text)?;
+        Ok(graphics::Document::new(
+            name,
+            Some(PathBuf::from(path)),
+            world,
+            None,
+        ))
+    }
+}
@@ -0,0 +1,109 @@
+// Records the drawing board to a numbered sequence of PNG images. Frames are taken at a fixed
+// rate, repeating or skipping drawn frames as needed, so the recording plays back at the right
+// speed whatever the frame rate of the window was.
+
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+use png;
+use ytesrev::prelude::*;
+use ytesrev::sdl2::pixels::PixelFormatEnum;
+
+/// Frames per second of the recording
+pub const FRAME_RATE: f64 = 25.;
+/// Recordings go to recording1, recording2, ...
+pub const RECORDING_DIR: &str = "recording";
+
+/// A frame to write, ((width, height), RGB pixels)
+type Frame = ((u32, u32), Vec<u8>);
+
+pub struct Recorder {
+    pub dir: PathBuf,
+    pub frames: usize,
+    /// How many frames are due since the last capture
+    due: f64,
+    /// Frames are encoded on another thread, so drawing isn't slowed down
+    send: Sender<(PathBuf, Frame)>,
+    /// What went wrong writing frames on the other thread
+    errors: Receiver<String>,
+}
+
+impl Recorder {
+    /// Starts recording to a new directory
+    pub fn start() -> Result<Recorder, String> {
+        let dir = (1..)
+            .map(|i| PathBuf::from(format!("{}{}", RECORDING_DIR, i)))
+            .find(|dir| !dir.exists())
+            .unwrap();
+        fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+
+        let (send, recv) = channel::<(PathBuf, Frame)>();
+        let (send_error, errors) = channel();
+        thread::spawn(move || {
+            for (path, frame) in recv {
+                if let Err(err) = write_png(&path, frame) {
+                    let _ = send_error.send(format!("Can't write {}: {}", path.display(), err));
+                }
+            }
+        });
+
+        Ok(Recorder {
+            dir,
+            frames: 0,
+            // The first frame is taken right away
+            due: 1.,
+            send,
+            errors,
+        })
+    }
+
+    /// The next error from writing the frames, if there is one
+    pub fn error(&self) -> Option<String> {
+        self.errors.try_recv().ok()
+    }
+
+    /// Lets `dt` seconds pass
+    pub fn tick(&mut self, dt: f64) {
+        self.due += dt * FRAME_RATE;
+    }
+
+    /// Takes the frames that are due from what's drawn in `rect`
+    pub fn capture(&mut self, canvas: &Canvas<Window>, rect: Rect) -> Result<(), String> {
+        let count = self.due.floor() as usize;
+        if count == 0 {
+            return Ok(());
+        }
+        self.due -= count as f64;
+
+        // `rect` is in window units, which the canvas scales to pixels
+        let (sx, sy) = canvas.scale();
+        let rect = Rect::new(
+            (rect.x() as f32 * sx) as i32,
+            (rect.y() as f32 * sy) as i32,
+            (rect.width() as f32 * sx) as u32,
+            (rect.height() as f32 * sy) as u32,
+        );
+        let pixels = canvas.read_pixels(rect, PixelFormatEnum::RGB24)?;
+        for _ in 0..count {
+            let path = self.dir.join(format!("frame{:05}.png", self.frames));
+            self.send
+                .send((path, ((rect.width(), rect.height()), pixels.clone())))
+                .map_err(|err| err.to_string())?;
+            self.frames += 1;
+        }
+        Ok(())
+    }
+}
+
+fn write_png(path: &Path, ((width, height), pixels): Frame) -> Result<(), String> {
+    let file = File::create(path).map_err(|err| err.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set(png::ColorType::RGB).set(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|err| err.to_string())?;
+    writer
+        .write_image_data(&pixels)
+        .map_err(|err| err.to_string())
+}
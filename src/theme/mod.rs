@@ -0,0 +1,381 @@
+// The colors of the whole window. There is a light and a dark theme built in, and a custom theme
+// can be read from a file, where every line sets one color:
+//
+//     base dark                   start from a built in theme, light if left out
+//     background 20 20 30         red, green and blue
+//     label_background 0 0 0 180  and optionally opacity
+//     hidden_alpha 90             how opaque hidden objects are, 0 to 255
+//
+// The names of the colors are the fields of Theme. Everything after a # is a comment.
+
+use std::fs;
+
+use backend::format;
+use keymap;
+use ytesrev::prelude::*;
+
+/// Where a custom theme is read from, relative to the config directory
+pub const CONFIG_FILE: &str = "drawmetry/theme.txt";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Theme {
+    pub background: Color,
+    /// Objects in the default color, text, icons and the slider panel
+    pub foreground: Color,
+    pub label_background: Color,
+    /// Steps after the current one while replaying
+    pub later_step: Color,
+    pub grid: Color,
+    pub axis: Color,
+    /// The ring around what a click would pick
+    pub hover: Color,
+    pub measurement: Color,
+    pub puzzle: Color,
+    pub solved: Color,
+    pub toolbar: Color,
+    pub toolbar_edge: Color,
+    pub tool_selected: Color,
+    pub tool_selected_edge: Color,
+    /// Selected objects
+    pub primary: Color,
+    /// Objects a tool is working with
+    pub active: Color,
+    pub hidden_alpha: u8,
+    /// Objects a click would make
+    pub ghost_alpha: u8,
+    pub trail_alpha: u8,
+}
+
+pub const LIGHT: Theme = Theme {
+    background: Color {
+        r: 255,
+        g: 255,
+        b: 255,
+        a: 255,
+    },
+    foreground: Color {
+        r: 0,
+        g: 0,
+        b: 0,
+        a: 255,
+    },
+    label_background: Color {
+        r: 255,
+        g: 255,
+        b: 255,
+        a: 200,
+    },
+    later_step: Color {
+        r: 150,
+        g: 150,
+        b: 150,
+        a: 255,
+    },
+    grid: Color {
+        r: 225,
+        g: 225,
+        b: 225,
+        a: 255,
+    },
+    axis: Color {
+        r: 120,
+        g: 120,
+        b: 120,
+        a: 255,
+    },
+    hover: Color {
+        r: 255,
+        g: 140,
+        b: 0,
+        a: 255,
+    },
+    measurement: Color {
+        r: 150,
+        g: 40,
+        b: 40,
+        a: 255,
+    },
+    puzzle: Color {
+        r: 0,
+        g: 0,
+        b: 0,
+        a: 255,
+    },
+    solved: Color {
+        r: 30,
+        g: 130,
+        b: 30,
+        a: 255,
+    },
+    toolbar: Color {
+        r: 162,
+        g: 184,
+        b: 219,
+        a: 255,
+    },
+    toolbar_edge: Color {
+        r: 38,
+        g: 62,
+        b: 99,
+        a: 255,
+    },
+    tool_selected: Color {
+        r: 245,
+        g: 230,
+        b: 230,
+        a: 255,
+    },
+    tool_selected_edge: Color {
+        r: 140,
+        g: 120,
+        b: 100,
+        a: 255,
+    },
+    primary: Color {
+        r: 0,
+        g: 255,
+        b: 0,
+        a: 255,
+    },
+    active: Color {
+        r: 128,
+        g: 195,
+        b: 255,
+        a: 255,
+    },
+    hidden_alpha: 64,
+    ghost_alpha: 90,
+    trail_alpha: 40,
+};
+
+pub const DARK: Theme = Theme {
+    background: Color {
+        r: 30,
+        g: 32,
+        b: 38,
+        a: 255,
+    },
+    foreground: Color {
+        r: 230,
+        g: 230,
+        b: 230,
+        a: 255,
+    },
+    label_background: Color {
+        r: 30,
+        g: 32,
+        b: 38,
+        a: 200,
+    },
+    later_step: Color {
+        r: 110,
+        g: 110,
+        b: 115,
+        a: 255,
+    },
+    grid: Color {
+        r: 50,
+        g: 53,
+        b: 60,
+        a: 255,
+    },
+    axis: Color {
+        r: 130,
+        g: 130,
+        b: 140,
+        a: 255,
+    },
+    hover: Color {
+        r: 255,
+        g: 160,
+        b: 40,
+        a: 255,
+    },
+    measurement: Color {
+        r: 240,
+        g: 110,
+        b: 110,
+        a: 255,
+    },
+    puzzle: Color {
+        r: 230,
+        g: 230,
+        b: 230,
+        a: 255,
+    },
+    solved: Color {
+        r: 110,
+        g: 210,
+        b: 110,
+        a: 255,
+    },
+    toolbar: Color {
+        r: 52,
+        g: 56,
+        b: 66,
+        a: 255,
+    },
+    toolbar_edge: Color {
+        r: 20,
+        g: 22,
+        b: 28,
+        a: 255,
+    },
+    tool_selected: Color {
+        r: 84,
+        g: 90,
+        b: 106,
+        a: 255,
+    },
+    tool_selected_edge: Color {
+        r: 255,
+        g: 160,
+        b: 40,
+        a: 255,
+    },
+    primary: Color {
+        r: 60,
+        g: 230,
+        b: 90,
+        a: 255,
+    },
+    active: Color {
+        r: 100,
+        g: 170,
+        b: 255,
+        a: 255,
+    },
+    hidden_alpha: 80,
+    ghost_alpha: 110,
+    trail_alpha: 60,
+};
+
+impl Theme {
+    /// Reads a custom theme
+    pub fn read(text: &str) -> Result<Theme, String> {
+        let mut theme = LIGHT;
+        for (i, line) in text.lines().enumerate() {
+            let words = format::split_line(line);
+            if words.is_empty() {
+                continue;
+            }
+            theme
+                .read_line(&words)
+                .map_err(|err| format!("Line {}: {}", i + 1, err))?;
+        }
+        Ok(theme)
+    }
+
+    fn read_line(&mut self, words: &[&str]) -> Result<(), String> {
+        let numbers = words[1..]
+            .iter()
+            .map(|word| {
+                word.parse::<u8>()
+                    .map_err(|_| format!("Invalid number `{}`", word))
+            })
+            .collect::<Result<Vec<u8>, String>>();
+
+        if words[0] == "base" {
+            *self = match words.get(1) {
+                Some(&"light") if words.len() == 2 => LIGHT,
+                Some(&"dark") if words.len() == 2 => DARK,
+                _ => return Err("Expected `base light` or `base dark`".into()),
+            };
+            return Ok(());
+        }
+        if let Some(alpha) = self.alpha_mut(words[0]) {
+            return match &numbers?[..] {
+                [a] => {
+                    *alpha = *a;
+                    Ok(())
+                }
+                _ => Err(format!("`{}` takes one number", words[0])),
+            };
+        }
+        match self.color_mut(words[0]) {
+            Some(color) => {
+                *color = match &numbers?[..] {
+                    [r, g, b] => Color::RGB(*r, *g, *b),
+                    [r, g, b, a] => Color::RGBA(*r, *g, *b, *a),
+                    _ => return Err(format!("`{}` takes 3 or 4 numbers", words[0])),
+                };
+                Ok(())
+            }
+            None => Err(format!("Unknown color `{}`", words[0])),
+        }
+    }
+
+    fn color_mut(&mut self, name: &str) -> Option<&mut Color> {
+        Some(match name {
+            "background" => &mut self.background,
+            "foreground" => &mut self.foreground,
+            "label_background" => &mut self.label_background,
+            "later_step" => &mut self.later_step,
+            "grid" => &mut self.grid,
+            "axis" => &mut self.axis,
+            "hover" => &mut self.hover,
+            "measurement" => &mut self.measurement,
+            "puzzle" => &mut self.puzzle,
+            "solved" => &mut self.solved,
+            "toolbar" => &mut self.toolbar,
+            "toolbar_edge" => &mut self.toolbar_edge,
+            "tool_selected" => &mut self.tool_selected,
+            "tool_selected_edge" => &mut self.tool_selected_edge,
+            "primary" => &mut self.primary,
+            "active" => &mut self.active,
+            _ => return None,
+        })
+    }
+
+    fn alpha_mut(&mut self, name: &str) -> Option<&mut u8> {
+        Some(match name {
+            "hidden_alpha" => &mut self.hidden_alpha,
+            "ghost_alpha" => &mut self.ghost_alpha,
+            "trail_alpha" => &mut self.trail_alpha,
+            _ => return None,
+        })
+    }
+
+    /// The color objects are drawn in. Objects without a color of their own follow the theme, so
+    /// they show on dark backgrounds.
+    pub fn ink(&self, color: Option<(u8, u8, u8)>, alpha: u8) -> Color {
+        let (r, g, b) = color.unwrap_or((self.foreground.r, self.foreground.g, self.foreground.b));
+        Color::RGBA(r, g, b, alpha)
+    }
+}
+
+/// The built in themes, followed by the custom theme if there is one
+pub fn load() -> Result<Vec<Theme>, String> {
+    let mut themes = vec![LIGHT, DARK];
+    if let Some(path) = keymap::config_path(CONFIG_FILE) {
+        if let Ok(text) = fs::read_to_string(&path) {
+            themes.push(Theme::read(&text).map_err(|err| format!("{}: {}", path.display(), err))?);
+        }
+    }
+    Ok(themes)
+}
+
+#[test]
+fn test_theme() {
+    assert_eq!(Theme::read("# nothing\n").unwrap(), LIGHT);
+
+    let theme =
+        Theme::read("base dark\nbackground 1 2 3\nhover 4 5 6 7 # ok\nhidden_alpha 9").unwrap();
+    assert_eq!(theme.background, Color::RGB(1, 2, 3));
+    assert_eq!(theme.hover, Color::RGBA(4, 5, 6, 7));
+    assert_eq!(theme.hidden_alpha, 9);
+    assert_eq!(theme.foreground, DARK.foreground);
+
+    assert_eq!(theme.ink(None, 10), Color::RGBA(230, 230, 230, 10));
+    assert_eq!(theme.ink(Some((0, 0, 0)), 10), Color::RGBA(0, 0, 0, 10));
+    assert_eq!(
+        theme.ink(Some((200, 40, 40)), 10),
+        Color::RGBA(200, 40, 40, 10)
+    );
+
+    assert!(Theme::read("background 1 2").is_err());
+    assert!(Theme::read("background 1 2 300").is_err());
+    assert!(Theme::read("sparkle 1 2 3").is_err());
+    assert!(Theme::read("hidden_alpha 1 2").is_err());
+    assert!(Theme::read("base sepia").is_err());
+}
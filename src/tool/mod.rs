@@ -1,13 +1,46 @@
 pub mod tools;
 
-use backend::gwrapper;
-use drawing_board::View;
+use backend::{constructions, geometry, gwrapper};
+use drawing_board::{Asker, View};
+use macros::Macro;
 use std::collections::{HashMap, HashSet};
 
+/// The factor the dilation tool suggests at first
+pub const DEFAULT_DILATION: f64 = 2.;
+/// (min, max, step) of the sliders made by the slider dilation tool
+pub const DILATION_SLIDER: (f64, f64, f64) = (-3., 3., 0.1);
+/// How far the slider of a point attached to a line reaches past where the point is, in
+/// lengths between the points the line is through
+pub const LINE_SLIDER_SPAN: f64 = 5.;
+
 pub trait Tool: Send {
     fn click(&mut self, ctx: &mut gwrapper::GWrapper, view: &mut View, at: (f64, f64));
     fn selected(&self, ctx: &gwrapper::GWrapper) -> HashMap<gwrapper::ThingID, SelectedStatus>;
     fn kind(&self) -> ToolKind;
+    /// What a click at `at` would pick and make, shown while the mouse moves
+    fn hover(&self, ctx: &gwrapper::GWrapper, view: &View, at: (f64, f64)) -> Hover;
+    /// Takes the text typed in for the prompt the tool put up
+    fn answer(&mut self, _ctx: &mut gwrapper::GWrapper, _view: &mut View, _text: &str) {}
+}
+
+/// Feedback about what a click where the mouse is would do
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Hover {
+    /// The existing object the click would pick
+    pub target: Option<gwrapper::ThingID>,
+    /// Where a new point would be
+    pub point: Option<(f64, f64)>,
+    /// The shape that would be made
+    pub shape: Option<geometry::ResolvedShape>,
+}
+
+impl Hover {
+    pub fn target(target: Option<gwrapper::ThingID>) -> Hover {
+        Hover {
+            target,
+            ..Hover::default()
+        }
+    }
 }
 
 #[derive(Clone, PartialEq)]
@@ -16,23 +49,189 @@ pub enum SelectedStatus {
     Active,
 }
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Debug)]
 pub enum ToolKind {
     Point,
     Circle,
     Line,
     Mover,
     Selector,
+    Reflect,
+    Rotate,
+    Translate,
+    Dilate,
+    /// Dilation by a new slider
+    DilateBySlider,
+    Invert,
+    Distance,
+    Angle,
+    Radius,
+    Area,
+    Perimeter,
+    Polygon,
+    /// A regular polygon with the given number of sides
+    RegularPolygon(usize),
+    Locus,
+    /// A point on a circle, at the angle of a new slider
+    OnCircle,
+    /// Replaces something an object is defined from
+    Redefine,
+    /// Attaches a free point to what's under it, or frees a constructed point
+    Attach,
+    Macro(Macro),
 }
 
 impl ToolKind {
+    /// The tool named by `name`, e.g. "point", "regular 5" for a regular polygon, or "macro
+    /// midpoint" for a macro in `macros`
+    pub fn from_words(words: &[&str], macros: &[Macro]) -> Result<ToolKind, String> {
+        let kind = match words {
+            ["point"] => ToolKind::Point,
+            ["circle"] => ToolKind::Circle,
+            ["line"] => ToolKind::Line,
+            ["mover"] => ToolKind::Mover,
+            ["selector"] => ToolKind::Selector,
+            ["reflect"] => ToolKind::Reflect,
+            ["rotate"] => ToolKind::Rotate,
+            ["translate"] => ToolKind::Translate,
+            ["dilate"] => ToolKind::Dilate,
+            ["dilate_slider"] => ToolKind::DilateBySlider,
+            ["invert"] => ToolKind::Invert,
+            ["distance"] => ToolKind::Distance,
+            ["angle"] => ToolKind::Angle,
+            ["radius"] => ToolKind::Radius,
+            ["area"] => ToolKind::Area,
+            ["perimeter"] => ToolKind::Perimeter,
+            ["polygon"] => ToolKind::Polygon,
+            ["regular", sides] => match sides.parse() {
+                Ok(sides) if constructions::is_supported(sides) => ToolKind::RegularPolygon(sides),
+                Ok(_) => return Err(format!("A regular {}-gon can't be constructed", sides)),
+                Err(_) => return Err(format!("`{}` isn't a number of sides", sides)),
+            },
+            ["locus"] => ToolKind::Locus,
+            ["on_circle"] => ToolKind::OnCircle,
+            ["redefine"] => ToolKind::Redefine,
+            ["attach"] => ToolKind::Attach,
+            ["macro", name] => match macros.iter().find(|m| m.name == *name) {
+                Some(definition) => ToolKind::Macro(definition.clone()),
+                None => return Err(format!("Unknown macro `{}`", name)),
+            },
+            _ => return Err(format!("Unknown tool `{}`", words.join(" "))),
+        };
+        Ok(kind)
+    }
+
+    /// The inverse of `from_words`
+    pub fn name(&self) -> String {
+        match self {
+            ToolKind::Point => "point",
+            ToolKind::Circle => "circle",
+            ToolKind::Line => "line",
+            ToolKind::Mover => "mover",
+            ToolKind::Selector => "selector",
+            ToolKind::Reflect => "reflect",
+            ToolKind::Rotate => "rotate",
+            ToolKind::Translate => "translate",
+            ToolKind::Dilate => "dilate",
+            ToolKind::DilateBySlider => "dilate_slider",
+            ToolKind::Invert => "invert",
+            ToolKind::Distance => "distance",
+            ToolKind::Angle => "angle",
+            ToolKind::Radius => "radius",
+            ToolKind::Area => "area",
+            ToolKind::Perimeter => "perimeter",
+            ToolKind::Polygon => "polygon",
+            ToolKind::RegularPolygon(sides) => return format!("regular {}", sides),
+            ToolKind::Locus => "locus",
+            ToolKind::OnCircle => "on_circle",
+            ToolKind::Redefine => "redefine",
+            ToolKind::Attach => "attach",
+            ToolKind::Macro(definition) => return format!("macro {}", definition.name),
+        }
+        .to_string()
+    }
+
+    /// Whether the tool can be used in puzzles, where only circles and lines count as moves.
+    /// Transformations, points on circles and changing what objects are constructed from
+    /// would make objects without any moves.
+    pub fn in_puzzles(&self) -> bool {
+        match self {
+            ToolKind::Reflect
+            | ToolKind::Rotate
+            | ToolKind::Translate
+            | ToolKind::Dilate
+            | ToolKind::DilateBySlider
+            | ToolKind::Invert
+            | ToolKind::OnCircle
+            | ToolKind::Redefine
+            | ToolKind::Attach => false,
+            ToolKind::Macro(definition) => definition.uses_circles_and_lines(),
+            _ => true,
+        }
+    }
+
     pub fn into_tool(self) -> Box<dyn Tool> {
         match self {
             ToolKind::Point => Box::new(tools::PointTool {}),
             ToolKind::Circle => Box::new(tools::CircleTool { center: None }),
             ToolKind::Line => Box::new(tools::LineTool { edge: None }),
             ToolKind::Mover => Box::new(tools::MoverTool { moving: None }),
-            ToolKind::Selector => Box::new(tools::Selector { selected: HashSet::new() }),
+            ToolKind::Selector => Box::new(tools::Selector {
+                selected: HashSet::new(),
+            }),
+            ToolKind::Reflect => Box::new(tools::ReflectTool { object: None }),
+            ToolKind::Rotate => Box::new(tools::RotateTool {
+                object: None,
+                points: Vec::new(),
+            }),
+            ToolKind::Translate => Box::new(tools::TranslateTool {
+                object: None,
+                from: None,
+            }),
+            ToolKind::Dilate => Box::new(tools::DilateTool {
+                object: None,
+                center: None,
+                factor: DEFAULT_DILATION,
+                by_slider: false,
+            }),
+            ToolKind::DilateBySlider => Box::new(tools::DilateTool {
+                object: None,
+                center: None,
+                factor: DEFAULT_DILATION,
+                by_slider: true,
+            }),
+            ToolKind::Invert => Box::new(tools::InvertTool {
+                objects: Vec::new(),
+            }),
+            ToolKind::Redefine => Box::new(tools::RedefineTool {
+                object: None,
+                old: None,
+            }),
+            ToolKind::Attach => Box::new(tools::AttachTool {}),
+            ToolKind::Distance => Box::new(tools::DistanceTool { points: Vec::new() }),
+            ToolKind::Angle => Box::new(tools::AngleTool { points: Vec::new() }),
+            ToolKind::Radius => Box::new(tools::RadiusTool {}),
+            ToolKind::Area => Box::new(tools::AreaTool {
+                vertices: Vec::new(),
+            }),
+            ToolKind::Perimeter => Box::new(tools::PerimeterTool {
+                vertices: Vec::new(),
+            }),
+            ToolKind::Polygon => Box::new(tools::PolygonTool {
+                vertices: Vec::new(),
+            }),
+            ToolKind::RegularPolygon(sides) => {
+                Box::new(tools::RegularPolygonTool { sides, first: None })
+            }
+            ToolKind::Locus => Box::new(tools::LocusTool {
+                traced: None,
+                driver: None,
+            }),
+            ToolKind::OnCircle => Box::new(tools::OnCircleTool {}),
+            ToolKind::Macro(definition) => Box::new(tools::MacroTool {
+                definition,
+                inputs: Vec::new(),
+            }),
         }
     }
 }
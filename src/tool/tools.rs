@@ -1,23 +1,19 @@
 use std::collections::HashSet;
 use std::f64;
+use std::mem;
 
 use super::*;
 
-use backend::{geometry, gwrapper};
+use backend::{constructions, geometry, gwrapper, protocol};
 use graphics::{get_best, get_closest};
+use macros::Macro;
 use std::collections::HashMap;
 
 pub struct PointTool {}
 
 impl Tool for PointTool {
     fn click(&mut self, ctx: &mut gwrapper::GWrapper, view: &mut View, at: (f64, f64)) {
-        let point = get_closest(
-            at,
-            ctx.geometry.get_potential_points(),
-            |point| ctx.geometry.resolve_point(point).unwrap_or((0., 0.)),
-            Some(100. / view.transform.scale),
-        ).unwrap_or(geometry::create_arbitrary(at));
-
+        let point = new_point(ctx, view, at);
         ctx.geometry.add_point(point);
     }
     fn selected(&self, _ctx: &gwrapper::GWrapper) -> HashMap<gwrapper::ThingID, SelectedStatus> {
@@ -26,6 +22,28 @@ impl Tool for PointTool {
     fn kind(&self) -> ToolKind {
         ToolKind::Point
     }
+    fn hover(&self, ctx: &gwrapper::GWrapper, view: &View, at: (f64, f64)) -> Hover {
+        Hover {
+            point: ctx.geometry.resolve_point(&new_point(ctx, view, at)),
+            ..Hover::default()
+        }
+    }
+}
+
+/// A potential intersection close to `at`, otherwise a free point at `at`
+fn new_point(ctx: &gwrapper::GWrapper, view: &View, at: (f64, f64)) -> geometry::Point {
+    let free = if view.snap_to_grid {
+        view.transform.snap_to_grid(at)
+    } else {
+        at
+    };
+    get_closest(
+        at,
+        ctx.geometry.get_potential_points(),
+        |point| ctx.geometry.resolve_point(point).unwrap_or((0., 0.)),
+        Some(100. / view.transform.scale),
+    )
+    .unwrap_or(geometry::create_arbitrary(free))
 }
 
 pub struct CircleTool {
@@ -34,12 +52,7 @@ pub struct CircleTool {
 
 impl Tool for CircleTool {
     fn click(&mut self, ctx: &mut gwrapper::GWrapper, view: &mut View, at: (f64, f64)) {
-        if let Some((&id, _)) = get_closest(
-            at,
-            ctx.geometry.points.iter().collect(),
-            |(_, point)| ctx.geometry.resolve_point(point).unwrap_or((0., 0.)),
-            Some(100. / view.transform.scale),
-        ) {
+        if let Some(id) = closest_point(ctx, view, at) {
             if let Some(center) = self.center {
                 ctx.geometry.add_shape(geometry::Shape::Circle(center, id));
                 self.center = None;
@@ -58,6 +71,21 @@ impl Tool for CircleTool {
     fn kind(&self) -> ToolKind {
         ToolKind::Circle
     }
+    fn hover(&self, ctx: &gwrapper::GWrapper, view: &View, at: (f64, f64)) -> Hover {
+        let target = closest_point(ctx, view, at);
+        let shape = match (self.center.and_then(|id| point_pos(ctx, id)), target) {
+            (Some(center), Some(id)) => point_pos(ctx, id).map(|through| {
+                let rad = ((through.0 - center.0).powi(2) + (through.1 - center.1).powi(2)).sqrt();
+                geometry::ResolvedShape::Circle(center, rad)
+            }),
+            _ => None,
+        };
+        Hover {
+            target: target.map(gwrapper::ThingID::PointID),
+            shape,
+            ..Hover::default()
+        }
+    }
 }
 
 pub struct LineTool {
@@ -66,12 +94,7 @@ pub struct LineTool {
 
 impl Tool for LineTool {
     fn click(&mut self, ctx: &mut gwrapper::GWrapper, view: &mut View, at: (f64, f64)) {
-        if let Some((&id, _)) = get_closest(
-            at,
-            ctx.geometry.points.iter().collect(),
-            |(_, point)| ctx.geometry.resolve_point(point).unwrap_or((0., 0.)),
-            Some(100. / view.transform.scale),
-        ) {
+        if let Some(id) = closest_point(ctx, view, at) {
             if let Some(edge) = self.edge {
                 ctx.geometry.add_shape(geometry::Shape::Line(edge, id));
                 self.edge = None;
@@ -90,6 +113,20 @@ impl Tool for LineTool {
     fn kind(&self) -> ToolKind {
         ToolKind::Line
     }
+    fn hover(&self, ctx: &gwrapper::GWrapper, view: &View, at: (f64, f64)) -> Hover {
+        let target = closest_point(ctx, view, at);
+        let shape = match (self.edge.and_then(|id| point_pos(ctx, id)), target) {
+            (Some(edge), Some(id)) => point_pos(ctx, id)
+                .filter(|pos| *pos != edge)
+                .map(|pos| geometry::line_through(edge, pos)),
+            _ => None,
+        };
+        Hover {
+            target: target.map(gwrapper::ThingID::PointID),
+            shape,
+            ..Hover::default()
+        }
+    }
 }
 
 pub struct MoverTool {
@@ -98,21 +135,7 @@ pub struct MoverTool {
 
 impl Tool for MoverTool {
     fn click(&mut self, ctx: &mut gwrapper::GWrapper, _view: &mut View, at: (f64, f64)) {
-        if let Some((&id, _)) = get_closest(
-            at,
-            ctx.geometry
-                .points
-                .iter()
-                .filter(|(_, point)| {
-                    if let geometry::Point::Arbitrary(_) = point {
-                        true
-                    } else {
-                        false
-                    }
-                }).collect(),
-            |(_, point)| ctx.geometry.resolve_point(point).unwrap_or((0., 0.)),
-            None,
-        ) {
+        if let Some(id) = closest_free_point(ctx, at) {
             self.moving = Some(id);
         }
     }
@@ -132,6 +155,12 @@ impl Tool for MoverTool {
     fn kind(&self) -> ToolKind {
         ToolKind::Mover
     }
+    fn hover(&self, ctx: &gwrapper::GWrapper, _view: &View, at: (f64, f64)) -> Hover {
+        match self.moving {
+            Some(_) => Hover::default(),
+            None => Hover::target(closest_free_point(ctx, at).map(gwrapper::ThingID::PointID)),
+        }
+    }
 }
 
 pub struct Selector {
@@ -140,75 +169,1277 @@ pub struct Selector {
 
 impl Tool for Selector {
     fn click(&mut self, ctx: &mut gwrapper::GWrapper, view: &mut View, at: (f64, f64)) {
-        let mut objects = Vec::<(gwrapper::ThingID, gwrapper::Thing)>::new();
+        if let Some(id) = closest_thing(ctx, view, at) {
+            if self.selected.contains(&id) {
+                self.selected.remove(&id);
+            } else {
+                self.selected.insert(id);
+            }
+        }
+    }
+    fn selected(&self, _ctx: &gwrapper::GWrapper) -> HashMap<gwrapper::ThingID, SelectedStatus> {
+        let mut res = HashMap::new();
 
-        for (id, point) in &ctx.geometry.points {
-            objects.push((
-                gwrapper::ThingID::PointID(*id),
-                gwrapper::Thing::Point(*point),
-            ));
+        for x in &self.selected {
+            res.insert(*x, SelectedStatus::Primary);
+        }
+
+        res
+    }
+    fn kind(&self) -> ToolKind {
+        ToolKind::Selector
+    }
+    fn hover(&self, ctx: &gwrapper::GWrapper, view: &View, at: (f64, f64)) -> Hover {
+        Hover::target(closest_thing(ctx, view, at))
+    }
+}
+
+/// Selects pasted objects, after going through their inputs. Clicking an older point attaches
+/// the next input to it, clicking anywhere else leaves it free.
+pub struct Rebinder {
+    /// The inputs still to attach, the next one first
+    pub inputs: Vec<geometry::PointID>,
+    pub selector: Selector,
+}
+
+impl Tool for Rebinder {
+    fn click(&mut self, ctx: &mut gwrapper::GWrapper, view: &mut View, at: (f64, f64)) {
+        if self.inputs.is_empty() {
+            self.selector.click(ctx, view, at);
+            return;
+        }
+        let input = self.inputs.remove(0);
+        if let Some(target) = rebind_target(ctx, view, input, at) {
+            ctx.replace(
+                gwrapper::ThingID::PointID(input),
+                gwrapper::ThingID::PointID(target),
+            );
+            self.selector
+                .selected
+                .remove(&gwrapper::ThingID::PointID(input));
+        }
+    }
+    fn selected(&self, ctx: &gwrapper::GWrapper) -> HashMap<gwrapper::ThingID, SelectedStatus> {
+        let mut res = self.selector.selected(ctx);
+        if let Some(input) = self.inputs.first() {
+            res.insert(gwrapper::ThingID::PointID(*input), SelectedStatus::Active);
+        }
+        res
+    }
+    // Starting over leaves the remaining inputs free
+    fn kind(&self) -> ToolKind {
+        ToolKind::Selector
+    }
+    fn hover(&self, ctx: &gwrapper::GWrapper, view: &View, at: (f64, f64)) -> Hover {
+        match self.inputs.first() {
+            Some(input) => {
+                Hover::target(rebind_target(ctx, view, *input, at).map(gwrapper::ThingID::PointID))
+            }
+            None => self.selector.hover(ctx, view, at),
+        }
+    }
+}
+
+/// The point at `at` that `input` can be attached to. It has to be older than the input, so it
+/// can't be constructed from it.
+fn rebind_target(
+    ctx: &gwrapper::GWrapper,
+    view: &View,
+    input: geometry::PointID,
+    at: (f64, f64),
+) -> Option<geometry::PointID> {
+    let age = |id| {
+        ctx.order
+            .iter()
+            .position(|x| *x == gwrapper::ThingID::PointID(id))
+    };
+    closest_point(ctx, view, at).filter(|target| age(*target) < age(input))
+}
+
+pub struct ReflectTool {
+    pub object: Option<gwrapper::ThingID>,
+}
+
+impl Tool for ReflectTool {
+    fn click(&mut self, ctx: &mut gwrapper::GWrapper, view: &mut View, at: (f64, f64)) {
+        if let Some(object) = self.object {
+            if let Some(line) = closest_line(ctx, view, at) {
+                ctx.add_transformed(object, geometry::Transformation::Reflect(line));
+                self.object = None;
+            }
+        } else {
+            self.object = closest_thing(ctx, view, at);
         }
-        for (id, shape) in &ctx.geometry.shapes {
-            objects.push((
-                gwrapper::ThingID::ShapeID(*id),
-                gwrapper::Thing::Shape(*shape),
-            ));
+    }
+    fn selected(&self, _ctx: &gwrapper::GWrapper) -> HashMap<gwrapper::ThingID, SelectedStatus> {
+        let mut res = HashMap::new();
+        if let Some(object) = self.object {
+            res.insert(object, SelectedStatus::Primary);
         }
+        res
+    }
+    fn kind(&self) -> ToolKind {
+        ToolKind::Reflect
+    }
+    fn hover(&self, ctx: &gwrapper::GWrapper, view: &View, at: (f64, f64)) -> Hover {
+        match (self.object, closest_line(ctx, view, at)) {
+            (Some(object), Some(line)) => hover_image(
+                ctx,
+                gwrapper::ThingID::ShapeID(line),
+                object,
+                &geometry::Transformation::Reflect(line),
+            ),
+            (Some(_), None) => Hover::default(),
+            (None, _) => Hover::target(closest_thing(ctx, view, at)),
+        }
+    }
+}
 
-        let point_bonus = 25. / view.transform.scale;
+pub struct RotateTool {
+    pub object: Option<gwrapper::ThingID>,
+    /// The center, followed by the three points defining the angle
+    pub points: Vec<geometry::PointID>,
+}
 
-        let dist_fn = |(_, obj): &(gwrapper::ThingID, gwrapper::Thing)| match obj {
-            gwrapper::Thing::Point(p) => {
-                if let Some(pos) = ctx.geometry.resolve_point(&p) {
-                    let (dx, dy) = (pos.0 - at.0, pos.1 - at.1);
-                    (dx * dx + dy * dy).sqrt()
+impl Tool for RotateTool {
+    fn click(&mut self, ctx: &mut gwrapper::GWrapper, view: &mut View, at: (f64, f64)) {
+        if let Some(object) = self.object {
+            if let Some(id) = closest_point(ctx, view, at) {
+                self.points.push(id);
+            }
+            if let [center, a, vertex, b] = self.points[..] {
+                ctx.add_transformed(
+                    object,
+                    geometry::Transformation::Rotate(center, (a, vertex, b)),
+                );
+                self.object = None;
+                self.points.clear();
+            }
+        } else {
+            self.object = closest_thing(ctx, view, at);
+        }
+    }
+    fn selected(&self, _ctx: &gwrapper::GWrapper) -> HashMap<gwrapper::ThingID, SelectedStatus> {
+        let mut res = HashMap::new();
+        for point in &self.points {
+            res.insert(gwrapper::ThingID::PointID(*point), SelectedStatus::Active);
+        }
+        if let Some(object) = self.object {
+            res.insert(object, SelectedStatus::Primary);
+        }
+        res
+    }
+    fn kind(&self) -> ToolKind {
+        ToolKind::Rotate
+    }
+    fn hover(&self, ctx: &gwrapper::GWrapper, view: &View, at: (f64, f64)) -> Hover {
+        let object = match self.object {
+            Some(object) => object,
+            None => return Hover::target(closest_thing(ctx, view, at)),
+        };
+        match (&self.points[..], closest_point(ctx, view, at)) {
+            (&[center, a, vertex], Some(b)) => hover_image(
+                ctx,
+                gwrapper::ThingID::PointID(b),
+                object,
+                &geometry::Transformation::Rotate(center, (a, vertex, b)),
+            ),
+            (_, target) => Hover::target(target.map(gwrapper::ThingID::PointID)),
+        }
+    }
+}
+
+pub struct TranslateTool {
+    pub object: Option<gwrapper::ThingID>,
+    pub from: Option<geometry::PointID>,
+}
+
+impl Tool for TranslateTool {
+    fn click(&mut self, ctx: &mut gwrapper::GWrapper, view: &mut View, at: (f64, f64)) {
+        if let Some(object) = self.object {
+            if let Some(id) = closest_point(ctx, view, at) {
+                if let Some(from) = self.from {
+                    ctx.add_transformed(object, geometry::Transformation::Translate(from, id));
+                    self.object = None;
+                    self.from = None;
                 } else {
-                    f64::MAX
+                    self.from = Some(id);
                 }
             }
-            gwrapper::Thing::Shape(s) => {
-                let shape = ctx.geometry.resolve_shape(&s);
-                match shape {
-                    Some(geometry::ResolvedShape::Circle(pos, rad)) => {
-                        let (dx, dy) = (pos.0 - at.0, pos.1 - at.1);
-                        let dist = (dx * dx + dy * dy).sqrt();
-                        (dist - rad).abs() + point_bonus
-                    }
-                    Some(geometry::ResolvedShape::Line(k, m)) => {
-                        let k_ = -1. / k;
-                        let m_ = at.1 - k_ * at.0;
-
-                        let int_x = (m - m_) / (k_ - k);
-                        let int_y = k * int_x + m;
+        } else {
+            self.object = closest_thing(ctx, view, at);
+        }
+    }
+    fn selected(&self, _ctx: &gwrapper::GWrapper) -> HashMap<gwrapper::ThingID, SelectedStatus> {
+        let mut res = HashMap::new();
+        if let Some(from) = self.from {
+            res.insert(gwrapper::ThingID::PointID(from), SelectedStatus::Active);
+        }
+        if let Some(object) = self.object {
+            res.insert(object, SelectedStatus::Primary);
+        }
+        res
+    }
+    fn kind(&self) -> ToolKind {
+        ToolKind::Translate
+    }
+    fn hover(&self, ctx: &gwrapper::GWrapper, view: &View, at: (f64, f64)) -> Hover {
+        let object = match self.object {
+            Some(object) => object,
+            None => return Hover::target(closest_thing(ctx, view, at)),
+        };
+        match (self.from, closest_point(ctx, view, at)) {
+            (Some(from), Some(to)) => hover_image(
+                ctx,
+                gwrapper::ThingID::PointID(to),
+                object,
+                &geometry::Transformation::Translate(from, to),
+            ),
+            (_, target) => Hover::target(target.map(gwrapper::ThingID::PointID)),
+        }
+    }
+}
 
-                        let (dx, dy) = (int_x - at.0, int_y - at.1);
+/// Picks an object and a center. The factor is typed in, or is a new slider.
+pub struct DilateTool {
+    pub object: Option<gwrapper::ThingID>,
+    /// The center, while the factor is typed in
+    pub center: Option<geometry::PointID>,
+    /// The last factor, suggested for the next dilation
+    pub factor: f64,
+    /// Whether to dilate by a new slider, starting at `factor`
+    pub by_slider: bool,
+}
 
-                        (dx * dx + dy * dy).sqrt() + point_bonus
-                    }
-                    Some(geometry::ResolvedShape::LineUp(k)) => (k - at.0).abs() + point_bonus,
-                    None => f64::MAX,
+impl Tool for DilateTool {
+    fn click(&mut self, ctx: &mut gwrapper::GWrapper, view: &mut View, at: (f64, f64)) {
+        if let Some(object) = self.object {
+            if let Some(center) = closest_point(ctx, view, at) {
+                if self.by_slider {
+                    let (min, max, step) = DILATION_SLIDER;
+                    let factor = new_slider(ctx, "k", min, max, step, self.factor);
+                    let transformation = geometry::Transformation::DilateBySlider(center, factor);
+                    ctx.add_transformed(object, transformation);
+                    self.object = None;
+                } else {
+                    self.center = Some(center);
+                    view.ask("Dilation factor", self.factor.to_string(), Asker::Tool);
                 }
             }
+        } else {
+            self.object = closest_thing(ctx, view, at);
+        }
+    }
+    fn answer(&mut self, ctx: &mut gwrapper::GWrapper, view: &mut View, text: &str) {
+        let (object, center) = match (self.object.take(), self.center.take()) {
+            (Some(object), Some(center)) => (object, center),
+            _ => return,
         };
+        match text.trim().parse::<f64>() {
+            Ok(factor) if factor.is_finite() && factor != 0. => {
+                self.factor = factor;
+                ctx.add_transformed(object, geometry::create_dilation(center, factor));
+            }
+            _ => view.message(format!("`{}` isn't a factor to dilate by", text.trim())),
+        }
+    }
+    fn selected(&self, _ctx: &gwrapper::GWrapper) -> HashMap<gwrapper::ThingID, SelectedStatus> {
+        let mut res = HashMap::new();
+        if let Some(object) = self.object {
+            res.insert(object, SelectedStatus::Primary);
+        }
+        if let Some(center) = self.center {
+            res.insert(gwrapper::ThingID::PointID(center), SelectedStatus::Active);
+        }
+        res
+    }
+    fn kind(&self) -> ToolKind {
+        if self.by_slider {
+            ToolKind::DilateBySlider
+        } else {
+            ToolKind::Dilate
+        }
+    }
+    fn hover(&self, ctx: &gwrapper::GWrapper, view: &View, at: (f64, f64)) -> Hover {
+        match (self.object, closest_point(ctx, view, at)) {
+            (Some(object), Some(center)) => hover_image(
+                ctx,
+                gwrapper::ThingID::PointID(center),
+                object,
+                &geometry::create_dilation(center, self.factor),
+            ),
+            (Some(_), None) => Hover::default(),
+            (None, _) => Hover::target(closest_thing(ctx, view, at)),
+        }
+    }
+}
 
-        if let Some((_, (id, _))) = get_best(objects, dist_fn) {
-            if self.selected.contains(&id) {
-                self.selected.remove(&id);
+pub struct InvertTool {
+    /// The objects to invert, either picked by clicking or taken from the selection
+    pub objects: Vec<gwrapper::ThingID>,
+}
+
+impl Tool for InvertTool {
+    fn click(&mut self, ctx: &mut gwrapper::GWrapper, view: &mut View, at: (f64, f64)) {
+        if self.objects.is_empty() {
+            self.objects.extend(closest_thing(ctx, view, at));
+        } else if let Some(circle) = closest_circle(ctx, view, at) {
+            for object in self.objects.drain(..) {
+                ctx.add_transformed(object, geometry::Transformation::Invert(circle));
+            }
+        }
+    }
+    fn selected(&self, _ctx: &gwrapper::GWrapper) -> HashMap<gwrapper::ThingID, SelectedStatus> {
+        let mut res = HashMap::new();
+        for object in &self.objects {
+            res.insert(*object, SelectedStatus::Primary);
+        }
+        res
+    }
+    fn kind(&self) -> ToolKind {
+        ToolKind::Invert
+    }
+    fn hover(&self, ctx: &gwrapper::GWrapper, view: &View, at: (f64, f64)) -> Hover {
+        match (self.objects.first(), closest_circle(ctx, view, at)) {
+            (Some(object), Some(circle)) => hover_image(
+                ctx,
+                gwrapper::ThingID::ShapeID(circle),
+                *object,
+                &geometry::Transformation::Invert(circle),
+            ),
+            (Some(_), None) => Hover::default(),
+            (None, _) => Hover::target(closest_thing(ctx, view, at)),
+        }
+    }
+}
+
+pub struct DistanceTool {
+    pub points: Vec<geometry::PointID>,
+}
+
+impl Tool for DistanceTool {
+    fn click(&mut self, ctx: &mut gwrapper::GWrapper, view: &mut View, at: (f64, f64)) {
+        if let Some(id) = closest_point(ctx, view, at) {
+            self.points.push(id);
+        }
+        if let [a, b] = self.points[..] {
+            ctx.add_measurement(geometry::Measurement::Distance(a, b));
+            self.points.clear();
+        }
+    }
+    fn selected(&self, _ctx: &gwrapper::GWrapper) -> HashMap<gwrapper::ThingID, SelectedStatus> {
+        let mut res = HashMap::new();
+        for point in &self.points {
+            res.insert(gwrapper::ThingID::PointID(*point), SelectedStatus::Primary);
+        }
+        res
+    }
+    fn kind(&self) -> ToolKind {
+        ToolKind::Distance
+    }
+    fn hover(&self, ctx: &gwrapper::GWrapper, view: &View, at: (f64, f64)) -> Hover {
+        Hover::target(closest_point(ctx, view, at).map(gwrapper::ThingID::PointID))
+    }
+}
+
+pub struct AngleTool {
+    /// (a, vertex, b)
+    pub points: Vec<geometry::PointID>,
+}
+
+impl Tool for AngleTool {
+    fn click(&mut self, ctx: &mut gwrapper::GWrapper, view: &mut View, at: (f64, f64)) {
+        if let Some(id) = closest_point(ctx, view, at) {
+            self.points.push(id);
+        }
+        if let [a, vertex, b] = self.points[..] {
+            ctx.add_measurement(geometry::Measurement::Angle(a, vertex, b));
+            self.points.clear();
+        }
+    }
+    fn selected(&self, _ctx: &gwrapper::GWrapper) -> HashMap<gwrapper::ThingID, SelectedStatus> {
+        let mut res = HashMap::new();
+        for point in &self.points {
+            res.insert(gwrapper::ThingID::PointID(*point), SelectedStatus::Primary);
+        }
+        res
+    }
+    fn kind(&self) -> ToolKind {
+        ToolKind::Angle
+    }
+    fn hover(&self, ctx: &gwrapper::GWrapper, view: &View, at: (f64, f64)) -> Hover {
+        Hover::target(closest_point(ctx, view, at).map(gwrapper::ThingID::PointID))
+    }
+}
+
+pub struct RadiusTool {}
+
+impl Tool for RadiusTool {
+    fn click(&mut self, ctx: &mut gwrapper::GWrapper, view: &mut View, at: (f64, f64)) {
+        if let Some(circle) = closest_circle(ctx, view, at) {
+            ctx.add_measurement(geometry::Measurement::Radius(circle));
+        }
+    }
+    fn selected(&self, _ctx: &gwrapper::GWrapper) -> HashMap<gwrapper::ThingID, SelectedStatus> {
+        HashMap::new()
+    }
+    fn kind(&self) -> ToolKind {
+        ToolKind::Radius
+    }
+    fn hover(&self, ctx: &gwrapper::GWrapper, view: &View, at: (f64, f64)) -> Hover {
+        Hover::target(closest_circle(ctx, view, at).map(gwrapper::ThingID::ShapeID))
+    }
+}
+
+pub struct AreaTool {
+    pub vertices: Vec<geometry::PointID>,
+}
+
+impl Tool for AreaTool {
+    fn click(&mut self, ctx: &mut gwrapper::GWrapper, view: &mut View, at: (f64, f64)) {
+        if let Some(vertices) = polygon_click(&mut self.vertices, ctx, view, at, true) {
+            ctx.add_measurement(geometry::Measurement::Area(vertices));
+        }
+    }
+    fn selected(&self, _ctx: &gwrapper::GWrapper) -> HashMap<gwrapper::ThingID, SelectedStatus> {
+        polygon_selected(&self.vertices)
+    }
+    fn kind(&self) -> ToolKind {
+        ToolKind::Area
+    }
+    fn hover(&self, ctx: &gwrapper::GWrapper, view: &View, at: (f64, f64)) -> Hover {
+        polygon_hover(&self.vertices, ctx, view, at, true)
+    }
+}
+
+pub struct PerimeterTool {
+    pub vertices: Vec<geometry::PointID>,
+}
+
+impl Tool for PerimeterTool {
+    fn click(&mut self, ctx: &mut gwrapper::GWrapper, view: &mut View, at: (f64, f64)) {
+        if let Some(vertices) = polygon_click(&mut self.vertices, ctx, view, at, true) {
+            ctx.add_measurement(geometry::Measurement::Perimeter(vertices));
+        }
+    }
+    fn selected(&self, _ctx: &gwrapper::GWrapper) -> HashMap<gwrapper::ThingID, SelectedStatus> {
+        polygon_selected(&self.vertices)
+    }
+    fn kind(&self) -> ToolKind {
+        ToolKind::Perimeter
+    }
+    fn hover(&self, ctx: &gwrapper::GWrapper, view: &View, at: (f64, f64)) -> Hover {
+        polygon_hover(&self.vertices, ctx, view, at, true)
+    }
+}
+
+pub struct PolygonTool {
+    pub vertices: Vec<geometry::PointID>,
+}
+
+impl Tool for PolygonTool {
+    fn click(&mut self, ctx: &mut gwrapper::GWrapper, view: &mut View, at: (f64, f64)) {
+        if let Some(vertices) = polygon_click(&mut self.vertices, ctx, view, at, false) {
+            let id = ctx.geometry.add_polygon(geometry::Polygon(vertices));
+            ctx.style_mut(gwrapper::ThingID::PolygonID(id)).fill = Some(gwrapper::DEFAULT_FILL);
+        }
+    }
+    fn selected(&self, _ctx: &gwrapper::GWrapper) -> HashMap<gwrapper::ThingID, SelectedStatus> {
+        polygon_selected(&self.vertices)
+    }
+    fn kind(&self) -> ToolKind {
+        ToolKind::Polygon
+    }
+    fn hover(&self, ctx: &gwrapper::GWrapper, view: &View, at: (f64, f64)) -> Hover {
+        polygon_hover(&self.vertices, ctx, view, at, false)
+    }
+}
+
+/// Constructs a regular polygon on the side between two clicked points
+pub struct RegularPolygonTool {
+    pub sides: usize,
+    pub first: Option<geometry::PointID>,
+}
+
+impl Tool for RegularPolygonTool {
+    fn click(&mut self, ctx: &mut gwrapper::GWrapper, view: &mut View, at: (f64, f64)) {
+        if let Some(id) = closest_point(ctx, view, at) {
+            if let Some(first) = self.first {
+                constructions::regular_polygon(ctx, first, id, self.sides);
+                self.first = None;
             } else {
-                self.selected.insert(id);
+                self.first = Some(id);
             }
         }
     }
     fn selected(&self, _ctx: &gwrapper::GWrapper) -> HashMap<gwrapper::ThingID, SelectedStatus> {
         let mut res = HashMap::new();
+        if let Some(first) = self.first {
+            res.insert(gwrapper::ThingID::PointID(first), SelectedStatus::Primary);
+        }
+        res
+    }
+    fn kind(&self) -> ToolKind {
+        ToolKind::RegularPolygon(self.sides)
+    }
+    fn hover(&self, ctx: &gwrapper::GWrapper, view: &View, at: (f64, f64)) -> Hover {
+        Hover::target(closest_point(ctx, view, at).map(gwrapper::ThingID::PointID))
+    }
+}
 
-        for x in &self.selected {
-            res.insert(*x, SelectedStatus::Primary);
+/// Traces a point as a free point moves along a shape. Click the traced point, then the free
+/// point and last the shape.
+pub struct LocusTool {
+    pub traced: Option<geometry::PointID>,
+    pub driver: Option<geometry::PointID>,
+}
+
+impl Tool for LocusTool {
+    fn click(&mut self, ctx: &mut gwrapper::GWrapper, view: &mut View, at: (f64, f64)) {
+        match (self.traced, self.driver) {
+            (None, _) => self.traced = closest_point(ctx, view, at),
+            (Some(_), None) => {
+                self.driver = closest_point(ctx, view, at).filter(|id| {
+                    matches!(
+                        ctx.geometry.points.get(id),
+                        Some(geometry::Point::Arbitrary(_))
+                    )
+                })
+            }
+            (Some(traced), Some(driver)) => {
+                if let Some(gwrapper::ThingID::ShapeID(path)) = closest_thing(ctx, view, at) {
+                    ctx.geometry.add_locus(geometry::Locus {
+                        traced,
+                        driver,
+                        path,
+                    });
+                    self.traced = None;
+                    self.driver = None;
+                }
+            }
+        }
+    }
+    fn selected(&self, _ctx: &gwrapper::GWrapper) -> HashMap<gwrapper::ThingID, SelectedStatus> {
+        let mut res = HashMap::new();
+        if let Some(traced) = self.traced {
+            res.insert(gwrapper::ThingID::PointID(traced), SelectedStatus::Primary);
+        }
+        if let Some(driver) = self.driver {
+            res.insert(gwrapper::ThingID::PointID(driver), SelectedStatus::Active);
+        }
+        res
+    }
+    fn kind(&self) -> ToolKind {
+        ToolKind::Locus
+    }
+    fn hover(&self, ctx: &gwrapper::GWrapper, view: &View, at: (f64, f64)) -> Hover {
+        let target = match (self.traced, self.driver) {
+            (None, _) => closest_point(ctx, view, at).map(gwrapper::ThingID::PointID),
+            (Some(_), None) => closest_free_point(ctx, at).map(gwrapper::ThingID::PointID),
+            (Some(_), Some(_)) => closest_thing(ctx, view, at)
+                .filter(|id| matches!(id, gwrapper::ThingID::ShapeID(_))),
+        };
+        Hover::target(target)
+    }
+}
+
+/// Puts a point on the clicked circle, at an angle given by a new slider
+pub struct OnCircleTool {}
+
+impl Tool for OnCircleTool {
+    fn click(&mut self, ctx: &mut gwrapper::GWrapper, view: &mut View, at: (f64, f64)) {
+        if let Some(circle) = closest_circle(ctx, view, at) {
+            let center = match ctx
+                .geometry
+                .shapes
+                .get(&circle)
+                .and_then(|shape| ctx.geometry.resolve_shape(shape))
+            {
+                Some(geometry::ResolvedShape::Circle(center, _)) => center,
+                _ => return,
+            };
+            // The slider starts where the circle was clicked
+            let slider = new_slider(ctx, "a", 0., 360., 1., circle_angle(center, at));
+            ctx.geometry
+                .add_point(geometry::Point::OnCircle(circle, slider));
+        }
+    }
+    fn selected(&self, _ctx: &gwrapper::GWrapper) -> HashMap<gwrapper::ThingID, SelectedStatus> {
+        HashMap::new()
+    }
+    fn kind(&self) -> ToolKind {
+        ToolKind::OnCircle
+    }
+    fn hover(&self, ctx: &gwrapper::GWrapper, view: &View, at: (f64, f64)) -> Hover {
+        let circle = closest_circle(ctx, view, at);
+        let point = match circle
+            .and_then(|id| ctx.geometry.shapes.get(&id))
+            .and_then(|shape| ctx.geometry.resolve_shape(shape))
+        {
+            Some(geometry::ResolvedShape::Circle(center, rad)) => {
+                let angle = (at.1 - center.1).atan2(at.0 - center.0);
+                Some((center.0 + rad * angle.cos(), center.1 + rad * angle.sin()))
+            }
+            _ => None,
+        };
+        Hover {
+            target: circle.map(gwrapper::ThingID::ShapeID),
+            point,
+            ..Hover::default()
         }
+    }
+}
 
+pub struct MacroTool {
+    pub definition: Macro,
+    /// The inputs picked so far
+    pub inputs: Vec<geometry::PointID>,
+}
+
+impl Tool for MacroTool {
+    fn click(&mut self, ctx: &mut gwrapper::GWrapper, view: &mut View, at: (f64, f64)) {
+        if let Some(id) = closest_point(ctx, view, at) {
+            self.inputs.push(id);
+            if self.inputs.len() == self.definition.inputs {
+                if let Err(err) = self.definition.apply(ctx, &self.inputs) {
+                    view.message(format!("Can't use {}: {}", self.definition.name, err));
+                }
+                self.inputs.clear();
+            }
+        }
+    }
+    fn selected(&self, _ctx: &gwrapper::GWrapper) -> HashMap<gwrapper::ThingID, SelectedStatus> {
+        let mut res = HashMap::new();
+        for input in &self.inputs {
+            res.insert(gwrapper::ThingID::PointID(*input), SelectedStatus::Primary);
+        }
         res
     }
     fn kind(&self) -> ToolKind {
-        ToolKind::Selector
+        ToolKind::Macro(self.definition.clone())
+    }
+    fn hover(&self, ctx: &gwrapper::GWrapper, view: &View, at: (f64, f64)) -> Hover {
+        Hover::target(closest_point(ctx, view, at).map(gwrapper::ThingID::PointID))
+    }
+}
+
+/// Picks an object, then one of the objects it's defined from, then another point or shape to
+/// define it from instead
+pub struct RedefineTool {
+    pub object: Option<gwrapper::ThingID>,
+    /// The object being replaced in the definition
+    pub old: Option<gwrapper::ThingID>,
+}
+
+impl Tool for RedefineTool {
+    fn click(&mut self, ctx: &mut gwrapper::GWrapper, view: &mut View, at: (f64, f64)) {
+        let target = match redefine_target(ctx, view, self.object, self.old, at) {
+            Some(target) => target,
+            None => return,
+        };
+        let (object, old) = match (self.object, self.old) {
+            (Some(object), Some(old)) => (object, old),
+            (Some(_), None) => {
+                self.old = Some(target);
+                return;
+            }
+            (None, _) => {
+                if is_free(ctx, target) {
+                    view.message("Click two shapes to put the point where they intersect");
+                }
+                self.object = Some(target);
+                return;
+            }
+        };
+        self.object = None;
+        self.old = None;
+
+        let res = match object {
+            // A free point becomes the intersection of the two shapes that is closest to it
+            gwrapper::ThingID::PointID(id) if is_free(ctx, object) => match (old, target) {
+                (gwrapper::ThingID::ShapeID(a), gwrapper::ThingID::ShapeID(b)) => {
+                    let point = closest_intersection(ctx, id, a, b);
+                    ctx.geometry.redefine_point(id, point)
+                }
+                _ => return,
+            },
+            gwrapper::ThingID::PointID(id) => {
+                let mut point = ctx.geometry.points[&id];
+                point.replace(old, target);
+                ctx.geometry.redefine_point(id, point)
+            }
+            gwrapper::ThingID::ShapeID(id) => {
+                let mut shape = ctx.geometry.shapes[&id];
+                shape.replace(old, target);
+                ctx.geometry.redefine_shape(id, shape)
+            }
+            _ => return,
+        };
+        report_redefinition(ctx, view, object, res);
+    }
+    fn selected(&self, _ctx: &gwrapper::GWrapper) -> HashMap<gwrapper::ThingID, SelectedStatus> {
+        let mut res = HashMap::new();
+        if let Some(object) = self.object {
+            res.insert(object, SelectedStatus::Primary);
+        }
+        if let Some(old) = self.old {
+            res.insert(old, SelectedStatus::Active);
+        }
+        res
+    }
+    fn kind(&self) -> ToolKind {
+        ToolKind::Redefine
+    }
+    fn hover(&self, ctx: &gwrapper::GWrapper, view: &View, at: (f64, f64)) -> Hover {
+        Hover::target(redefine_target(ctx, view, self.object, self.old, at))
+    }
+}
+
+/// Shows what went wrong when redefining `object`, or what was lost
+fn report_redefinition(
+    ctx: &gwrapper::GWrapper,
+    view: &mut View,
+    object: gwrapper::ThingID,
+    res: Result<Vec<gwrapper::ThingID>, geometry::RedefineError>,
+) {
+    let names = protocol::names(ctx);
+    let name = |id| names.get(&id).map(|name| &name[..]).unwrap_or("?");
+    match res {
+        Ok(ref lost) if lost.is_empty() => {}
+        Ok(lost) => {
+            let lost: Vec<_> = lost.into_iter().map(name).collect();
+            view.message(format!("{} can't be constructed anymore", lost.join(", ")));
+        }
+        Err(geometry::RedefineError::Cycle(used)) => view.message(format!(
+            "Can't redefine {} from {}, which is defined from it",
+            name(object),
+            name(used)
+        )),
+        Err(geometry::RedefineError::Missing) => {
+            view.message(format!("Can't redefine {}", name(object)))
+        }
+    }
+}
+
+/// Whether `id` is a free point
+fn is_free(ctx: &gwrapper::GWrapper, id: gwrapper::ThingID) -> bool {
+    match id {
+        gwrapper::ThingID::PointID(point) => matches!(
+            ctx.geometry.points.get(&point),
+            Some(geometry::Point::Arbitrary(_))
+        ),
+        _ => false,
+    }
+}
+
+/// The intersection of `a` and `b` closest to where the point `id` is
+fn closest_intersection(
+    ctx: &gwrapper::GWrapper,
+    id: geometry::PointID,
+    a: geometry::ShapeID,
+    b: geometry::ShapeID,
+) -> geometry::Point {
+    let both = vec![
+        geometry::Point::PrimIntersection(a, b),
+        geometry::Point::SecIntersection(a, b),
+    ];
+    // Shapes that don't meet give an intersection that can't be constructed
+    let pos = point_pos(ctx, id).unwrap_or((0., 0.));
+    get_closest(
+        pos,
+        both,
+        |point| {
+            ctx.geometry
+                .resolve_point(point)
+                .unwrap_or((f64::INFINITY, f64::INFINITY))
+        },
+        None,
+    )
+    .unwrap_or(geometry::Point::PrimIntersection(a, b))
+}
+
+/// What the redefine tool would pick at `at`: a point or shape defined from something, then
+/// one of the objects it's defined from, then another object of the same kind. A free point
+/// is followed by the two shapes it's put on the intersection of.
+fn redefine_target(
+    ctx: &gwrapper::GWrapper,
+    view: &View,
+    object: Option<gwrapper::ThingID>,
+    old: Option<gwrapper::ThingID>,
+    at: (f64, f64),
+) -> Option<gwrapper::ThingID> {
+    let target = closest_thing(ctx, view, at)?;
+    let ok = match (object, old, target) {
+        (None, _, gwrapper::ThingID::PointID(_)) | (None, _, gwrapper::ThingID::ShapeID(_)) => {
+            is_free(ctx, target) || !ctx.geometry.dependencies(target).0.is_empty()
+        }
+        (None, _, _) => false,
+        (Some(object), None, gwrapper::ThingID::ShapeID(_)) if is_free(ctx, object) => true,
+        (Some(object), None, _) => ctx.geometry.dependencies(object).0.contains(&target),
+        (Some(_), Some(old), _) => {
+            target != old && mem::discriminant(&target) == mem::discriminant(&old)
+        }
+    };
+    if ok {
+        Some(target)
+    } else {
+        None
+    }
+}
+
+/// Clicking a free point attaches it to an intersection close to it, or else to the closest
+/// circle or line. Clicking any other point frees it where it is.
+pub struct AttachTool {}
+
+impl Tool for AttachTool {
+    fn click(&mut self, ctx: &mut gwrapper::GWrapper, view: &mut View, at: (f64, f64)) {
+        let id = match closest_point(ctx, view, at) {
+            Some(id) => id,
+            None => return,
+        };
+        let point = match ctx.geometry.points[&id] {
+            geometry::Point::Arbitrary(_) => match attachment(ctx, view, id) {
+                Some(Attachment::Intersection(intersection)) => intersection,
+                Some(Attachment::Circle(circle, angle)) => {
+                    let slider = new_slider(ctx, "a", 0., 360., 1., angle);
+                    geometry::Point::OnCircle(circle, slider)
+                }
+                Some(Attachment::Line(line, along)) => {
+                    let (min, max) = (
+                        along.floor() - LINE_SLIDER_SPAN,
+                        along.ceil() + LINE_SLIDER_SPAN,
+                    );
+                    let slider = new_slider(ctx, "t", min, max, 0., along);
+                    geometry::Point::OnLine(line, slider)
+                }
+                None => {
+                    view.message("There is no intersection, circle or line to attach the point to");
+                    return;
+                }
+            },
+            _ => match point_pos(ctx, id) {
+                Some(pos) => geometry::create_arbitrary(pos),
+                None => return,
+            },
+        };
+        // The slider a point on a circle or line was at is removed with it
+        let slider = match ctx.geometry.points[&id] {
+            geometry::Point::OnCircle(_, slider) | geometry::Point::OnLine(_, slider) => {
+                Some(slider)
+            }
+            _ => None,
+        };
+        let res = ctx.geometry.redefine_point(id, point);
+        if let (Ok(_), Some(slider)) = (&res, slider) {
+            ctx.geometry.remove_unused_slider(slider);
+        }
+        report_redefinition(ctx, view, gwrapper::ThingID::PointID(id), res);
+    }
+    fn selected(&self, _ctx: &gwrapper::GWrapper) -> HashMap<gwrapper::ThingID, SelectedStatus> {
+        HashMap::new()
+    }
+    fn kind(&self) -> ToolKind {
+        ToolKind::Attach
+    }
+    fn hover(&self, ctx: &gwrapper::GWrapper, view: &View, at: (f64, f64)) -> Hover {
+        let id = match closest_point(ctx, view, at) {
+            Some(id) => id,
+            None => return Hover::default(),
+        };
+        let point = match ctx.geometry.points[&id] {
+            geometry::Point::Arbitrary(_) => match attachment(ctx, view, id) {
+                Some(Attachment::Intersection(intersection)) => {
+                    ctx.geometry.resolve_point(&intersection)
+                }
+                Some(Attachment::Circle(circle, angle)) => {
+                    match ctx.geometry.resolve_shape(&ctx.geometry.shapes[&circle]) {
+                        Some(geometry::ResolvedShape::Circle(center, rad)) => {
+                            let angle = angle.to_radians();
+                            Some((center.0 + rad * angle.cos(), center.1 - rad * angle.sin()))
+                        }
+                        _ => None,
+                    }
+                }
+                Some(Attachment::Line(line, along)) => ctx
+                    .geometry
+                    .line_anchors(&ctx.geometry.shapes[&line])
+                    .map(|(a, b)| (a.0 + along * (b.0 - a.0), a.1 + along * (b.1 - a.1))),
+                None => None,
+            },
+            _ => None,
+        };
+        Hover {
+            target: Some(gwrapper::ThingID::PointID(id)),
+            point,
+            ..Hover::default()
+        }
+    }
+}
+
+/// What a free point is attached to
+enum Attachment {
+    Intersection(geometry::Point),
+    /// A circle, and the angle the point is at on it in degrees
+    Circle(geometry::ShapeID, f64),
+    /// A line, and where the point is along it as for `Point::OnLine`
+    Line(geometry::ShapeID, f64),
+}
+
+/// Where the free point `id` would be attached: an intersection close to it, otherwise the
+/// closest circle or line. Shapes defined from the point are left out.
+fn attachment(ctx: &gwrapper::GWrapper, view: &View, id: geometry::PointID) -> Option<Attachment> {
+    let pos = point_pos(ctx, id)?;
+    // Compared squared, like the points in `get_closest`
+    let max = 100. / view.transform.scale;
+    let independent = |shape: &geometry::ShapeID| {
+        !ctx.geometry.is_defined_from(
+            gwrapper::ThingID::ShapeID(*shape),
+            gwrapper::ThingID::PointID(id),
+        )
+    };
+
+    let intersections =
+        ctx.geometry
+            .intersections()
+            .into_iter()
+            .filter(|point| match point {
+                geometry::Point::PrimIntersection(a, b)
+                | geometry::Point::SecIntersection(a, b) => independent(a) && independent(b),
+                _ => false,
+            })
+            .collect();
+    let intersection = get_closest(
+        pos,
+        intersections,
+        |point| ctx.geometry.resolve_point(point).unwrap_or((0., 0.)),
+        Some(max),
+    );
+    if let Some(intersection) = intersection {
+        return Some(Attachment::Intersection(intersection));
+    }
+
+    let shapes = ctx
+        .geometry
+        .shapes
+        .iter()
+        .filter(|(shape, _)| independent(shape))
+        .filter_map(|(shape, definition)| Some((*shape, ctx.geometry.resolve_shape(definition)?)))
+        .collect();
+    let (dist, (shape, resolved)) = get_best(shapes, |(_, resolved)| resolved.distance_to(pos))?;
+    if dist * dist >= max {
+        return None;
+    }
+    match resolved {
+        geometry::ResolvedShape::Circle(center, _) => {
+            Some(Attachment::Circle(shape, circle_angle(center, pos)))
+        }
+        _ => line_position(ctx, shape, pos).map(|along| Attachment::Line(shape, along)),
+    }
+}
+
+/// Where the foot of `at` is along `line`, as for `Point::OnLine`
+fn line_position(ctx: &gwrapper::GWrapper, line: geometry::ShapeID, at: (f64, f64)) -> Option<f64> {
+    let (a, b) = ctx.geometry.line_anchors(ctx.geometry.shapes.get(&line)?)?;
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let along = ((at.0 - a.0) * dx + (at.1 - a.1) * dy) / (dx * dx + dy * dy);
+    Some(along).filter(|along| along.is_finite())
+}
+
+/// The angle of `at` around `center` in degrees, counter-clockwise from the right
+fn circle_angle(center: (f64, f64), at: (f64, f64)) -> f64 {
+    let angle = (center.1 - at.1).atan2(at.0 - center.0).to_degrees();
+    if angle < 0. {
+        angle + 360.
+    } else {
+        angle
+    }
+}
+
+/// Adds a slider named `prefix` followed by the first unused number
+fn new_slider(
+    ctx: &mut gwrapper::GWrapper,
+    prefix: &str,
+    min: f64,
+    max: f64,
+    step: f64,
+    value: f64,
+) -> geometry::SliderID {
+    let name = (1..)
+        .map(|i| format!("{}{}", prefix, i))
+        .find(|name| {
+            !ctx.geometry
+                .sliders
+                .values()
+                .any(|slider| &slider.name == name)
+        })
+        .unwrap();
+    ctx.geometry
+        .add_slider(geometry::Slider::new(&name, min, max, step, value))
+}
+
+/// Adds the clicked point to `vertices`. Returns the vertices once the polygon is closed by
+/// clicking the first vertex again. If `existing` is set, clicking inside an existing polygon
+/// when no vertices are picked returns its vertices.
+fn polygon_click(
+    vertices: &mut Vec<geometry::PointID>,
+    ctx: &gwrapper::GWrapper,
+    view: &View,
+    at: (f64, f64),
+    existing: bool,
+) -> Option<Vec<geometry::PointID>> {
+    match closest_point(ctx, view, at) {
+        Some(id) if vertices.first() == Some(&id) && vertices.len() >= 3 => {
+            Some(mem::take(vertices))
+        }
+        Some(id) => {
+            if !vertices.contains(&id) {
+                vertices.push(id);
+            }
+            None
+        }
+        None if existing && vertices.is_empty() => match closest_thing(ctx, view, at) {
+            Some(gwrapper::ThingID::PolygonID(id)) => ctx
+                .geometry
+                .polygons
+                .get(&id)
+                .map(|polygon| polygon.0.clone()),
+            _ => None,
+        },
+        None => None,
+    }
+}
+
+fn polygon_hover(
+    vertices: &[geometry::PointID],
+    ctx: &gwrapper::GWrapper,
+    view: &View,
+    at: (f64, f64),
+    existing: bool,
+) -> Hover {
+    match closest_point(ctx, view, at) {
+        Some(id) => Hover::target(Some(gwrapper::ThingID::PointID(id))),
+        None if existing && vertices.is_empty() => match closest_thing(ctx, view, at) {
+            Some(id @ gwrapper::ThingID::PolygonID(_)) => Hover::target(Some(id)),
+            _ => Hover::default(),
+        },
+        None => Hover::default(),
+    }
+}
+
+/// Picks `target`, showing the image of `object` under a transformation
+fn hover_image(
+    ctx: &gwrapper::GWrapper,
+    target: gwrapper::ThingID,
+    object: gwrapper::ThingID,
+    transformation: &geometry::Transformation,
+) -> Hover {
+    let mut hover = Hover::target(Some(target));
+    let transformation = match ctx.geometry.resolve_transformation(transformation) {
+        Some(transformation) => transformation,
+        None => return hover,
+    };
+    match object {
+        gwrapper::ThingID::PointID(id) => {
+            hover.point = point_pos(ctx, id).and_then(|pos| transformation.apply_point(pos));
+        }
+        gwrapper::ThingID::ShapeID(id) => {
+            hover.shape = ctx
+                .geometry
+                .shapes
+                .get(&id)
+                .and_then(|shape| ctx.geometry.resolve_shape(shape))
+                .and_then(|shape| transformation.apply_shape(&shape));
+        }
+        _ => {}
+    }
+    hover
+}
+
+fn point_pos(ctx: &gwrapper::GWrapper, id: geometry::PointID) -> Option<(f64, f64)> {
+    ctx.geometry.resolve_point(ctx.geometry.points.get(&id)?)
+}
+
+fn polygon_selected(vertices: &[geometry::PointID]) -> HashMap<gwrapper::ThingID, SelectedStatus> {
+    let mut res = HashMap::new();
+    for vertex in vertices {
+        res.insert(gwrapper::ThingID::PointID(*vertex), SelectedStatus::Active);
+    }
+    if let Some(first) = vertices.first() {
+        res.insert(gwrapper::ThingID::PointID(*first), SelectedStatus::Primary);
+    }
+    res
+}
+
+/// Finds the point or shape closest to `at`, preferring points
+pub fn closest_thing(
+    ctx: &gwrapper::GWrapper,
+    view: &View,
+    at: (f64, f64),
+) -> Option<gwrapper::ThingID> {
+    let mut objects = Vec::<(gwrapper::ThingID, gwrapper::Thing)>::new();
+
+    for (id, point) in &ctx.geometry.points {
+        objects.push((
+            gwrapper::ThingID::PointID(*id),
+            gwrapper::Thing::Point(*point),
+        ));
+    }
+    for (id, shape) in &ctx.geometry.shapes {
+        objects.push((
+            gwrapper::ThingID::ShapeID(*id),
+            gwrapper::Thing::Shape(*shape),
+        ));
+    }
+    for (id, polygon) in &ctx.geometry.polygons {
+        objects.push((
+            gwrapper::ThingID::PolygonID(*id),
+            gwrapper::Thing::Polygon(polygon.clone()),
+        ));
+    }
+    for (id, locus) in &ctx.geometry.loci {
+        objects.push((
+            gwrapper::ThingID::LocusID(*id),
+            gwrapper::Thing::Locus(*locus),
+        ));
+    }
+
+    let point_bonus = 25. / view.transform.scale;
+
+    let dist_fn = |(id, obj): &(gwrapper::ThingID, gwrapper::Thing)| match obj {
+        gwrapper::Thing::Point(p) => {
+            if let Some(pos) = ctx.geometry.resolve_point(p) {
+                let (dx, dy) = (pos.0 - at.0, pos.1 - at.1);
+                (dx * dx + dy * dy).sqrt()
+            } else {
+                f64::MAX
+            }
+        }
+        gwrapper::Thing::Shape(s) => match ctx.geometry.resolve_shape(s) {
+            Some(shape) => shape.distance_to(at) + point_bonus,
+            None => f64::MAX,
+        },
+        // Polygons are picked by their interior, losing to any nearby edge or point
+        gwrapper::Thing::Polygon(p) => match ctx.geometry.resolve_polygon(p) {
+            Some(ref vertices) if geometry::point_in_polygon(vertices, at) => 2. * point_bonus,
+            _ => f64::MAX,
+        },
+        gwrapper::Thing::Locus(_) => match id {
+            gwrapper::ThingID::LocusID(id) => ctx
+                .geometry
+                .locus_path(*id)
+                .map(|paths| {
+                    paths
+                        .iter()
+                        .map(|path| geometry::distance_to_polyline(path, at) + point_bonus)
+                        .fold(f64::MAX, f64::min)
+                })
+                .unwrap_or(f64::MAX),
+            _ => f64::MAX,
+        },
+    };
+
+    get_best(objects, dist_fn)
+        .filter(|(dist, _)| *dist < f64::MAX)
+        .map(|(_, (id, _))| id)
+}
+
+pub fn closest_point(
+    ctx: &gwrapper::GWrapper,
+    view: &View,
+    at: (f64, f64),
+) -> Option<geometry::PointID> {
+    get_closest(
+        at,
+        ctx.geometry.points.iter().collect(),
+        |(_, point)| ctx.geometry.resolve_point(point).unwrap_or((0., 0.)),
+        Some(100. / view.transform.scale),
+    )
+    .map(|(&id, _)| id)
+}
+
+/// The closest point that isn't placed at an intersection or a transformation
+pub fn closest_free_point(ctx: &gwrapper::GWrapper, at: (f64, f64)) -> Option<geometry::PointID> {
+    get_closest(
+        at,
+        ctx.geometry
+            .points
+            .iter()
+            .filter(|(_, point)| matches!(point, geometry::Point::Arbitrary(_)))
+            .collect(),
+        |(_, point)| ctx.geometry.resolve_point(point).unwrap_or((0., 0.)),
+        None,
+    )
+    .map(|(&id, _)| id)
+}
+
+pub fn closest_line(
+    ctx: &gwrapper::GWrapper,
+    view: &View,
+    at: (f64, f64),
+) -> Option<geometry::ShapeID> {
+    closest_shape(ctx, view, at, |shape| {
+        !matches!(shape, geometry::ResolvedShape::Circle(..))
+    })
+}
+
+pub fn closest_circle(
+    ctx: &gwrapper::GWrapper,
+    view: &View,
+    at: (f64, f64),
+) -> Option<geometry::ShapeID> {
+    closest_shape(ctx, view, at, |shape| {
+        matches!(shape, geometry::ResolvedShape::Circle(..))
+    })
+}
+
+/// The closest shape passing `filter`, if it's as close as a point would have to be
+fn closest_shape<F: Fn(&geometry::ResolvedShape) -> bool>(
+    ctx: &gwrapper::GWrapper,
+    view: &View,
+    at: (f64, f64),
+    filter: F,
+) -> Option<geometry::ShapeID> {
+    let shapes = ctx
+        .geometry
+        .shapes
+        .iter()
+        .filter_map(|(id, shape)| {
+            ctx.geometry
+                .resolve_shape(shape)
+                .filter(|resolved| filter(resolved))
+                .map(|resolved| (*id, resolved))
+        })
+        .collect();
+
+    // Compared squared, like the points in `get_closest`
+    let max = 100. / view.transform.scale;
+    get_best(shapes, |(_, shape)| shape.distance_to(at))
+        .filter(|(dist, _)| dist * dist < max)
+        .map(|(_, (id, _))| id)
+}
+
+#[test]
+fn test_attach_radius() {
+    use transform::Transform;
+
+    let mut view = View::new(Transform::new_from_winsize((800., 600.)));
+    let radius = (100. / view.transform.scale).sqrt();
+    for &(offset, attached) in &[(0.9, true), (1.1, false)] {
+        let mut ctx = gwrapper::GWrapper::new(geometry::Geometry::new());
+        let center = ctx.geometry.add_point(geometry::create_arbitrary((0., 0.)));
+        let through = ctx
+            .geometry
+            .add_point(geometry::create_arbitrary((0., 10.)));
+        ctx.geometry
+            .add_shape(geometry::Shape::Circle(center, through));
+        let at = (10. + offset * radius, 0.);
+        let point = ctx.geometry.add_point(geometry::create_arbitrary(at));
+
+        AttachTool {}.click(&mut ctx, &mut view, at);
+        match ctx.geometry.points[&point] {
+            geometry::Point::OnCircle(..) => assert!(attached),
+            geometry::Point::Arbitrary(_) => assert!(!attached),
+            ref other => panic!("Attached to {:?}", other),
+        }
     }
 }
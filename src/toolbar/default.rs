@@ -1,26 +1,51 @@
 use super::*;
 use backend::gwrapper;
-use tool::SelectedStatus;
+use macros::Macro;
+use tool::{tools, SelectedStatus};
 
-pub fn default_toolbar(send: Sender<Button>) -> ToolBar {
+pub fn default_toolbar(send: Sender<Button>, theme: Theme) -> ToolBar {
     let tools = vec![
         (make_selector(send.clone()), icons::TOOL_SELECTOR.clone()),
         (make_peeker(), icons::TOOL_PEEK.clone()),
+        (make_gridder(), icons::TOOL_GRID.clone()),
+        (make_snapper(), icons::TOOL_SNAP.clone()),
         (cb_set_tool(ToolKind::Point), icons::TOOL_POINT.clone()),
+        (
+            cb_set_tool(ToolKind::OnCircle),
+            icons::TOOL_ON_CIRCLE.clone(),
+        ),
         (cb_set_tool(ToolKind::Circle), icons::TOOL_CIRCLE.clone()),
         (cb_set_tool(ToolKind::Line), icons::TOOL_LINE.clone()),
+        (make_polygoner(send.clone()), icons::TOOL_POLYGON.clone()),
         (cb_set_tool(ToolKind::Mover), icons::TOOL_MOVER.clone()),
+        (
+            cb_set_tool(ToolKind::Redefine),
+            icons::TOOL_REDEFINE.clone(),
+        ),
+        (cb_set_tool(ToolKind::Attach), icons::TOOL_ATTACH.clone()),
+        (cb_set_tool(ToolKind::Locus), icons::TOOL_LOCUS.clone()),
+        (make_transformer(send.clone()), icons::TOOL_REFLECT.clone()),
+        (make_measurer(send.clone()), icons::TOOL_DISTANCE.clone()),
     ];
 
     ToolBar {
-        tools: tools,
+        tools,
         send_tool: send,
         selected: Some(0),
+        theme,
+        tabs: Vec::new(),
+        tab: 0,
     }
 }
 
+/// The button of a macro's tool
+pub fn macro_tool(definition: Macro) -> (MakeButton, Icon) {
+    let icon = definition.icon();
+    (cb_set_tool(ToolKind::Macro(definition)), icon)
+}
+
 fn cb_set_tool(kind: ToolKind) -> MakeButton {
-    MakeButton(Box::new(move || {
+    MakeButton(Box::new(move |_| {
         let kind = kind.clone();
         Button {
             function: Box::new(move |state| state.current_tool = kind.clone().into_tool()),
@@ -31,7 +56,7 @@ fn cb_set_tool(kind: ToolKind) -> MakeButton {
 }
 
 fn make_selector(send: Sender<Button>) -> MakeButton {
-    MakeButton(Box::new(move || {
+    MakeButton(Box::new(move |theme| {
         let tools = vec![
             (
                 make_vis_changer(gwrapper::Visibility::Visible),
@@ -41,12 +66,18 @@ fn make_selector(send: Sender<Button>) -> MakeButton {
                 make_vis_changer(gwrapper::Visibility::Hidden),
                 icons::SELECTED_HIDE.clone(),
             ),
+            (make_selection_inverter(), icons::TOOL_INVERT.clone()),
+            (make_trace_toggler(), icons::SELECTED_TRACE.clone()),
+            (make_trail_clearer(), icons::TRAILS_CLEAR.clone()),
         ];
 
         let subtoolbar = ToolBar {
-            tools: tools,
+            tools,
             send_tool: send.clone(),
             selected: None,
+            theme,
+            tabs: Vec::new(),
+            tab: 0,
         };
 
         Button {
@@ -57,8 +88,118 @@ fn make_selector(send: Sender<Button>) -> MakeButton {
     }))
 }
 
+fn make_polygoner(send: Sender<Button>) -> MakeButton {
+    MakeButton(Box::new(move |theme| {
+        let tools = vec![
+            (cb_set_tool(ToolKind::Polygon), icons::TOOL_POLYGON.clone()),
+            (
+                cb_set_tool(ToolKind::RegularPolygon(3)),
+                icons::TOOL_REGULAR_3.clone(),
+            ),
+            (
+                cb_set_tool(ToolKind::RegularPolygon(4)),
+                icons::TOOL_REGULAR_4.clone(),
+            ),
+            (
+                cb_set_tool(ToolKind::RegularPolygon(5)),
+                icons::TOOL_REGULAR_5.clone(),
+            ),
+            (
+                cb_set_tool(ToolKind::RegularPolygon(6)),
+                icons::TOOL_REGULAR_6.clone(),
+            ),
+            (
+                cb_set_tool(ToolKind::RegularPolygon(8)),
+                icons::TOOL_REGULAR_8.clone(),
+            ),
+        ];
+
+        let subtoolbar = ToolBar {
+            tools,
+            send_tool: send.clone(),
+            selected: None,
+            theme,
+            tabs: Vec::new(),
+            tab: 0,
+        };
+
+        Button {
+            function: Box::new(move |state| state.current_tool = ToolKind::Polygon.into_tool()),
+            select: true,
+            subtoolbar: Some(subtoolbar),
+        }
+    }))
+}
+
+fn make_transformer(send: Sender<Button>) -> MakeButton {
+    MakeButton(Box::new(move |theme| {
+        let tools = vec![
+            (cb_set_tool(ToolKind::Reflect), icons::TOOL_REFLECT.clone()),
+            (cb_set_tool(ToolKind::Rotate), icons::TOOL_ROTATE.clone()),
+            (
+                cb_set_tool(ToolKind::Translate),
+                icons::TOOL_TRANSLATE.clone(),
+            ),
+            (cb_set_tool(ToolKind::Dilate), icons::TOOL_DILATE.clone()),
+            (
+                cb_set_tool(ToolKind::DilateBySlider),
+                icons::TOOL_DILATE_SLIDER.clone(),
+            ),
+            (cb_set_tool(ToolKind::Invert), icons::TOOL_INVERT.clone()),
+        ];
+
+        let subtoolbar = ToolBar {
+            tools,
+            send_tool: send.clone(),
+            selected: None,
+            theme,
+            tabs: Vec::new(),
+            tab: 0,
+        };
+
+        Button {
+            function: Box::new(move |state| state.current_tool = ToolKind::Reflect.into_tool()),
+            select: true,
+            subtoolbar: Some(subtoolbar),
+        }
+    }))
+}
+
+fn make_measurer(send: Sender<Button>) -> MakeButton {
+    MakeButton(Box::new(move |theme| {
+        let tools = vec![
+            (
+                cb_set_tool(ToolKind::Distance),
+                icons::TOOL_DISTANCE.clone(),
+            ),
+            (cb_set_tool(ToolKind::Angle), icons::TOOL_ANGLE.clone()),
+            (cb_set_tool(ToolKind::Radius), icons::TOOL_RADIUS.clone()),
+            (cb_set_tool(ToolKind::Area), icons::TOOL_AREA.clone()),
+            (
+                cb_set_tool(ToolKind::Perimeter),
+                icons::TOOL_PERIMETER.clone(),
+            ),
+        ];
+
+        let subtoolbar = ToolBar {
+            tools,
+            send_tool: send.clone(),
+            selected: None,
+            theme,
+            tabs: Vec::new(),
+            tab: 0,
+        };
+
+        Button {
+            function: Box::new(move |state| state.current_tool = ToolKind::Distance.into_tool()),
+            select: true,
+            subtoolbar: Some(subtoolbar),
+        }
+    }))
+}
+
 fn make_vis_changer(status: gwrapper::Visibility) -> MakeButton {
-    MakeButton(Box::new(move || Button {
+    MakeButton(Box::new(move |_| Button {
         function: Box::new(move |state| {
             for (id, sel) in state.current_tool.selected(&state.world) {
                 if sel == SelectedStatus::Primary {
@@ -71,8 +212,73 @@ fn make_vis_changer(status: gwrapper::Visibility) -> MakeButton {
     }))
 }
 
+/// Toggles whether the selected points and shapes leave trails while dragging
+fn make_trace_toggler() -> MakeButton {
+    MakeButton(Box::new(move |_| Button {
+        function: Box::new(move |state| {
+            for (id, sel) in state.current_tool.selected(&state.world) {
+                match id {
+                    gwrapper::ThingID::PointID(_) | gwrapper::ThingID::ShapeID(_)
+                        if sel == SelectedStatus::Primary && !state.view.traced.remove(&id) =>
+                    {
+                        state.view.traced.insert(id);
+                    }
+                    _ => {}
+                }
+            }
+        }),
+        select: false,
+        subtoolbar: None,
+    }))
+}
+
+fn make_trail_clearer() -> MakeButton {
+    MakeButton(Box::new(move |_| Button {
+        function: Box::new(move |state| state.view.trails.clear()),
+        select: false,
+        subtoolbar: None,
+    }))
+}
+
+fn make_selection_inverter() -> MakeButton {
+    MakeButton(Box::new(move |_| Button {
+        function: Box::new(move |state| {
+            let objects = state
+                .current_tool
+                .selected(&state.world)
+                .into_iter()
+                .filter(|(_, sel)| sel == &SelectedStatus::Primary)
+                .map(|(id, _)| id)
+                .collect();
+            state.current_tool = Box::new(tools::InvertTool { objects });
+        }),
+        select: false,
+        subtoolbar: None,
+    }))
+}
+
+fn make_gridder() -> MakeButton {
+    MakeButton(Box::new(move |_| Button {
+        function: Box::new(move |state| {
+            state.view.show_grid = !state.view.show_grid;
+        }),
+        select: false,
+        subtoolbar: None,
+    }))
+}
+
+fn make_snapper() -> MakeButton {
+    MakeButton(Box::new(move |_| Button {
+        function: Box::new(move |state| {
+            state.view.snap_to_grid = !state.view.snap_to_grid;
+        }),
+        select: false,
+        subtoolbar: None,
+    }))
+}
+
 fn make_peeker() -> MakeButton {
-    MakeButton(Box::new(move || Button {
+    MakeButton(Box::new(move |_| Button {
         function: Box::new(move |state| {
             state.view.show_hidden = !state.view.show_hidden;
         }),
@@ -1,10 +1,11 @@
 mod default;
-pub use self::default::default_toolbar;
+pub use self::default::{default_toolbar, macro_tool};
 
 use std::sync::mpsc::Sender;
 
-use graphics::DState;
-use icons;
+use graphics::{text, DState};
+use icons::{self, Icon};
+use theme::Theme;
 use tool::ToolKind;
 
 use ytesrev::drawable::{DrawSettings, Drawable, KnownSize, Position, State};
@@ -14,6 +15,11 @@ use ytesrev::sdl2::mouse::MouseButton;
 use ytesrev::sdl2::rect::Rect;
 
 pub const TOOL_EDGE: u32 = 2;
+/// Space between the name on a tab and its edges
+pub const TAB_PADDING: u32 = 4;
+/// The size icons are drawn in, in window units, which are several pixels on high density
+/// displays
+pub const TOOL_SIZE: u32 = icons::ICON_SIZE as u32;
 
 pub struct Button {
     pub function: Box<Fn(&mut DState)>,
@@ -24,22 +30,29 @@ pub struct Button {
 unsafe impl Send for Button {}
 unsafe impl Sync for Button {}
 
-pub struct MakeButton(Box<Fn() -> Button>);
+/// Makes a button, whose subtoolbar has the given theme
+pub struct MakeButton(Box<Fn(Theme) -> Button>);
 
 unsafe impl Send for MakeButton {}
 unsafe impl Sync for MakeButton {}
 
 pub struct ToolBar {
-    pub tools: Vec<(MakeButton, PngImage)>,
+    pub tools: Vec<(MakeButton, Icon)>,
     pub send_tool: Sender<Button>,
     pub selected: Option<usize>,
+    /// The active theme, which subtoolbars are made with
+    pub theme: Theme,
+    /// The names of the open constructions, shown below the tools. Subtoolbars have no tabs.
+    pub tabs: Vec<String>,
+    /// The tab being shown
+    pub tab: usize,
 }
 
 impl ToolBar {
     pub fn mouse_down(&mut self, position: Point, mbutton: MouseButton) {
         for (i, (rect, (tool, _))) in self.tool_rects().iter().zip(self.tools.iter()).enumerate() {
             if rect.contains_point(position) {
-                let callback = (*tool.0)();
+                let callback = (*tool.0)(self.theme);
 
                 if callback.select {
                     self.selected = Some(i);
@@ -49,8 +62,20 @@ impl ToolBar {
             }
         }
 
+        for (i, rect) in self.tab_rects().iter().enumerate() {
+            if rect.contains_point(position) {
+                self.send_tool
+                    .send(Button {
+                        function: Box::new(move |state| state.switch_tab(i)),
+                        select: false,
+                        subtoolbar: None,
+                    })
+                    .expect("Couldn't send tool!");
+            }
+        }
+
         if let Some((tool, _)) = self.selected.and_then(|x| self.tools.get(x)) {
-            let button = (*tool.0)();
+            let button = (*tool.0)(self.theme);
             if let Some(mut subbar) = button.subtoolbar {
                 subbar.mouse_down(
                     Point::new(position.x(), position.y() - self.content_height() as i32),
@@ -64,15 +89,26 @@ impl ToolBar {
         let mut x = TOOL_EDGE as i32;
 
         let mut res = Vec::new();
-        for (_, image) in self.tools.iter() {
-            res.push(Rect::new(
-                x,
-                TOOL_EDGE as i32,
-                image.width() as u32,
-                image.height() as u32,
-            ));
-
-            x += image.width() as i32 + 10;
+        for _ in &self.tools {
+            res.push(Rect::new(x, TOOL_EDGE as i32, TOOL_SIZE, TOOL_SIZE));
+
+            x += TOOL_SIZE as i32 + 10;
+        }
+
+        res
+    }
+
+    /// Where the tabs are, in a row below the tools
+    pub fn tab_rects(&self) -> Vec<Rect> {
+        let mut x = TOOL_EDGE as i32;
+        let y = self.tools_height() as i32;
+
+        let mut res = Vec::new();
+        for name in &self.tabs {
+            let (w, h) = text::text_size(name);
+            let rect = Rect::new(x, y, w + 2 * TAB_PADDING, h + 2 * TAB_PADDING);
+            x = rect.right() + TOOL_EDGE as i32;
+            res.push(rect);
         }
 
         res
@@ -82,11 +118,11 @@ impl ToolBar {
         &self,
         canvas: &mut Canvas<Window>,
         at: Rect,
-        settings: DrawSettings,
+        theme: &Theme,
     ) -> Result<(), String> {
-        canvas.set_draw_color(Color::RGBA(38, 62, 99, 255));
+        canvas.set_draw_color(theme.toolbar_edge);
         canvas.fill_rect(at)?;
-        canvas.set_draw_color(Color::RGBA(162, 184, 219, 255));
+        canvas.set_draw_color(theme.toolbar);
         canvas.fill_rect(Rect::new(
             at.left() + TOOL_EDGE as i32,
             at.top() + TOOL_EDGE as i32,
@@ -94,7 +130,7 @@ impl ToolBar {
             at.height() - TOOL_EDGE * 2,
         ))?;
 
-        for (i, (rect, (_, image))) in self.tool_rects().iter().zip(self.tools.iter()).enumerate() {
+        for (i, (rect, (_, icon))) in self.tool_rects().iter().zip(self.tools.iter()).enumerate() {
             let rect = Rect::new(
                 at.left() + rect.left(),
                 at.top() + rect.top(),
@@ -103,9 +139,9 @@ impl ToolBar {
             );
 
             if Some(i) == self.selected {
-                canvas.set_draw_color(Color::RGBA(140, 120, 100, 255));
+                canvas.set_draw_color(theme.tool_selected_edge);
                 canvas.fill_rect(rect)?;
-                canvas.set_draw_color(Color::RGBA(245, 230, 230, 255));
+                canvas.set_draw_color(theme.tool_selected);
                 canvas.fill_rect(Rect::new(
                     rect.x() + TOOL_EDGE as i32,
                     rect.y() + TOOL_EDGE as i32,
@@ -114,13 +150,51 @@ impl ToolBar {
                 ))?;
             }
 
-            image.draw(canvas, &Position::TopLeftCorner(rect.top_left()), settings);
+            draw_icon(canvas, icon, rect, theme.foreground)?;
+        }
+
+        for (i, (rect, name)) in self.tab_rects().iter().zip(self.tabs.iter()).enumerate() {
+            let rect = Rect::new(
+                at.left() + rect.left(),
+                at.top() + rect.top(),
+                rect.width(),
+                rect.height(),
+            );
+            let (edge, fill) = if i == self.tab {
+                (theme.tool_selected_edge, theme.tool_selected)
+            } else {
+                (theme.toolbar_edge, theme.toolbar)
+            };
+            canvas.set_draw_color(edge);
+            canvas.fill_rect(rect)?;
+            canvas.set_draw_color(fill);
+            canvas.fill_rect(Rect::new(
+                rect.x() + 1,
+                rect.y() + 1,
+                rect.width() - 2,
+                rect.height() - 2,
+            ))?;
+            canvas.set_draw_color(theme.foreground);
+            text::draw_text(
+                canvas,
+                name,
+                (rect.x() + TAB_PADDING as i32, rect.y() + TAB_PADDING as i32),
+            )?;
         }
 
         Ok(())
     }
 
     fn content_height(&self) -> usize {
+        self.tab_rects()
+            .iter()
+            .map(|x| x.bottom() as usize + TOOL_EDGE as usize)
+            .max()
+            .unwrap_or_else(|| self.tools_height())
+    }
+
+    /// The height of the row of tools
+    fn tools_height(&self) -> usize {
         self.tool_rects()
             .iter()
             .map(|x| x.bottom())
@@ -130,6 +204,26 @@ impl ToolBar {
     }
 }
 
+/// Draws an icon in full resolution, rather than scaled up with the rest of the window
+fn draw_icon(
+    canvas: &mut Canvas<Window>,
+    icon: &Icon,
+    rect: Rect,
+    foreground: Color,
+) -> Result<(), String> {
+    let (sx, sy) = canvas.scale();
+    let pixels = Rect::new(
+        (rect.x() as f32 * sx) as i32,
+        (rect.y() as f32 * sy) as i32,
+        (rect.width() as f32 * sx) as u32,
+        (rect.height() as f32 * sy) as u32,
+    );
+    canvas.set_scale(1., 1.)?;
+    let drawn = icon.draw(canvas, pixels, foreground);
+    canvas.set_scale(sx, sy)?;
+    drawn
+}
+
 impl Drawable for ToolBar {
     fn content(&self) -> Vec<&Drawable> {
         Vec::new()
@@ -145,14 +239,14 @@ impl Drawable for ToolBar {
         State::Working
     }
 
-    fn draw(&self, canvas: &mut Canvas<Window>, _position: &Position, settings: DrawSettings) {
+    fn draw(&self, canvas: &mut Canvas<Window>, _position: &Position, _settings: DrawSettings) {
         let rect = Rect::new(0, 0, canvas.window().size().0, self.content_height() as u32);
 
-        self.draw_menu(canvas, rect, settings)
+        self.draw_menu(canvas, rect, &self.theme)
             .expect("Can't draw toolbar");
 
         if let Some((tool, _)) = self.selected.and_then(|x| self.tools.get(x)) {
-            let button = (*tool.0)();
+            let button = (*tool.0)(self.theme);
             if let Some(subbar) = button.subtoolbar {
                 subbar
                     .draw_menu(
@@ -161,9 +255,9 @@ impl Drawable for ToolBar {
                             rect.left(),
                             rect.top() + rect.height() as i32,
                             rect.width(),
-                            rect.height(),
+                            subbar.content_height() as u32,
                         ),
-                        settings,
+                        &subbar.theme,
                     ).expect("Can't draw toolbar");
             }
         }
@@ -185,7 +279,7 @@ impl KnownSize for ToolBar {
 
     fn height(&self) -> usize {
         if let Some((tool, _)) = self.selected.and_then(|x| self.tools.get(x)) {
-            let button = (*tool.0)();
+            let button = (*tool.0)(self.theme);
             if let Some(subbar) = button.subtoolbar {
                 return self.content_height() + subbar.height();
             }
@@ -1,3 +1,15 @@
+use std::f64;
+
+/// The smallest distance between grid lines, in pixels
+pub const MIN_GRID_SPACING: f64 = 40.;
+/// The space left around everything by `fit`, in pixels
+pub const FIT_MARGIN: f64 = 80.;
+/// How much points grow with the zoom. Points grow much slower than the figure, so they stay
+/// visible when zoomed out without covering it when zoomed in.
+pub const POINT_ZOOM_POWER: f64 = 0.25;
+pub const MIN_POINT_ZOOM: f64 = 0.6;
+pub const MAX_POINT_ZOOM: f64 = 2.;
+
 #[derive(Debug)]
 pub struct Transform {
     pub win_size: (f64, f64),
@@ -27,4 +39,156 @@ impl Transform {
         let (tx, ty) = (stx / self.scale, sty / self.scale);
         (tx - self.translation.0, ty - self.translation.1)
     }
+
+    /// Zooms in by `factor`, keeping what's at `anchor` (in pixels) in place
+    pub fn zoom_at(&mut self, factor: f64, anchor: (f64, f64)) {
+        let fixed = self.transform_px_to_po(anchor);
+        self.scale *= factor;
+        self.translation = (
+            (anchor.0 - self.win_size.0 / 2.) / self.scale - fixed.0,
+            (anchor.1 - self.win_size.1 / 2.) / self.scale - fixed.1,
+        );
+    }
+
+    /// Moves the view by `(dx, dy)` pixels
+    pub fn pan(&mut self, (dx, dy): (f64, f64)) {
+        self.translation = (
+            self.translation.0 + dx / self.scale,
+            self.translation.1 + dy / self.scale,
+        );
+    }
+
+    /// Centers and zooms so that everything between the corners `min` and `max` is shown. A
+    /// single point is only centered.
+    pub fn fit(&mut self, min: (f64, f64), max: (f64, f64)) {
+        let (width, height) = (max.0 - min.0, max.1 - min.1);
+        let scales = [
+            (self.win_size.0 - 2. * FIT_MARGIN) / width,
+            (self.win_size.1 - 2. * FIT_MARGIN) / height,
+        ];
+        let scale = scales
+            .iter()
+            .cloned()
+            .filter(|scale| scale.is_finite() && *scale > 0.)
+            .fold(f64::INFINITY, f64::min);
+        if scale.is_finite() {
+            self.scale = scale;
+        }
+        self.translation = (-(min.0 + max.0) / 2., -(min.1 + max.1) / 2.);
+    }
+
+    /// The distance between grid lines in the plane, 1, 2 or 5 times a power of ten, so that
+    /// the lines are at least MIN_GRID_SPACING pixels apart
+    pub fn grid_spacing(&self) -> f64 {
+        let min = MIN_GRID_SPACING / self.scale;
+        let power = 10_f64.powf(min.log10().floor());
+        [1., 2., 5.]
+            .iter()
+            .map(|factor| factor * power)
+            .find(|spacing| *spacing >= min)
+            .unwrap_or(10. * power)
+    }
+
+    /// How many times bigger than their size points are drawn, 1 at the zoom a window starts at
+    pub fn point_zoom(&self) -> f64 {
+        let start = Transform::new_from_winsize(self.win_size).scale;
+        (self.scale / start)
+            .powf(POINT_ZOOM_POWER)
+            .clamp(MIN_POINT_ZOOM, MAX_POINT_ZOOM)
+    }
+
+    /// The closest intersection of grid lines
+    pub fn snap_to_grid(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let spacing = self.grid_spacing();
+        (
+            (x / spacing).round() * spacing,
+            (y / spacing).round() * spacing,
+        )
+    }
+}
+
+#[test]
+fn test_grid_spacing() {
+    let mut transform = Transform::new_from_winsize((800., 600.));
+    for &(scale, spacing) in &[
+        (40., 1.),
+        (30., 2.),
+        (9., 5.),
+        (7.9, 10.),
+        (150., 0.5),
+        (1e4, 0.005),
+    ] {
+        transform.scale = scale;
+        assert!(
+            (transform.grid_spacing() - spacing).abs() < 1e-9 * spacing,
+            "{}",
+            scale
+        );
+    }
+
+    transform.scale = 30.;
+    assert_eq!(transform.snap_to_grid((2.9, -0.9)), (2., 0.));
+    assert_eq!(transform.snap_to_grid((3.1, 5.2)), (4., 6.));
+}
+
+#[test]
+fn test_point_zoom() {
+    let mut transform = Transform::new_from_winsize((800., 600.));
+    assert_eq!(transform.point_zoom(), 1.);
+    transform.scale *= 16.;
+    assert!((transform.point_zoom() - 2.).abs() < 1e-9);
+    transform.scale /= 16. * 16.;
+    assert_eq!(transform.point_zoom(), MIN_POINT_ZOOM);
+}
+
+#[test]
+fn test_fit() {
+    let mut transform = Transform::new_from_winsize((800., 600.));
+    transform.fit((-10., 5.), (30., 15.));
+    let (a, b) = (
+        transform.transform_po_to_px((-10., 5.)),
+        transform.transform_po_to_px((30., 15.)),
+    );
+    assert!((a.0 - FIT_MARGIN).abs() < 1e-9 && (b.0 - (800. - FIT_MARGIN)).abs() < 1e-9);
+    assert!(a.1 >= FIT_MARGIN && b.1 <= 600. - FIT_MARGIN);
+
+    // A single point is centered without zooming
+    let scale = transform.scale;
+    transform.fit((3., 4.), (3., 4.));
+    assert_eq!(transform.scale, scale);
+    assert_eq!(transform.transform_po_to_px((3., 4.)), (400., 300.));
+}
+
+#[cfg(test)]
+quickcheck! {
+    fn check_round_trip(scale: f64, tx: f64, ty: f64, x: f64, y: f64) -> bool {
+        let mut transform = Transform::new_from_winsize((800., 600.));
+        transform.scale = scale.abs().max(1e-3);
+        transform.translation = (tx, ty);
+
+        let (x_, y_) = transform.transform_px_to_po(transform.transform_po_to_px((x, y)));
+        let tolerance = 1e-9 * x.abs().max(y.abs()).max(tx.abs()).max(ty.abs()).max(1.);
+        (x - x_).abs() < tolerance && (y - y_).abs() < tolerance
+    }
+
+    fn check_zoom_keeps_anchor(factor: f64, ax: f64, ay: f64, tx: f64, ty: f64) -> bool {
+        let mut transform = Transform::new_from_winsize((800., 600.));
+        transform.translation = (tx, ty);
+        let anchor = (ax.abs() % 800., ay.abs() % 600.);
+        let fixed = transform.transform_px_to_po(anchor);
+
+        transform.zoom_at(factor.abs() % 10. + 0.1, anchor);
+        let (x, y) = transform.transform_po_to_px(fixed);
+        let tolerance = 1e-6 * tx.abs().max(ty.abs()).max(1.);
+        (x - anchor.0).abs() < tolerance && (y - anchor.1).abs() < tolerance
+    }
+
+    fn check_pan(dx: f64, dy: f64, x: f64, y: f64) -> bool {
+        let mut transform = Transform::new_from_winsize((800., 600.));
+        let before = transform.transform_po_to_px((x, y));
+        transform.pan((dx, dy));
+        let after = transform.transform_po_to_px((x, y));
+        let tolerance = 1e-9 * dx.abs().max(dy.abs()).max(x.abs()).max(y.abs()).max(1.);
+        (after.0 - before.0 - dx).abs() < tolerance && (after.1 - before.1 - dy).abs() < tolerance
+    }
 }